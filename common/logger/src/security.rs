@@ -58,6 +58,9 @@ pub enum SecurityEvent {
     /// Invalid chunk of transactions received
     StateSyncInvalidChunk,
 
+    /// Chunk of transactions received that doesn't extend the local transaction accumulator
+    StateSyncForkDetected,
+
     // Health Checker
     // --------------
     /// HealthChecker received an invalid network event
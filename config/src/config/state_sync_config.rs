@@ -6,38 +6,370 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct StateSyncConfig {
-    // Size of chunk to request for state synchronization
-    pub chunk_limit: u64,
+    // if set, the long poll timeout sent to remote peers is scaled to the observed interval
+    // between local commits (bounded by `max_timeout_ms`) instead of `long_poll_timeout_ms`
+    pub adaptive_long_poll_timeout: bool,
+    // whether the optional protocol features supported by this node are advertised to the newly
+    // connected peers (which only enables them with the peers advertising them too); off by
+    // default as the nodes predating the capabilities message warn about it on every connection
+    pub advertise_capabilities: bool,
+    // interval used for checkpointing the in-memory coordinator state to storage, so that it's
+    // resumed upon restart, 0 disables checkpointing
+    pub checkpoint_interval_ms: u64,
+    // Size of chunk to request for state synchronization, unset means the value of `profile`
+    pub chunk_limit: Option<u64>,
+    // number of consecutive failures to apply chunks after which the requested chunk limit is
+    // halved (down to `min_chunk_limit`), 0 disables the downgrade
+    pub chunk_limit_downgrade_failures: u64,
+    // whether the penalty of an upstream peer sending stale chunks in a row escalates (the n-th
+    // one multiplies its score by 0.5^n), otherwise they are penalized like the other chunks that
+    // can't be applied
+    pub escalate_stale_chunk_penalty: bool,
+    // whether a consensus commit whose notification mempool didn't ACK within
+    // `mempool_commit_ack_timeout_ms` is reported to consensus as failed (otherwise, only an
+    // error message is reported along with the successful commit)
+    pub fail_commit_on_mempool_timeout: bool,
+    // interval used for checking state synchronization progress while idle and caught up (at
+    // least `tick_interval_ms`, which is used while actively syncing), 0 means always ticking at
+    // `tick_interval_ms`
+    pub idle_tick_interval_ms: u64,
+    // whether the reconfiguration events of a commit are journaled until they are published, so
+    // that they are published again on startup if the node crashed in between
+    pub journal_reconfig_events: bool,
     // default timeout used for long polling to remote peer
     pub long_poll_timeout_ms: u64,
     // valid maximum chunk limit for sanity check
     pub max_chunk_limit: u64,
+    // max size of the encoded transactions of a served chunk, beyond which the chunk is shrunk
+    // (a single transaction is served regardless), 0 means no limit
+    pub max_chunk_response_bytes: u64,
+    // max number of applied chunks whose commit notification to mempool may await an ACK
+    // before the node stops applying new chunks, 0 awaits the ACK of each chunk commit in turn;
+    // unset means the value of `profile`
+    pub max_commit_pipeline_depth: Option<usize>,
+    // max number of chunk requests served concurrently off the coordinator loop, requests beyond
+    // it are answered with an empty chunk; 0 serves the chunks inline on the coordinator loop,
+    // unset means the value of `profile`
+    pub max_concurrent_chunk_serves: Option<usize>,
+    // max number of epoch-ending LI lookups per second while serving chunk requests, requests
+    // beyond it are answered with an empty chunk; 0 means no limit
+    pub max_epoch_lookups_per_sec: u64,
+    // max number of epoch-ending LIs to prefetch along with the chunks requested during waypoint
+    // sync, 0 disables prefetching (only requested from the upstream peers advertising support),
+    // unset means the value of `profile`
+    pub max_epoch_prefetch: Option<u64>,
+    // max serialized size in bytes of the pending ledger info's kept in memory (their size grows
+    // with the validator set), the lowest versions are evicted first, 0 means no limit
+    pub max_pending_li_bytes: usize,
     // max number of pending ledger info's to keep in memory
     // This is to prevent OOM
     pub max_pending_li_limit: usize,
+    // max number of chunks received ahead of the local version that are buffered until the
+    // chunks preceding them are applied, 0 disables buffering (such chunks are rejected)
+    pub max_reorder_buffer_chunks: usize,
+    // ceiling of the chunk request retry timeout, which is doubled on every consecutive timeout of
+    // the request for the same version, no backoff if not above the base retry timeout
+    pub max_retry_timeout_ms: u64,
+    // max number of long poll subscriptions delivered to at once, the remaining ready ones are
+    // delivered to later (oldest first), 0 means no limit
+    pub max_subscription_deliveries: usize,
+    // max estimated memory in bytes held by the pending long poll subscriptions, the oldest
+    // subscriptions are shed when it's exceeded, 0 means no limit
+    pub max_subscription_memory_bytes: usize,
+    // max number of versions the known version of a long poll subscriber may be ahead of the
+    // local committed version, the subscriptions further ahead are rejected, 0 means no limit
+    pub max_subscription_version_margin: u64,
+    // max number of chunk requests sent for a sync request without the synced version advancing
+    // before the sync request is failed, 0 means no limit
+    pub max_sync_attempts: u64,
+    // max number of epochs the target of a sync request may be ahead of the local epoch, 0 means
+    // no limit
+    pub max_sync_request_epochs: u64,
     // valid maximum timeout limit for sanity check
     pub max_timeout_ms: u64,
+    // max age (relative to wall-clock) of the freshest signed LI carried by a chunk response
+    // before its sender is considered a stale upstream and deprioritized, 0 disables the check
+    pub max_upstream_staleness_ms: u64,
+    // max time to wait for mempool to ACK a commit notification
+    pub mempool_commit_ack_timeout_ms: u64,
+    // number of committed user transactions of applied chunks that are accumulated before mempool
+    // is notified of them at once (pending ones are also flushed on every tick and before any
+    // consensus commit), 0 disables batching
+    pub mempool_commit_batch_size: usize,
+    // score (out of 100) below which an upstream peer is only sent chunk requests when none of
+    // the peers of its network is above it, 0 disables it
+    pub min_available_peer_score: u64,
+    // floor of the requested chunk limit when it's downgraded upon failures to apply chunks
+    pub min_chunk_limit: u64,
+    // floor of the max long poll timeout granted to subscribers when the subscriptions are close
+    // to their capacity (see `subscription_load_threshold_pct`), 0 disables the adaptation
+    pub min_subscription_timeout_ms: u64,
+    // number of enabled upstream peers below which the low peer count sink (if any) is signalled
+    // to seek more peers, 0 disables the signal
+    pub min_upstream_peers: usize,
     // default timeout to make state sync progress by sending chunk requests to a certain number of networks
     // if no progress is made by sending chunk requests to a number of networks,
-    // the next sync request will be multicasted, i.e. sent to more networks
-    pub multicast_timeout_ms: u64,
+    // the next sync request will be multicasted, i.e. sent to more networks,
+    // unset means the value of `profile`
+    pub multicast_timeout_ms: Option<u64>,
+    // if set, the chunk requests of downstream peers already pending are served before each
+    // progress check, so that serving isn't starved under heavy load
+    pub prioritize_serving: bool,
+    // catch-up profile the fields left unset are tuned to (see `CatchUpProfile`), the explicitly
+    // configured values take precedence
+    pub profile: CatchUpProfile,
+    // if set, the chunk requests a read replica (if any) cannot serve yet because it lags behind
+    // are served from the primary storage, else the requester is sent an empty response
+    pub read_replica_fallback: bool,
+    // if set, the time spent receiving, verifying and storing each applied chunk is kept for the
+    // recently applied chunks
+    pub record_chunk_timings: bool,
+    // whether chunks whose proof doesn't extend the local transaction accumulator (e.g. a fork) are
+    // rejected before being executed
+    pub reject_forked_chunks: bool,
+    // if set, chunk responses arriving on a network the matching chunk request was never sent on
+    // are rejected
+    pub reject_network_mismatch_responses: bool,
+    // if set, chunk responses whose LI epoch is behind the local epoch (the upstream is lagging)
+    // are rejected before being verified, with a lighter penalty than invalid responses
+    pub reject_stale_epoch_responses: bool,
+    // if set, the ready long poll subscribers are checked to still be eligible to be served (still
+    // connected and allowed by the current serving policy) before the delivery, the ineligible
+    // ones are dropped
+    pub revalidate_subscribers: bool,
+    // whether to verify the waypoint again against the local storage when the committed state is
+    // observed moving backward (e.g. after a storage corruption or an external reset)
+    pub reverify_waypoint_on_regression: bool,
+    // if set, a commit that doesn't advance the synced version neither notifies mempool nor
+    // checks the long poll subscriptions
+    pub skip_noop_commit_notifications: bool,
+    // percentage of the subscription capacity (derived from `max_subscription_memory_bytes`)
+    // beyond which the max long poll timeout is lowered towards `min_subscription_timeout_ms`
+    pub subscription_load_threshold_pct: u64,
+    // percentage of a long poll subscription's timeout after which a subscription that is far
+    // behind the local LI is considered stale
+    pub subscription_staleness_pct: u64,
+    // number of the highest scored upstream peers a chunk request is sent to at once while syncing
+    // to a sync request target, 1 means a single peer (plus the multicast to failover networks),
+    // unset means the value of `profile`
+    pub sync_request_fanout: Option<usize>,
+    // timeout of the long poll the chunk requests for a sync request target are held for by
+    // upstream while it has nothing newer than this node, 0 means no long poll
+    pub sync_request_long_poll_timeout_ms: u64,
     // default timeout for sync request
     pub sync_request_timeout_ms: u64,
     // interval used for checking state synchronization progress
     pub tick_interval_ms: u64,
+    // if set, the responses received while syncing to a target are rejected if their LI is
+    // ahead of the epoch of the highest local LI, unless that LI ends its epoch and the response
+    // LI is in the very next epoch
+    pub validate_sync_request_epochs: bool,
+    // whether a warm-up probe is sent to newly enabled upstream peers, so that the connection is
+    // established before the first real chunk request
+    pub warm_up_new_peers: bool,
+    // max time for reaching the waypoint before the initialization listeners are failed (the node
+    // keeps syncing to the waypoint), 0 means no limit
+    pub waypoint_init_timeout_ms: u64,
+    // number of distinct peers whose LIs at the same version fail the waypoint verification
+    // before the waypoint is deemed misconfigured and the initialization listeners are failed (the
+    // node keeps syncing to the waypoint), 0 disables the detection
+    pub waypoint_mismatch_peer_threshold: usize,
 }
 
 impl Default for StateSyncConfig {
     fn default() -> Self {
         Self {
-            chunk_limit: 250,
+            adaptive_long_poll_timeout: false,
+            advertise_capabilities: false,
+            checkpoint_interval_ms: 0,
+            chunk_limit: None,
+            chunk_limit_downgrade_failures: 0,
+            escalate_stale_chunk_penalty: false,
+            fail_commit_on_mempool_timeout: false,
+            idle_tick_interval_ms: 0,
+            journal_reconfig_events: false,
             long_poll_timeout_ms: 10_000,
             max_chunk_limit: 1000,
+            max_chunk_response_bytes: 0,
+            max_commit_pipeline_depth: None,
+            max_concurrent_chunk_serves: None,
+            max_epoch_lookups_per_sec: 0,
+            max_epoch_prefetch: None,
+            max_pending_li_bytes: 0,
             max_pending_li_limit: 1000,
+            max_reorder_buffer_chunks: 0,
+            max_retry_timeout_ms: 0,
+            max_subscription_deliveries: 0,
+            max_subscription_memory_bytes: 0,
+            max_subscription_version_margin: 0,
+            max_sync_attempts: 0,
+            max_sync_request_epochs: 0,
             max_timeout_ms: 120_000,
-            multicast_timeout_ms: 30_000,
+            max_upstream_staleness_ms: 0,
+            mempool_commit_ack_timeout_ms: 5_000,
+            mempool_commit_batch_size: 0,
+            min_available_peer_score: 0,
+            min_chunk_limit: 10,
+            min_subscription_timeout_ms: 0,
+            min_upstream_peers: 0,
+            multicast_timeout_ms: None,
+            prioritize_serving: false,
+            profile: CatchUpProfile::Balanced,
+            read_replica_fallback: true,
+            record_chunk_timings: false,
+            reject_forked_chunks: false,
+            reject_network_mismatch_responses: false,
+            reject_stale_epoch_responses: false,
+            revalidate_subscribers: false,
+            reverify_waypoint_on_regression: false,
+            skip_noop_commit_notifications: false,
+            subscription_load_threshold_pct: 50,
+            subscription_staleness_pct: 50,
+            sync_request_fanout: None,
+            sync_request_long_poll_timeout_ms: 0,
             sync_request_timeout_ms: 60_000,
             tick_interval_ms: 100,
+            validate_sync_request_epochs: false,
+            warm_up_new_peers: false,
+            waypoint_init_timeout_ms: 0,
+            waypoint_mismatch_peer_threshold: 3,
         }
     }
 }
+
+impl StateSyncConfig {
+    /// Returns the default config tuned to the given catch-up profile.
+    /// Individual fields can still be overridden on the returned config.
+    pub fn with_profile(profile: CatchUpProfile) -> Self {
+        Self {
+            profile,
+            ..Self::default()
+        }
+    }
+
+    pub fn chunk_limit(&self) -> u64 {
+        self.chunk_limit.unwrap_or(match self.profile {
+            CatchUpProfile::Conservative => 100,
+            CatchUpProfile::Balanced => 250,
+            CatchUpProfile::Aggressive => 1000,
+        })
+    }
+
+    pub fn max_commit_pipeline_depth(&self) -> usize {
+        self.max_commit_pipeline_depth
+            .unwrap_or(match self.profile {
+                CatchUpProfile::Conservative | CatchUpProfile::Balanced => 0,
+                CatchUpProfile::Aggressive => 50,
+            })
+    }
+
+    pub fn max_concurrent_chunk_serves(&self) -> usize {
+        self.max_concurrent_chunk_serves
+            .unwrap_or(match self.profile {
+                CatchUpProfile::Conservative => 2,
+                CatchUpProfile::Balanced => 0,
+                CatchUpProfile::Aggressive => 16,
+            })
+    }
+
+    pub fn max_epoch_prefetch(&self) -> u64 {
+        self.max_epoch_prefetch.unwrap_or(match self.profile {
+            CatchUpProfile::Conservative | CatchUpProfile::Balanced => 0,
+            CatchUpProfile::Aggressive => 10,
+        })
+    }
+
+    pub fn multicast_timeout_ms(&self) -> u64 {
+        self.multicast_timeout_ms.unwrap_or(match self.profile {
+            CatchUpProfile::Conservative => 60_000,
+            CatchUpProfile::Balanced => 30_000,
+            CatchUpProfile::Aggressive => 5_000,
+        })
+    }
+
+    pub fn sync_request_fanout(&self) -> usize {
+        self.sync_request_fanout.unwrap_or(match self.profile {
+            CatchUpProfile::Conservative | CatchUpProfile::Balanced => 1,
+            CatchUpProfile::Aggressive => 3,
+        })
+    }
+}
+
+/// High-level presets for how eagerly a node catches up with its upstream peers.
+///
+/// | profile        | chunk_limit | max_commit_pipeline_depth | max_concurrent_chunk_serves | max_epoch_prefetch | multicast_timeout_ms | sync_request_fanout |
+/// |----------------|-------------|---------------------------|-----------------------------|--------------------|----------------------|---------------------|
+/// | `Conservative` | 100         | 0                         | 2                           | 0                  | 60_000               | 1                   |
+/// | `Balanced`     | 250         | 0                         | 0                           | 0                  | 30_000               | 1                   |
+/// | `Aggressive`   | 1000        | 50                        | 16                          | 10                 | 5_000                | 3                   |
+///
+/// `Balanced` is the default. The profile of a node is picked with the `profile` field of its
+/// config, and applies to the fields above that are left unset.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpProfile {
+    // small chunks, a shallow commit pipeline, few concurrent chunk serves and late
+    // multicasting, for resource constrained nodes
+    Conservative,
+    // the defaults
+    Balanced,
+    // large chunks, a deep commit pipeline, epoch prefetching, chunk requests fanned out to
+    // several peers and early multicasting, for nodes that are far behind
+    Aggressive,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_catch_up_profiles() {
+        let config = StateSyncConfig::with_profile(CatchUpProfile::Conservative);
+        assert_eq!(config.chunk_limit(), 100);
+        assert_eq!(config.max_commit_pipeline_depth(), 0);
+        assert_eq!(config.max_concurrent_chunk_serves(), 2);
+        assert_eq!(config.max_epoch_prefetch(), 0);
+        assert_eq!(config.multicast_timeout_ms(), 60_000);
+        assert_eq!(config.sync_request_fanout(), 1);
+
+        assert_eq!(
+            StateSyncConfig::with_profile(CatchUpProfile::Balanced),
+            StateSyncConfig::default()
+        );
+
+        let config = StateSyncConfig::with_profile(CatchUpProfile::Aggressive);
+        assert_eq!(config.chunk_limit(), 1000);
+        assert_eq!(config.max_commit_pipeline_depth(), 50);
+        assert_eq!(config.max_concurrent_chunk_serves(), 16);
+        assert_eq!(config.max_epoch_prefetch(), 10);
+        assert_eq!(config.multicast_timeout_ms(), 5_000);
+        assert_eq!(config.sync_request_fanout(), 3);
+        // the chunk limit of a profile must pass the sanity check
+        assert!(config.chunk_limit() <= config.max_chunk_limit);
+    }
+
+    #[test]
+    fn test_profile_overrides() {
+        // the profile picked in the config applies to the fields left unset
+        let config: StateSyncConfig =
+            serde_yaml::from_str("profile: aggressive\nchunk_limit: 500\n").unwrap();
+        assert_eq!(config.profile, CatchUpProfile::Aggressive);
+        assert_eq!(config.chunk_limit(), 500);
+        assert_eq!(config.max_commit_pipeline_depth(), 50);
+        assert_eq!(config.max_concurrent_chunk_serves(), 16);
+        assert_eq!(config.sync_request_fanout(), 3);
+        assert_eq!(
+            config.tick_interval_ms,
+            StateSyncConfig::default().tick_interval_ms
+        );
+
+        // an explicit value wins even if it's the value of the default profile
+        let config: StateSyncConfig = serde_yaml::from_str(
+            "profile: conservative\nchunk_limit: 250\nmulticast_timeout_ms: 30000\n",
+        )
+        .unwrap();
+        assert_eq!(config.chunk_limit(), 250);
+        assert_eq!(config.multicast_timeout_ms(), 30_000);
+        assert_eq!(config.max_concurrent_chunk_serves(), 2);
+    }
+}
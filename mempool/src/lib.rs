@@ -61,7 +61,7 @@ mod tests;
 pub use shared_mempool::{
     bootstrap, network,
     types::{
-        gen_mempool_reconfig_subscription, CommitNotification, CommitResponse,
+        gen_mempool_reconfig_subscription, CommitNotification, CommitResponse, CommitSource,
         CommittedTransaction, ConsensusRequest, ConsensusResponse, MempoolClientSender,
         SubmissionStatus, TransactionExclusion,
     },
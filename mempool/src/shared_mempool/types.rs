@@ -188,6 +188,17 @@ pub struct CommitNotification {
     pub block_timestamp_usecs: u64,
     /// callback to send back response from mempool to State Sync
     pub callback: oneshot::Sender<Result<CommitResponse>>,
+    /// what drove the commit
+    pub source: CommitSource,
+}
+
+/// Source of a commit notified by state sync
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommitSource {
+    /// transactions committed by consensus
+    Consensus,
+    /// transactions synced from other nodes
+    StateSync,
 }
 
 impl fmt::Display for CommitNotification {
@@ -198,8 +209,8 @@ impl fmt::Display for CommitNotification {
         }
         write!(
             f,
-            "CommitNotification [block_timestamp_usecs: {}, txns: {}]",
-            self.block_timestamp_usecs, txns
+            "CommitNotification [block_timestamp_usecs: {}, source: {:?}, txns: {}]",
+            self.block_timestamp_usecs, self.source, txns
         )
     }
 }
@@ -7,7 +7,7 @@ use crate::{
     network::{MempoolNetworkEvents, MempoolNetworkSender, MempoolSyncMsg},
     shared_mempool::{start_shared_mempool, types::SharedMempoolNotification},
     tests::common::{batch_add_signed_txn, TestTransaction},
-    CommitNotification, CommittedTransaction, ConsensusRequest,
+    CommitNotification, CommitSource, CommittedTransaction, ConsensusRequest,
 };
 use channel::{self, libra_channel, message_queues::QueueStyle};
 use futures::{
@@ -734,6 +734,7 @@ fn test_state_sync_events_committed_txns() {
         transactions: committed_txns,
         block_timestamp_usecs: 1,
         callback,
+        source: CommitSource::StateSync,
     };
     block_on(async {
         assert!(state_sync_sender.send(req).await.is_ok());
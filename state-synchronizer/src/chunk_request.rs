@@ -37,6 +37,27 @@ pub enum TargetType {
     },
     /// The response is built relative to a LedgerInfo at a given version.
     Waypoint(Version),
+    /// Same as `Waypoint`, but the response should additionally carry up to `max_epoch_proofs`
+    /// epoch-ending LedgerInfos of the epochs following the requester's current epoch, so that
+    /// the chain of epochs up to the waypoint is assembled with fewer round-trips.
+    WaypointWithEpochProofs {
+        version: Version,
+        max_epoch_proofs: u64,
+    },
+    /// The response carries only the highest LedgerInfo of the responder and no transactions,
+    /// so that a requester can cheaply learn how up to date a peer is before syncing from it.
+    Tip,
+    /// The response carries the transactions up to the end of the given (completed) epoch with
+    /// the proofs relative to its epoch-ending LedgerInfo, e.g., for archival fullnodes that
+    /// sync the history epoch by epoch.
+    EpochEnding { epoch: u64 },
+    /// Same as `TargetLedgerInfo`, but the upstream node may hold the request as a long poll
+    /// subscription (see `HighestAvailable`) for up to `timeout_ms` while it has nothing newer
+    /// than the requester.
+    TargetLedgerInfoWithTimeout {
+        target_li: LedgerInfoWithSignatures,
+        timeout_ms: u64,
+    },
 }
 
 impl TargetType {
@@ -47,6 +68,12 @@ impl TargetType {
                 target_li.as_ref().map(|li| li.ledger_info().version())
             }
             TargetType::Waypoint(version) => Some(*version),
+            TargetType::WaypointWithEpochProofs { version, .. } => Some(*version),
+            TargetType::Tip => None,
+            TargetType::EpochEnding { .. } => None,
+            TargetType::TargetLedgerInfoWithTimeout { target_li, .. } => {
+                Some(target_li.ledger_info().version())
+            }
         }
     }
 }
@@ -75,6 +102,24 @@ impl fmt::Display for TargetType {
                     .map_or_else(|| String::from("None"), |li| li.to_string())
             ),
             TargetType::Waypoint(version) => write!(f, "Waypoint({})", version),
+            TargetType::WaypointWithEpochProofs {
+                version,
+                max_epoch_proofs,
+            } => write!(
+                f,
+                "WaypointWithEpochProofs(version:{}, max_epoch_proofs:{})",
+                version, max_epoch_proofs
+            ),
+            TargetType::Tip => write!(f, "Tip"),
+            TargetType::EpochEnding { epoch } => write!(f, "EpochEnding({})", epoch),
+            TargetType::TargetLedgerInfoWithTimeout {
+                target_li,
+                timeout_ms,
+            } => write!(
+                f,
+                "TargetLedgerInfoWithTimeout(timeout:{}, target_li:{})",
+                timeout_ms, target_li
+            ),
         }
     }
 }
@@ -22,6 +22,8 @@ pub enum ResponseLedgerInfo {
         target_li: LedgerInfoWithSignatures,
         // LedgerInfo for a version later than that of `target_li`
         // If `None`, this is the same as `target_li`
+        // Being the highest LI of the responder, its (signed) timestamp lets the requester
+        // detect an upstream that is stale itself.
         highest_li: Option<LedgerInfoWithSignatures>,
     },
     /// During the initial catchup upon startup the chunks carry LedgerInfo that is verified
@@ -32,17 +34,42 @@ pub enum ResponseLedgerInfo {
         // In case a chunk terminates an epoch, the LedgerInfo corresponding to the epoch boundary.
         end_of_epoch_li: Option<LedgerInfoWithSignatures>,
     },
+    /// A response to `TargetType::WaypointWithEpochProofs` chunk request type.
+    LedgerInfoForWaypointWithEpochProofs {
+        // LedgerInfo corresponding to the waypoint version.
+        waypoint_li: LedgerInfoWithSignatures,
+        // In case a chunk terminates an epoch, the LedgerInfo corresponding to the epoch boundary.
+        end_of_epoch_li: Option<LedgerInfoWithSignatures>,
+        // Epoch-ending LedgerInfos of the consecutive epochs following the epoch of the chunk,
+        // stopping before the epoch of the waypoint.
+        epoch_proofs: Vec<LedgerInfoWithSignatures>,
+    },
+    /// The responder can't serve the request right now (e.g. its storage is temporarily
+    /// unavailable): the response carries its highest LedgerInfo and no transactions, and the
+    /// requester retries without waiting for the request to time out.
+    UnavailableLedgerInfo(LedgerInfoWithSignatures),
+    /// Answer to a `TargetType::Tip` request: the responder's highest LedgerInfo, without
+    /// transactions.
+    TipLedgerInfo(LedgerInfoWithSignatures),
+    /// The requested target is ahead of the responder's highest LedgerInfo: the response is
+    /// built relative to the latter (verified like a `VerifiableLedgerInfo`), and more can be
+    /// requested from the responder later on.
+    PartialLedgerInfo(LedgerInfoWithSignatures),
 }
 
 impl ResponseLedgerInfo {
     /// The version of the LedgerInfo relative to which the transactions proofs are built.
     pub fn version(&self) -> Version {
         match self {
-            ResponseLedgerInfo::VerifiableLedgerInfo(li) => li.ledger_info().version(),
+            ResponseLedgerInfo::VerifiableLedgerInfo(li)
+            | ResponseLedgerInfo::UnavailableLedgerInfo(li)
+            | ResponseLedgerInfo::TipLedgerInfo(li)
+            | ResponseLedgerInfo::PartialLedgerInfo(li) => li.ledger_info().version(),
             ResponseLedgerInfo::ProgressiveLedgerInfo { target_li, .. } => {
                 target_li.ledger_info().version()
             }
-            ResponseLedgerInfo::LedgerInfoForWaypoint { waypoint_li, .. } => {
+            ResponseLedgerInfo::LedgerInfoForWaypoint { waypoint_li, .. }
+            | ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs { waypoint_li, .. } => {
                 waypoint_li.ledger_info().version()
             }
         }
@@ -109,6 +136,27 @@ impl fmt::Display for GetChunkResponse {
                     .as_ref()
                     .map_or("None".to_string(), |li| li.ledger_info().to_string())
             ),
+            ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                waypoint_li,
+                end_of_epoch_li,
+                epoch_proofs,
+            } => format!(
+                "[waypoint LI {}, end of epoch LI {}, epoch proofs {}]",
+                waypoint_li.ledger_info(),
+                end_of_epoch_li
+                    .as_ref()
+                    .map_or("None".to_string(), |li| li.ledger_info().to_string()),
+                epoch_proofs.len()
+            ),
+            ResponseLedgerInfo::UnavailableLedgerInfo(li) => {
+                format!("[unavailable, highest LI {}]", li.ledger_info())
+            }
+            ResponseLedgerInfo::TipLedgerInfo(li) => {
+                format!("[tip, highest LI {}]", li.ledger_info())
+            }
+            ResponseLedgerInfo::PartialLedgerInfo(li) => {
+                format!("[partial, highest LI {}]", li.ledger_info())
+            }
         };
         write!(
             f,
@@ -5,24 +5,27 @@ use crate::{
     chunk_request::{GetChunkRequest, TargetType},
     chunk_response::{GetChunkResponse, ResponseLedgerInfo},
     counters,
-    executor_proxy::ExecutorProxyTrait,
+    executor_proxy::{ExecutorProxyTrait, SyncCheckpoint},
     logging::{LogEntry, LogEvent, LogSchema},
-    network::{StateSynchronizerEvents, StateSynchronizerMsg, StateSynchronizerSender},
-    request_manager::{PeerScoreUpdateType, RequestManager},
+    network::{
+        StateSynchronizerEvents, StateSynchronizerMsg, StateSynchronizerSender, SyncCapabilities,
+    },
+    request_manager::{NetworkHealth, PeerReliability, PeerScoreUpdateType, RequestManager},
     SynchronizerState,
 };
 use anyhow::{bail, ensure, format_err, Result};
 use futures::{
     channel::{mpsc, oneshot},
-    stream::select_all,
-    StreamExt,
+    future::{self, BoxFuture},
+    stream::{select_all, FuturesOrdered, FuturesUnordered, Peekable},
+    FutureExt, Stream, StreamExt,
 };
 use libra_config::{
     config::{PeerNetworkId, RoleType, StateSyncConfig, UpstreamConfig},
     network_id::NodeNetworkId,
 };
 use libra_logger::prelude::*;
-use libra_mempool::{CommitNotification, CommitResponse, CommittedTransaction};
+use libra_mempool::{CommitNotification, CommitResponse, CommitSource, CommittedTransaction};
 use libra_types::{
     contract_event::ContractEvent,
     epoch_change::Verifier,
@@ -30,13 +33,19 @@ use libra_types::{
     transaction::{Transaction, TransactionListWithProof, Version},
     waypoint::Waypoint,
 };
+use netcore::transport::ConnectionOrigin;
 use network::protocols::network::Event;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt, mem,
     ops::Bound::Included,
-    time::{Duration, SystemTime},
+    pin::Pin,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    task::{spawn_blocking, JoinError, JoinHandle},
+    time::{interval, timeout},
 };
-use tokio::time::{interval, timeout};
 
 pub struct SyncRequest {
     // The Result value returned to the caller is Error in case the StateSynchronizer failed to
@@ -46,6 +55,90 @@ pub struct SyncRequest {
     pub last_progress_tst: SystemTime,
 }
 
+/// Errors a sync request can be rejected with, reported to the caller through its callback
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncRequestError {
+    // the node has not reached its waypoint yet
+    NotInitialized,
+}
+
+impl fmt::Display for SyncRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncRequestError::NotInitialized => {
+                write!(f, "state sync is not initialized: waypoint not reached yet")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyncRequestError {}
+
+/// Error a chunk response is rejected with when its LI belongs to an epoch the node has already
+/// left, i.e. the upstream is lagging behind
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct StaleEpochResponse {
+    response_epoch: u64,
+    local_epoch: u64,
+}
+
+impl fmt::Display for StaleEpochResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "response LI epoch {} is behind the local epoch {}",
+            self.response_epoch, self.local_epoch
+        )
+    }
+}
+
+impl std::error::Error for StaleEpochResponse {}
+
+/// Error a chunk response is rejected with when its waypoint LI fails the waypoint verification
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct WaypointMismatch {
+    version: Version,
+    reason: String,
+}
+
+impl fmt::Display for WaypointMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "waypoint LI at version {} failed verification: {}",
+            self.version, self.reason
+        )
+    }
+}
+
+impl std::error::Error for WaypointMismatch {}
+
+/// Error a chunk request is failed with when the local storage can't be read: the requester is
+/// answered that the request can't be served right now
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct StorageUnavailable;
+
+impl fmt::Display for StorageUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "local storage is unavailable")
+    }
+}
+
+impl std::error::Error for StorageUnavailable {}
+
+/// Error a chunk request is failed with when serving it would exceed the epoch-ending LI lookup
+/// budget: the requester is answered with an empty chunk and retries later
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct EpochLookupRateLimited;
+
+impl fmt::Display for EpochLookupRateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "epoch-ending ledger info lookup rate limit exceeded")
+    }
+}
+
+impl std::error::Error for EpochLookupRateLimited {}
+
 /// message used by StateSyncClient for communication with Coordinator
 pub enum CoordinatorMessage {
     // used to initiate new sync
@@ -61,42 +154,274 @@ pub enum CoordinatorMessage {
     ),
     GetState(oneshot::Sender<SynchronizerState>),
     // Receive a notification via a given channel when coordinator is initialized.
-    WaitInitialize(oneshot::Sender<Result<()>>),
+    WaitInitialize(oneshot::Sender<Result<InitializationResult>>),
+    // used to reset the score of a peer to the default (fails if the peer is unknown)
+    ResetPeerScore(PeerNetworkId, oneshot::Sender<Result<()>>),
+    // used to immediately send the current chunk request to all the upstream networks
+    ForceMulticast(oneshot::Sender<Result<()>>),
+    // used to get the reliability of the upstream peers
+    GetPeerReliability(oneshot::Sender<HashMap<PeerNetworkId, PeerReliability>>),
+    // used to register a sink the sync phase transitions are pushed to (replaces any previous one)
+    SetPhaseChangeSink(mpsc::Sender<SyncPhaseChange>),
+    // used to get the timing breakdown of the recently applied chunks (oldest first), empty
+    // unless `record_chunk_timings` is set
+    GetRecentChunks(oneshot::Sender<Vec<ChunkTiming>>),
+    // used to get the config the coordinator is running with (the state sync config holds no
+    // sensitive fields)
+    GetConfig(oneshot::Sender<StateSyncConfig>),
+    // used to register a sink the number of enabled upstream peers is pushed to whenever a lost
+    // peer leaves fewer than `min_upstream_peers` of them, e.g. to trigger peer discovery
+    // (replaces any previous one)
+    SetLowPeerCountSink(mpsc::Sender<usize>),
+    // used to get the target of the latest chunk request sent to upstream (waypoint, sync request
+    // target or highest available), none if no request has been sent yet
+    GetCurrentTarget(oneshot::Sender<Option<TargetType>>),
+    // used to register a sink the record of every completed sync request is pushed to (replaces
+    // any previous one)
+    SetCompletionSink(mpsc::Sender<SyncRequestRecord>),
+    // used to get a snapshot of the pending long poll subscriptions (expired ones excluded)
+    GetSubscriptions(oneshot::Sender<Vec<SubscriptionInfo>>),
+    // used to cancel the pending sync request (fails if there is none)
+    CancelSync(oneshot::Sender<Result<()>>),
+    // Receive a notification via a given channel once the synced version reaches `version`.
+    WaitForVersion {
+        version: Version,
+        callback: oneshot::Sender<Result<()>>,
+    },
+    // used to stop the coordinator: the pending callbacks are failed before the shutdown is
+    // acknowledged
+    Shutdown(oneshot::Sender<()>),
+    // used to get the sync progress rate and how far the node is behind the known targets
+    GetSyncMetrics(oneshot::Sender<SyncMetrics>),
+    // used to get the highest LI pending to be synced to, e.g. to persist it across restarts
+    GetBestTargetLi(oneshot::Sender<Option<LedgerInfoWithSignatures>>),
+    // used to get the sync health of every network, e.g. to detect a dead upstream network masked
+    // by a healthy one
+    GetNetworkHealth(oneshot::Sender<HashMap<NodeNetworkId, NetworkHealth>>),
+    // used to verify a chunk response (LI and proofs) without executing or committing it
+    VerifyChunk {
+        response: Box<GetChunkResponse>,
+        callback: oneshot::Sender<Result<()>>,
+    },
+    // used to replace the waypoint of a node that hasn't reached it yet, e.g. after a bootstrap
+    // with a stale or wrong waypoint (fails if initialized or if the waypoint would move backward)
+    UpdateWaypoint {
+        waypoint: Waypoint,
+        callback: oneshot::Sender<Result<()>>,
+    },
+    // used to replace the policy restricting the peers chunks are served to
+    SetServingPolicy(ServingPolicy),
+}
+
+/// Time spent in the phases of applying a chunk
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkTiming {
+    // version of the first transaction in the chunk
+    pub first_version: Version,
+    pub num_txns: u64,
+    // time between the chunk request was (last) sent and the chunk was received, unknown if the
+    // chunk was not requested by this node
+    pub network_receive: Option<Duration>,
+    // time spent processing the chunk besides executing and storing it, mostly verifying its LIs
+    pub verify: Duration,
+    // time spent executing and storing the chunk
+    pub store: Duration,
+}
+
+/// Phase of the synchronization process
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncPhase {
+    // syncing up to the waypoint
+    WaypointSync,
+    // the waypoint is reached
+    Normal,
+}
+
+/// Outcome of waiting for the node to be caught up with its waypoint
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InitializationResult {
+    // the waypoint was already reached when the listener was registered
+    AlreadyInitialized,
+    // the waypoint was reached after the listener was registered
+    Reached,
+}
+
+/// Transition between sync phases
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyncPhaseChange {
+    pub old_phase: SyncPhase,
+    pub new_phase: SyncPhase,
+    // committed version at the time of the transition
+    pub version: Version,
+}
+
+/// Epoch change observed in the local storage
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EpochChangeNotification {
+    pub old_epoch: u64,
+    pub new_epoch: u64,
+    // committed version at which the epoch change was observed
+    pub version: Version,
+}
+
+/// Recent sync progress, e.g. for estimating the time to sync completion
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncMetrics {
+    pub synced_version: Version,
+    // highest version of the LIs pending to be synced to, if any
+    pub highest_target_version: Option<Version>,
+    // versions synced per second over the recent commits, 0 if unknown
+    pub versions_per_sec: f64,
+}
+
+impl SyncMetrics {
+    /// Number of versions the node is behind the highest pending target
+    pub fn lag(&self) -> u64 {
+        self.highest_target_version
+            .map_or(0, |target| target.saturating_sub(self.synced_version))
+    }
+}
+
+/// How a sync request was completed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncRequestOutcome {
+    // the target was reached
+    Complete,
+    // no progress was made within `sync_request_timeout_ms`
+    Timeout,
+    // no progress was made within `max_sync_attempts` chunk requests
+    AttemptsExhausted,
+    // the request was cancelled or overridden by a newer one before reaching its target
+    Cancelled,
+}
+
+/// Audit record of a completed sync request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyncRequestRecord {
+    pub target_version: Version,
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+    // number of chunks applied while the request was pending
+    pub chunks_applied: u64,
+    // serialized size of the transactions (with proofs) of the applied chunks
+    pub bytes_transferred: u64,
+    pub outcome: SyncRequestOutcome,
+}
+
+// Progress made on the pending sync request, reported by its completion record
+struct SyncRequestProgress {
+    target_version: Version,
+    start_time: SystemTime,
+    chunks_applied: u64,
+    bytes_transferred: u64,
+    // chunk requests sent since the synced version last advanced
+    attempts: u64,
+}
+
+// Chunk read from storage for serving a chunk request
+struct ServedChunk {
+    peer: PeerNetworkId,
+    response_li: ResponseLedgerInfo,
+    requested_limit: u64,
+    txns: Result<TransactionListWithProof>,
+}
+
+/// Peers the chunk requests are served for (every peer by default)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ServingPolicy {
+    // networks whose peers are served, all of them if none
+    pub networks: Option<HashSet<NodeNetworkId>>,
+    // peers served on these networks, all of them if none
+    pub allowlist: Option<HashSet<PeerNetworkId>>,
+}
+
+impl ServingPolicy {
+    pub fn allows(&self, peer: &PeerNetworkId) -> bool {
+        self.networks
+            .as_ref()
+            .map_or(true, |networks| networks.contains(&peer.network_id()))
+            && self
+                .allowlist
+                .as_ref()
+                .map_or(true, |allowlist| allowlist.contains(peer))
+    }
+}
+
+/// Snapshot of a pending long poll subscription
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionInfo {
+    pub peer: PeerNetworkId,
+    pub known_version: u64,
+    pub request_epoch: u64,
+    pub limit: u64,
+    // time left until the subscription expires
+    pub remaining: Duration,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct PendingRequestInfo {
+    subscription_time: SystemTime,
     expiration_time: SystemTime,
     known_version: u64,
     request_epoch: u64,
     limit: u64,
+    // target of a target LI request, the subscription is served relative to it (otherwise,
+    // relative to the highest local LI)
+    target_li: Option<LedgerInfoWithSignatures>,
+    // whether the subscriber is no longer eligible to be served (e.g. it disconnected since it
+    // subscribed)
+    revoked: bool,
 }
 
+/// Estimate of the memory held by a single pending subscription (its key and request info)
+pub(crate) const SUBSCRIPTION_MEMORY_ESTIMATE: usize =
+    mem::size_of::<PeerNetworkId>() + mem::size_of::<PendingRequestInfo>();
+
 // DS to help sync requester to keep track of ledger infos in the future
 // if it is lagging far behind the upstream node
 // Should only be modified upon local storage sync
-struct PendingLedgerInfos {
+pub(crate) struct PendingLedgerInfos {
     // In-memory store of ledger infos that are pending commits
     // (k, v) - (LI version, LI)
     pending_li_queue: BTreeMap<Version, LedgerInfoWithSignatures>,
     // max size limit on `pending_li_queue`, to prevent OOM
     max_pending_li_limit: usize,
+    // serialized size of the LIs in `pending_li_queue`
+    pending_li_bytes: usize,
+    // max serialized size of `pending_li_queue`, 0 means no limit
+    max_pending_li_bytes: usize,
     // target li
     target_li: Option<LedgerInfoWithSignatures>,
 }
 
 impl PendingLedgerInfos {
-    fn new(max_pending_li_limit: usize) -> Self {
+    pub(crate) fn new(max_pending_li_limit: usize, max_pending_li_bytes: usize) -> Self {
         Self {
             pending_li_queue: BTreeMap::new(),
             max_pending_li_limit,
+            pending_li_bytes: 0,
+            max_pending_li_bytes,
             target_li: None,
         }
     }
 
-    /// Adds `new_li` to the queue of pending LI's
-    fn add_li(&mut self, new_li: LedgerInfoWithSignatures) {
-        if self.pending_li_queue.len() >= self.max_pending_li_limit {
+    /// Adds `new_li` to the queue of pending LI's.
+    /// Once the queue is at capacity, the lowest-version LIs are evicted in favor of higher ones,
+    /// so that the queue keeps the LIs closest to the frontier.
+    pub(crate) fn add_li(&mut self, new_li: LedgerInfoWithSignatures) {
+        // update pending_ledgers if new LI is ahead of target LI (in terms of version), if any:
+        // a node syncing from genesis has no target yet
+        let new_version = new_li.ledger_info().version();
+        let behind_target = self
+            .target_li
+            .as_ref()
+            .map_or(false, |li| new_version <= li.ledger_info().version());
+        if behind_target || self.pending_li_queue.contains_key(&new_version) {
+            return;
+        }
+        let at_capacity = self.pending_li_queue.len() >= self.max_pending_li_limit;
+        let lowest_version = self.pending_li_queue.keys().next().cloned();
+        if at_capacity && lowest_version.map_or(true, |version| new_version < version) {
             warn!(
                 LogSchema::new(LogEntry::ProcessChunkResponse),
                 "pending LI store reached max capacity {}, failed to add LI {}",
@@ -105,30 +430,75 @@ impl PendingLedgerInfos {
             );
             return;
         }
+        let new_li_bytes = Self::li_bytes(&new_li);
+        if !self.make_room(new_version, new_li_bytes) {
+            warn!(
+                LogSchema::new(LogEntry::ProcessChunkResponse),
+                "pending LI store reached max size {} bytes, failed to add LI {}",
+                self.max_pending_li_bytes,
+                new_li
+            );
+            return;
+        }
+        // making room for the bytes of the new LI may have freed a slot already
+        if self.pending_li_queue.len() >= self.max_pending_li_limit {
+            if let Some(version) = lowest_version {
+                if let Some(evicted_li) = self.pending_li_queue.remove(&version) {
+                    self.pending_li_bytes -= Self::li_bytes(&evicted_li);
+                }
+            }
+        }
+        self.pending_li_queue.insert(new_version, new_li);
+        self.pending_li_bytes += new_li_bytes;
+        counters::PENDING_LI_QUEUE_BYTES.set(self.pending_li_bytes as i64);
+    }
 
-        // update pending_ledgers if new LI is ahead of target LI (in terms of version)
-        let target_version = self
-            .target_li
-            .as_ref()
-            .map_or(0, |li| li.ledger_info().version());
-        if new_li.ledger_info().version() > target_version {
-            self.pending_li_queue
-                .insert(new_li.ledger_info().version(), new_li);
+    /// Evicts the lowest-version LIs below `new_version` until `new_li_bytes` more bytes fit in
+    /// the queue. Returns false (and evicts nothing) if they cannot be made to fit.
+    fn make_room(&mut self, new_version: Version, new_li_bytes: usize) -> bool {
+        if self.max_pending_li_bytes == 0 {
+            return true;
+        }
+        let mut remaining_bytes = self.pending_li_bytes;
+        let mut evicted = vec![];
+        for (version, li) in self.pending_li_queue.range(..new_version) {
+            if remaining_bytes + new_li_bytes <= self.max_pending_li_bytes {
+                break;
+            }
+            remaining_bytes -= Self::li_bytes(li);
+            evicted.push(*version);
+        }
+        if remaining_bytes + new_li_bytes > self.max_pending_li_bytes {
+            return false;
         }
+        for version in evicted {
+            self.pending_li_queue.remove(&version);
+        }
+        self.pending_li_bytes = remaining_bytes;
+        true
+    }
+
+    fn li_bytes(li: &LedgerInfoWithSignatures) -> usize {
+        lcs::to_bytes(li).map_or(0, |bytes| bytes.len())
     }
 
-    fn update(&mut self, sync_state: &SynchronizerState, chunk_limit: u64) {
+    pub(crate) fn update(&mut self, sync_state: &SynchronizerState, chunk_limit: u64) {
         let highest_committed_li = sync_state.highest_local_li.ledger_info().version();
         let highest_synced = sync_state.highest_version_in_local_storage();
 
         // prune any pending LIs that were successfully committed
         self.pending_li_queue = self.pending_li_queue.split_off(&(highest_committed_li + 1));
+        self.pending_li_bytes = self.pending_li_queue.values().map(Self::li_bytes).sum();
+        counters::PENDING_LI_QUEUE_BYTES.set(self.pending_li_bytes as i64);
 
         // pick target LI to use for sending ProgressiveTargetType requests.
         self.target_li = if highest_committed_li == highest_synced {
             // try to find LI with max version that will fit in a single chunk
             self.pending_li_queue
-                .range((Included(0), Included(highest_synced + chunk_limit)))
+                .range((
+                    Included(0),
+                    Included(highest_synced.saturating_add(chunk_limit)),
+                ))
                 .rev()
                 .next()
                 .map(|(_version, ledger_info)| ledger_info.clone())
@@ -140,9 +510,245 @@ impl PendingLedgerInfos {
         };
     }
 
-    fn target_li(&self) -> Option<LedgerInfoWithSignatures> {
+    pub(crate) fn target_li(&self) -> Option<LedgerInfoWithSignatures> {
         self.target_li.clone()
     }
+
+    pub(crate) fn ledger_infos(&self) -> Vec<LedgerInfoWithSignatures> {
+        self.pending_li_queue.values().cloned().collect()
+    }
+
+    pub(crate) fn highest_version(&self) -> Option<Version> {
+        self.pending_li_queue.keys().next_back().copied()
+    }
+
+    pub(crate) fn highest_li(&self) -> Option<LedgerInfoWithSignatures> {
+        self.pending_li_queue.values().next_back().cloned()
+    }
+}
+
+// Max number of applied chunks the timing breakdown is kept for
+const MAX_RECENT_CHUNK_TIMINGS: usize = 100;
+
+// Max number of epoch-ending LIs bundled in a single waypoint chunk response
+const MAX_EPOCH_PROOFS_PER_RESPONSE: u64 = 100;
+
+// Max number of pending chunk requests served ahead of a progress check when `prioritize_serving`
+// is set, so that a flood of requests can't starve the progress checks either
+const MAX_PRIORITIZED_CHUNK_REQUESTS: usize = 1_000;
+
+// Multiple of the observed commit interval used as the adaptive long poll timeout
+const LONG_POLL_COMMIT_INTERVAL_MULTIPLIER: u64 = 2;
+
+// Number of consecutive successfully applied chunks after which a downgraded chunk limit is
+// doubled back (up to the configured chunk limit)
+const CHUNK_LIMIT_RECOVERY_SUCCESSES: u64 = 10;
+
+// Adapts the requested chunk limit to repeated failures to apply chunks (e.g. due to memory
+// pressure): the limit is halved after `downgrade_failures` consecutive failures, down to
+// `min_limit`, and doubled back after sustained successes
+struct ChunkLimitController {
+    limit: u64,
+    max_limit: u64,
+    min_limit: u64,
+    // 0 disables the downgrade
+    downgrade_failures: u64,
+    consecutive_failures: u64,
+    consecutive_successes: u64,
+}
+
+impl ChunkLimitController {
+    fn new(config: &StateSyncConfig) -> Self {
+        Self {
+            limit: config.chunk_limit(),
+            max_limit: config.chunk_limit(),
+            min_limit: std::cmp::min(config.min_chunk_limit, config.chunk_limit()),
+            downgrade_failures: config.chunk_limit_downgrade_failures,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+        }
+    }
+
+    fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    fn record_failure(&mut self) {
+        self.consecutive_successes = 0;
+        if self.downgrade_failures == 0 {
+            return;
+        }
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= self.downgrade_failures && self.limit > self.min_limit {
+            self.consecutive_failures = 0;
+            self.limit = std::cmp::max(self.limit / 2, self.min_limit);
+            counters::CHUNK_LIMIT_DOWNGRADE_COUNT.inc();
+            counters::EFFECTIVE_CHUNK_LIMIT.set(self.limit as i64);
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        if self.limit >= self.max_limit {
+            return;
+        }
+        self.consecutive_successes += 1;
+        if self.consecutive_successes >= CHUNK_LIMIT_RECOVERY_SUCCESSES {
+            self.consecutive_successes = 0;
+            self.limit = std::cmp::min(self.limit.saturating_mul(2), self.max_limit);
+            counters::CHUNK_LIMIT_RECOVERY_COUNT.inc();
+            counters::EFFECTIVE_CHUNK_LIMIT.set(self.limit as i64);
+        }
+    }
+}
+
+// Limits the number of epoch-ending LI lookups performed per one-second window, so a burst of
+// waypoint syncing peers can't overwhelm the storage
+struct EpochLookupRateLimiter {
+    // 0 means no limit
+    max_lookups_per_sec: u64,
+    window_start: Instant,
+    window_lookups: u64,
+}
+
+impl EpochLookupRateLimiter {
+    fn new(max_lookups_per_sec: u64) -> Self {
+        Self {
+            max_lookups_per_sec,
+            window_start: Instant::now(),
+            window_lookups: 0,
+        }
+    }
+
+    /// Accounts for a lookup if it fits in the budget of the current window.
+    fn try_acquire(&mut self) -> bool {
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.window_start = Instant::now();
+            self.window_lookups = 0;
+        }
+        if self.max_lookups_per_sec > 0 && self.window_lookups >= self.max_lookups_per_sec {
+            return false;
+        }
+        self.window_lookups += 1;
+        counters::EPOCH_LOOKUP_RATE.set(self.window_lookups as i64);
+        true
+    }
+}
+
+// Reads a chunk of up to `limit` transactions with `get_chunk`, halving the limit until the
+// encoded transactions fit in `max_bytes` (0 means no limit). A single transaction exceeding
+// `max_bytes` is returned anyway, so that the requester doesn't stall.
+fn get_chunk_within_bytes(
+    get_chunk: impl Fn(u64) -> Result<TransactionListWithProof>,
+    limit: u64,
+    max_bytes: u64,
+) -> Result<TransactionListWithProof> {
+    let mut limit = limit;
+    loop {
+        let txns = get_chunk(limit)?;
+        if max_bytes == 0 {
+            return Ok(txns);
+        }
+        let num_bytes = lcs::to_bytes(&txns)?.len() as u64;
+        if num_bytes <= max_bytes {
+            return Ok(txns);
+        }
+        let num_txns = txns.len() as u64;
+        if num_txns <= 1 {
+            warn!(
+                LogSchema::event_log(LogEntry::ProcessChunkRequest, LogEvent::DeliverChunk),
+                "served transaction of {} bytes exceeds the max chunk response size of {} bytes",
+                num_bytes,
+                max_bytes
+            );
+            return Ok(txns);
+        }
+        counters::SHRUNK_CHUNK_RESPONSE_COUNT.inc();
+        limit = num_txns / 2;
+    }
+}
+
+// Returns the label of the sync duration of a chunk verified against `response_li`
+pub(crate) fn sync_duration_label(response_li: &ResponseLedgerInfo) -> &'static str {
+    match response_li {
+        ResponseLedgerInfo::LedgerInfoForWaypoint { .. }
+        | ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs { .. } => {
+            counters::WAYPOINT_RESPONSE_LABEL
+        }
+        ResponseLedgerInfo::VerifiableLedgerInfo(_)
+        | ResponseLedgerInfo::UnavailableLedgerInfo(_)
+        | ResponseLedgerInfo::TipLedgerInfo(_)
+        | ResponseLedgerInfo::PartialLedgerInfo(_) => counters::VERIFIABLE_RESPONSE_LABEL,
+        ResponseLedgerInfo::ProgressiveLedgerInfo { .. } => counters::PROGRESSIVE_RESPONSE_LABEL,
+    }
+}
+
+// Number of recent commits the sync rate is averaged over
+const COMMIT_RATE_WINDOW: usize = 10;
+
+// Tracks the interval between consecutive commits that advanced the synced version,
+// smoothed with an exponential moving average, and the sync rate over the recent commits
+struct CommitIntervalTracker {
+    last_version: Version,
+    last_commit_tst: Option<SystemTime>,
+    interval_ms: Option<u64>,
+    // (commit time, synced version) of the last `COMMIT_RATE_WINDOW` commits
+    recent_commits: VecDeque<(SystemTime, Version)>,
+}
+
+impl CommitIntervalTracker {
+    fn new(version: Version) -> Self {
+        Self {
+            last_version: version,
+            last_commit_tst: None,
+            interval_ms: None,
+            recent_commits: VecDeque::new(),
+        }
+    }
+
+    /// Records a commit that brought the synced version to `version`.
+    /// Returns true if the version advanced since the last observed commit.
+    fn observe(&mut self, version: Version) -> bool {
+        if version <= self.last_version {
+            return false;
+        }
+        let now = SystemTime::now();
+        if let Some(elapsed) = self
+            .last_commit_tst
+            .and_then(|tst| now.duration_since(tst).ok())
+        {
+            let sample = elapsed.as_millis() as u64;
+            self.interval_ms = Some(
+                self.interval_ms
+                    .map_or(sample, |interval| (3 * interval + sample) / 4),
+            );
+        }
+        self.last_version = version;
+        self.last_commit_tst = Some(now);
+        if self.recent_commits.len() == COMMIT_RATE_WINDOW {
+            self.recent_commits.pop_front();
+        }
+        self.recent_commits.push_back((now, version));
+        true
+    }
+
+    fn interval_ms(&self) -> Option<u64> {
+        self.interval_ms
+    }
+
+    /// Versions synced per second between the oldest and the newest of the recent commits
+    fn versions_per_sec(&self) -> f64 {
+        match (self.recent_commits.front(), self.recent_commits.back()) {
+            (Some((first_tst, first_version)), Some((last_tst, last_version))) => last_tst
+                .duration_since(*first_tst)
+                .ok()
+                .filter(|elapsed| elapsed.as_nanos() > 0)
+                .map_or(0.0, |elapsed| {
+                    (last_version - first_version) as f64 / elapsed.as_secs_f64()
+                }),
+            _ => 0.0,
+        }
+    }
 }
 
 /// Coordination of synchronization process is driven by SyncCoordinator, which `start()` function
@@ -176,15 +782,85 @@ pub(crate) struct SyncCoordinator<T> {
     request_manager: RequestManager,
     // Optional sync request to be called when the target sync is reached
     sync_request: Option<SyncRequest>,
+    // number of epoch transitions needed to reach the target of the sync request, as expected
+    // when the request was received
+    sync_request_epoch_transitions: u64,
+    // progress made on the pending sync request (if any)
+    sync_request_progress: Option<SyncRequestProgress>,
+    // optional sink the records of the completed sync requests are pushed to
+    completion_sink: Option<mpsc::Sender<SyncRequestRecord>>,
+    // optional sender of the epoch changes observed in the local storage
+    epoch_change_sender: Option<mpsc::Sender<EpochChangeNotification>>,
     // Ledger infos in the future that have not been committed yet
     pending_ledger_infos: PendingLedgerInfos,
     // Option initialization listener to be called when the coordinator is caught up with
     // its waypoint.
-    initialization_listener: Option<oneshot::Sender<Result<()>>>,
+    initialization_listener: Option<oneshot::Sender<Result<InitializationResult>>>,
+    // listeners to be called once the synced version reaches the version they are keyed by
+    version_waiters: BTreeMap<Version, Vec<oneshot::Sender<Result<()>>>>,
+    // time the coordinator started syncing to its waypoint
+    init_start_tst: SystemTime,
+    // distinct peers whose waypoint LIs failed the waypoint verification, keyed by the version
+    // of the LIs
+    waypoint_mismatch_peers: HashMap<Version, HashSet<PeerNetworkId>>,
+    // version at which `waypoint_mismatch_peer_threshold` peers failed the waypoint verification
+    waypoint_mismatch_version: Option<Version>,
+    // whether the waypoint was not reached within `waypoint_init_timeout_ms`
+    waypoint_init_timed_out: bool,
+    // whether the regressed local storage no longer matches the waypoint: the node is back in
+    // the waypoint sync until the storage matches it again
+    waypoint_invalidated: bool,
+    // phase as of the last sync with the local storage
+    phase: SyncPhase,
+    // optional sink the phase transitions are pushed to
+    phase_change_sink: Option<mpsc::Sender<SyncPhaseChange>>,
+    // optional sink signalled when the number of enabled upstream peers drops below
+    // `min_upstream_peers`
+    low_peer_count_sink: Option<mpsc::Sender<usize>>,
+    // peers the chunk requests are served for
+    serving_policy: ServingPolicy,
+    // whether the local storage was behind the target LI as of the last commit, used for
+    // detecting the moment the node catches up
+    lagging_behind_target: bool,
     // queue of incoming long polling requests
     // peer will be notified about new chunk of transactions if it's available before expiry time
     subscriptions: HashMap<PeerNetworkId, PendingRequestInfo>,
+    // number of ready subscriptions whose delivery was deferred to the next check because of the
+    // `max_subscription_deliveries` cap
+    deferred_subscription_deliveries: usize,
+    // observed interval between commits, used for adapting the long poll timeout
+    commit_interval_tracker: CommitIntervalTracker,
+    // budget of the epoch-ending LI lookups performed while serving chunk requests
+    epoch_lookup_rate_limiter: EpochLookupRateLimiter,
+    // chunk limit of the chunk requests, downgraded upon repeated failures to apply chunks
+    chunk_limit_controller: ChunkLimitController,
+    // mempool ACKs of the commit notifications sent for applied chunks that are still pending
+    // (the commit pipeline): resolve to false if mempool did not ACK in time
+    pending_mempool_acks: FuturesUnordered<BoxFuture<'static, bool>>,
+    // committed user txns of the applied chunks mempool has not been notified of yet, none if
+    // there are no such commits (only used if `mempool_commit_batch_size` is set)
+    mempool_commit_batch: Option<Vec<CommittedTransaction>>,
+    // verified epoch-ending LIs (keyed by epoch) on the way to the waypoint that were prefetched
+    // during waypoint sync, including the waypoint LI itself
+    epoch_proofs: BTreeMap<u64, LedgerInfoWithSignatures>,
+    // time of the latest checkpoint of the coordinator state
+    last_checkpoint_tst: SystemTime,
+    // chunks being read from storage on the blocking thread pool in order to serve chunk
+    // requests, forwarded to the requesters in the order the requests were processed
+    pending_chunk_serves: FuturesOrdered<JoinHandle<ServedChunk>>,
+    // chunks received ahead of the local version (oldest first) along with their senders,
+    // applied once the chunks preceding them are
+    reorder_buffer: VecDeque<(PeerNetworkId, GetChunkResponse)>,
     executor_proxy: T,
+    // timing breakdown of the recently applied chunks (oldest first)
+    recent_chunk_timings: VecDeque<ChunkTiming>,
+    // time spent executing and storing the chunk being applied
+    chunk_store_duration: Duration,
+    // optional read-only replica of the storage chunk requests are served from, isolating the
+    // serving load from the commit path (the replica may lag behind `executor_proxy`)
+    read_replica: Option<T>,
+    // target of the latest chunk request sent to upstream, i.e. the mode the node is syncing in
+    last_sent_target: Option<TargetType>,
 }
 
 impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
@@ -198,34 +874,89 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         upstream_config: UpstreamConfig,
         executor_proxy: T,
         initial_state: SynchronizerState,
+        epoch_change_sender: Option<mpsc::Sender<EpochChangeNotification>>,
+        persisted_target_li: Option<LedgerInfoWithSignatures>,
     ) -> Self {
         info!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::Initialize).waypoint(waypoint));
-        let retry_timeout_val = match role {
-            RoleType::FullNode => config.tick_interval_ms + config.long_poll_timeout_ms,
-            RoleType::Validator => 2 * config.tick_interval_ms,
-        };
-        let multicast_timeout = Duration::from_millis(config.multicast_timeout_ms);
+        let retry_timeout_val = Self::base_request_timeout_ms(role, &config);
+        let max_retry_timeout = Duration::from_millis(config.max_retry_timeout_ms);
+        let multicast_timeout = Duration::from_millis(config.multicast_timeout_ms());
+        let commit_interval_tracker =
+            CommitIntervalTracker::new(initial_state.highest_version_in_local_storage());
+        let chunk_limit_controller = ChunkLimitController::new(&config);
+        let epoch_lookup_rate_limiter =
+            EpochLookupRateLimiter::new(config.max_epoch_lookups_per_sec);
 
-        Self {
+        let mut coordinator = Self {
             client_events,
             state_sync_to_mempool_sender,
             local_state: initial_state,
-            pending_ledger_infos: PendingLedgerInfos::new(config.max_pending_li_limit),
+            pending_ledger_infos: PendingLedgerInfos::new(
+                config.max_pending_li_limit,
+                config.max_pending_li_bytes,
+            ),
             config,
             role,
             waypoint,
             request_manager: RequestManager::new(
                 upstream_config,
                 Duration::from_millis(retry_timeout_val),
+                max_retry_timeout,
                 multicast_timeout,
                 network_senders.clone(),
             ),
             network_senders,
+            lagging_behind_target: false,
             subscriptions: HashMap::new(),
+            deferred_subscription_deliveries: 0,
             sync_request: None,
+            sync_request_epoch_transitions: 0,
+            sync_request_progress: None,
+            completion_sink: None,
+            epoch_change_sender,
             initialization_listener: None,
+            version_waiters: BTreeMap::new(),
+            init_start_tst: SystemTime::now(),
+            waypoint_mismatch_peers: HashMap::new(),
+            waypoint_mismatch_version: None,
+            waypoint_init_timed_out: false,
+            waypoint_invalidated: false,
+            phase: SyncPhase::WaypointSync,
+            phase_change_sink: None,
+            low_peer_count_sink: None,
+            serving_policy: ServingPolicy::default(),
+            commit_interval_tracker,
+            epoch_lookup_rate_limiter,
+            chunk_limit_controller,
+            pending_mempool_acks: FuturesUnordered::new(),
+            mempool_commit_batch: None,
+            epoch_proofs: BTreeMap::new(),
+            last_checkpoint_tst: SystemTime::now(),
+            pending_chunk_serves: FuturesOrdered::new(),
+            reorder_buffer: VecDeque::new(),
             executor_proxy,
+            recent_chunk_timings: VecDeque::new(),
+            chunk_store_duration: Duration::from_secs(0),
+            read_replica: None,
+            last_sent_target: None,
+        };
+        coordinator.phase = coordinator.current_phase();
+        coordinator
+            .request_manager
+            .set_availability_score_threshold(coordinator.config.min_available_peer_score as f64);
+        coordinator
+            .request_manager
+            .set_escalate_stale_chunk_penalty(coordinator.config.escalate_stale_chunk_penalty);
+        if coordinator.config.checkpoint_interval_ms > 0 {
+            coordinator.restore_checkpoint();
+        }
+        if let Some(target_li) = persisted_target_li {
+            coordinator.seed_target_li(target_li);
+        }
+        if coordinator.config.journal_reconfig_events {
+            coordinator.republish_journaled_reconfig_events();
         }
+        coordinator
     }
 
     /// main routine. starts sync coordinator that listens for CoordinatorMsg
@@ -238,13 +969,14 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         )>,
     ) {
         info!(LogSchema::new(LogEntry::RuntimeStart));
-        let mut interval = interval(Duration::from_millis(self.config.tick_interval_ms)).fuse();
+        let mut tick_interval = self.tick_interval();
+        let mut interval = interval(tick_interval).fuse();
 
         let events: Vec<_> = network_handles
             .into_iter()
             .map(|(network_id, _sender, events)| events.map(move |e| (network_id.clone(), e)))
             .collect();
-        let mut network_events = select_all(events).fuse();
+        let mut network_events = select_all(events).peekable();
 
         loop {
             ::futures::select! {
@@ -264,17 +996,15 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                                 let _timer = counters::PROCESS_COORDINATOR_MSG_LATENCY
                                     .with_label_values(&[counters::COMMIT_MSG_LABEL])
                                     .start_timer();
+                                if self.config.journal_reconfig_events {
+                                    self.journal_reconfig_events(&events);
+                                }
                                 if let Err(e) = self.process_commit(txns, Some(callback), None).await {
                                     counters::CONSENSUS_COMMIT_FAIL_COUNT.inc();
                                     error!(LogSchema::event_log(LogEntry::ConsensusCommit, LogEvent::PostCommitFail).error(&e));
                                 }
                             }
-                            if let Err(e) = self.executor_proxy.publish_on_chain_config_updates(events) {
-                                counters::RECONFIG_PUBLISH_COUNT
-                                    .with_label_values(&[counters::FAIL_LABEL])
-                                    .inc();
-                                error!(LogSchema::event_log(LogEntry::Reconfig, LogEvent::Fail).error(&e));
-                            }
+                            self.publish_reconfig_events(events);
                         }
                         CoordinatorMessage::GetState(callback) => {
                             self.get_state(callback);
@@ -282,84 +1012,534 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                         CoordinatorMessage::WaitInitialize(cb_sender) => {
                             self.set_initialization_listener(cb_sender);
                         }
+                        CoordinatorMessage::WaitForVersion { version, callback } => {
+                            self.add_version_waiter(version, callback);
+                        }
+                        CoordinatorMessage::ResetPeerScore(peer, callback) => {
+                            if callback.send(self.request_manager.reset_score(&peer)).is_err() {
+                                error!(LogSchema::new(LogEntry::ResetPeerScore).peer(&peer),
+                                    "failed to send peer score reset result");
+                            }
+                        }
+                        CoordinatorMessage::ForceMulticast(callback) => {
+                            if callback.send(self.force_multicast()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::Multicast, LogEvent::ForceMulticast),
+                                    "failed to send force multicast result");
+                            }
+                        }
+                        CoordinatorMessage::GetPeerReliability(callback) => {
+                            if callback.send(self.request_manager.peer_reliability()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send peer reliability");
+                            }
+                        }
+                        CoordinatorMessage::SetPhaseChangeSink(sink) => {
+                            self.set_phase_change_sink(sink);
+                        }
+                        CoordinatorMessage::SetLowPeerCountSink(sink) => {
+                            self.set_low_peer_count_sink(sink);
+                        }
+                        CoordinatorMessage::SetCompletionSink(sink) => {
+                            self.set_completion_sink(sink);
+                        }
+                        CoordinatorMessage::GetSubscriptions(callback) => {
+                            if callback.send(self.subscriptions_snapshot()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send subscriptions");
+                            }
+                        }
+                        CoordinatorMessage::CancelSync(callback) => {
+                            if callback.send(self.cancel_sync_request()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail),
+                                    "failed to send sync cancellation result");
+                            }
+                        }
+                        CoordinatorMessage::GetRecentChunks(callback) => {
+                            if callback.send(self.recent_chunk_timings()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send recent chunk timings");
+                            }
+                        }
+                        CoordinatorMessage::GetConfig(callback) => {
+                            if callback.send(self.config.clone()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send config");
+                            }
+                        }
+                        CoordinatorMessage::GetCurrentTarget(callback) => {
+                            if callback.send(self.last_sent_target.clone()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail),
+                                    "failed to send current target");
+                            }
+                        }
+                        CoordinatorMessage::GetSyncMetrics(callback) => {
+                            if callback.send(self.sync_metrics()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send sync metrics");
+                            }
+                        }
+                        CoordinatorMessage::GetBestTargetLi(callback) => {
+                            if callback.send(self.best_target_li()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail),
+                                    "failed to send best target LI");
+                            }
+                        }
+                        CoordinatorMessage::GetNetworkHealth(callback) => {
+                            if callback.send(self.request_manager.network_health()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::LocalState, LogEvent::CallbackFail),
+                                    "failed to send network health");
+                            }
+                        }
+                        CoordinatorMessage::VerifyChunk { response, callback } => {
+                            if callback.send(self.verify_chunk(&response)).is_err() {
+                                error!(LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::CallbackFail),
+                                    "failed to send chunk verification result");
+                            }
+                        }
+                        CoordinatorMessage::UpdateWaypoint { waypoint, callback } => {
+                            if callback.send(self.update_waypoint(waypoint)).is_err() {
+                                error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail),
+                                    "failed to send waypoint update result");
+                            }
+                        }
+                        CoordinatorMessage::SetServingPolicy(policy) => {
+                            self.set_serving_policy(policy);
+                        }
+                        CoordinatorMessage::Shutdown(callback) => {
+                            self.drain_pending_callbacks();
+                            self.flush_commit_notifications().await;
+                            info!(LogSchema::new(LogEntry::RuntimeShutdown));
+                            if callback.send(()).is_err() {
+                                error!(LogSchema::event_log(LogEntry::RuntimeShutdown, LogEvent::CallbackFail),
+                                    "failed to acknowledge shutdown");
+                            }
+                            return;
+                        }
                     };
                 },
                 (network_id, event) = network_events.select_next_some() => {
-                    match event {
-                        Event::NewPeer(peer_id, origin) => {
-                            let peer = PeerNetworkId(network_id, peer_id);
-                            self.request_manager.enable_peer(peer, origin);
-                            self.check_progress();
-                        }
-                        Event::LostPeer(peer_id, origin) => {
-                            let peer = PeerNetworkId(network_id, peer_id);
-                            self.request_manager.disable_peer(&peer, origin);
-                        }
-                        Event::Message(peer_id, message) => self.process_one_message(PeerNetworkId(network_id.clone(), peer_id), message).await,
-                        unexpected_event => {
-                            counters::NETWORK_ERROR_COUNT.inc();
-                            warn!(LogSchema::new(LogEntry::NetworkError),
-                            "received unexpected network event: {:?}", unexpected_event);
-                        },
-                    }
+                    self.process_network_event(network_id, event).await;
                 },
+                served_chunk = self.pending_chunk_serves.select_next_some() => {
+                    self.process_served_chunk(served_chunk);
+                }
+                _ = self.pending_mempool_acks.select_next_some() => {
+                    counters::COMMIT_PIPELINE_DEPTH.set(self.pending_mempool_acks.len() as i64);
+                }
                 _ = interval.select_next_some() => {
-                    self.check_progress();
+                    self.process_tick(&mut network_events).await;
                 }
             }
+
+            let new_tick_interval = self.tick_interval();
+            if new_tick_interval != tick_interval {
+                tick_interval = new_tick_interval;
+                interval = tokio::time::interval(tick_interval).fuse();
+            }
         }
     }
 
-    pub(crate) async fn process_one_message(
+    async fn process_network_event(
         &mut self,
-        peer: PeerNetworkId,
-        msg: StateSynchronizerMsg,
+        network_id: NodeNetworkId,
+        event: Event<StateSynchronizerMsg>,
     ) {
-        match msg {
-            StateSynchronizerMsg::GetChunkRequest(request) => {
-                let _timer = counters::PROCESS_MSG_LATENCY
-                    .with_label_values(&[
-                        &peer.raw_network_id().to_string(),
-                        &peer.peer_id().to_string(),
-                        counters::CHUNK_REQUEST_MSG_LABEL,
-                    ])
-                    .start_timer();
-                let result_label =
-                    if let Err(err) = self.process_chunk_request(peer.clone(), *request.clone()) {
-                        error!(
-                            LogSchema::event_log(LogEntry::ProcessChunkRequest, LogEvent::Fail)
-                                .peer(&peer)
-                                .error(&err)
-                                .local_li_version(
-                                    self.local_state.highest_local_li.ledger_info().version()
-                                )
-                                .chunk_req(&request)
-                        );
-                        counters::FAIL_LABEL
-                    } else {
-                        counters::SUCCESS_LABEL
-                    };
-                counters::PROCESS_CHUNK_REQUEST_COUNT
-                    .with_label_values(&[
-                        &peer.raw_network_id().to_string(),
-                        &peer.peer_id().to_string(),
-                        result_label,
-                    ])
-                    .inc();
+        match event {
+            Event::NewPeer(peer_id, origin) => {
+                self.process_new_peer(PeerNetworkId(network_id, peer_id), origin);
             }
-            StateSynchronizerMsg::GetChunkResponse(response) => {
-                let _timer = counters::PROCESS_MSG_LATENCY
-                    .with_label_values(&[
-                        &peer.raw_network_id().to_string(),
-                        &peer.peer_id().to_string(),
-                        counters::CHUNK_RESPONSE_MSG_LABEL,
-                    ])
+            Event::LostPeer(peer_id, origin) => {
+                self.process_lost_peer(PeerNetworkId(network_id, peer_id), origin);
+            }
+            Event::Message(peer_id, message) => {
+                self.process_one_message(PeerNetworkId(network_id, peer_id), message)
+                    .await
+            }
+            unexpected_event => {
+                counters::NETWORK_ERROR_COUNT.inc();
+                warn!(
+                    LogSchema::new(LogEntry::NetworkError),
+                    "received unexpected network event: {:?}", unexpected_event
+                );
+            }
+        }
+    }
+
+    /// Runs the periodic routines. With `prioritize_serving` set, the chunk requests that are
+    /// already pending are served first, so that a node serving many downstream peers under
+    /// heavy load doesn't keep them waiting while it issues its own chunk requests. The draining
+    /// stops at the first pending event that isn't a chunk request, which is left to the event
+    /// loop.
+    pub(crate) async fn process_tick<S>(&mut self, network_events: &mut Peekable<S>)
+    where
+        S: Stream<Item = (NodeNetworkId, Event<StateSynchronizerMsg>)> + Unpin,
+    {
+        if self.config.prioritize_serving {
+            let mut num_requests = 0;
+            while num_requests < MAX_PRIORITIZED_CHUNK_REQUESTS {
+                let next_is_chunk_request = future::poll_fn(|cx| {
+                    Pin::new(&mut *network_events)
+                        .poll_peek(cx)
+                        .map(|event| match event {
+                            Some((_, Event::Message(_, msg))) => {
+                                matches!(msg, StateSynchronizerMsg::GetChunkRequest(_))
+                            }
+                            _ => false,
+                        })
+                })
+                .now_or_never()
+                .unwrap_or(false);
+                if !next_is_chunk_request {
+                    break;
+                }
+                if let Some(Some((network_id, event))) = network_events.next().now_or_never() {
+                    self.process_network_event(network_id, event).await;
+                    num_requests += 1;
+                }
+            }
+            if num_requests > 0 {
+                counters::PRIORITIZED_CHUNK_REQUESTS.observe(num_requests as f64);
+            }
+        }
+        self.flush_mempool_commit_batch().await;
+        if self.deferred_subscription_deliveries > 0 {
+            self.check_subscriptions();
+        }
+        self.check_waypoint_init_timeout();
+        self.check_progress();
+        self.checkpoint_if_due();
+    }
+
+    /// Ticks at `tick_interval_ms` while actively syncing, to promptly detect timeouts and issue
+    /// requests, and at `idle_tick_interval_ms` (if set) while idle and caught up.
+    pub(crate) fn tick_interval(&self) -> Duration {
+        let active = self.config.idle_tick_interval_ms == 0
+            || self.sync_request.is_some()
+            || !self.is_initialized()
+            || self.lagging_behind_target
+            || !self.reorder_buffer.is_empty();
+        let tick_interval_ms = if active {
+            self.config.tick_interval_ms
+        } else {
+            std::cmp::max(
+                self.config.idle_tick_interval_ms,
+                self.config.tick_interval_ms,
+            )
+        };
+        Duration::from_millis(tick_interval_ms)
+    }
+
+    /// Sends the chunk request for the current local version to all the upstream networks right
+    /// away, e.g. for an operator to unstick a node waiting on a slow primary upstream.
+    pub(crate) fn force_multicast(&mut self) -> Result<()> {
+        ensure!(
+            self.role != RoleType::Validator
+                || self.sync_request.is_some()
+                || !self.is_initialized(),
+            "[state sync] No sync in progress to multicast chunk requests for"
+        );
+        self.sync_state_with_local_storage()?;
+        let known_version = self.local_state.highest_version_in_local_storage();
+        self.request_manager.force_multicast(known_version);
+        self.send_chunk_request(known_version, self.local_state.epoch())
+    }
+
+    pub(crate) fn process_new_peer(&mut self, peer: PeerNetworkId, origin: ConnectionOrigin) {
+        if let Some(request_info) = self.subscriptions.get_mut(&peer) {
+            request_info.revoked = false;
+        }
+        if self.config.advertise_capabilities {
+            self.send_capabilities(&peer);
+        }
+        let newly_enabled = self.request_manager.enable_peer(peer.clone(), origin);
+        if newly_enabled && self.config.warm_up_new_peers {
+            self.send_warm_up_probe(&peer);
+        }
+        self.check_progress();
+    }
+
+    /// Advertises the optional protocol features supported by this node to a newly connected
+    /// peer, whether upstream or downstream (only with `advertise_capabilities` set).
+    fn send_capabilities(&mut self, peer: &PeerNetworkId) {
+        let msg = StateSynchronizerMsg::Capabilities(SyncCapabilities::local());
+        if let Err(e) = self
+            .network_senders
+            .get_mut(&peer.network_id())
+            .expect("missing network sender")
+            .send_to(peer.peer_id(), msg)
+        {
+            warn!(
+                LogSchema::event_log(LogEntry::NewPeer, LogEvent::Capabilities)
+                    .peer(peer)
+                    .error(&e.into())
+            );
+        }
+    }
+
+    /// Sends a warm-up probe to a newly enabled upstream peer, so that the connection is
+    /// established by the time the first chunk request is sent to it.
+    fn send_warm_up_probe(&mut self, peer: &PeerNetworkId) {
+        if let Err(e) = self.request_manager.send_warm_up_probe(peer) {
+            warn!(
+                LogSchema::event_log(LogEntry::NewPeer, LogEvent::WarmUpProbe)
+                    .peer(peer)
+                    .error(&e)
+            );
+        }
+    }
+
+    pub(crate) fn process_lost_peer(&mut self, peer: PeerNetworkId, origin: ConnectionOrigin) {
+        // the subscription is kept until it's ready or expires in case the peer reconnects
+        if let Some(request_info) = self.subscriptions.get_mut(&peer) {
+            request_info.revoked = true;
+        }
+        self.request_manager.disable_peer(&peer, origin);
+        self.check_low_peer_count();
+    }
+
+    pub(crate) async fn process_one_message(
+        &mut self,
+        peer: PeerNetworkId,
+        msg: StateSynchronizerMsg,
+    ) {
+        match msg {
+            StateSynchronizerMsg::GetChunkRequest(request) => {
+                let _timer = counters::PROCESS_MSG_LATENCY
+                    .with_label_values(&[
+                        &peer.raw_network_id().to_string(),
+                        &peer.peer_id().to_string(),
+                        counters::CHUNK_REQUEST_MSG_LABEL,
+                    ])
+                    .start_timer();
+                let result_label = match self.process_chunk_request(peer.clone(), *request.clone())
+                {
+                    Err(err) if err.downcast_ref::<StorageUnavailable>().is_some() => {
+                        error!(LogSchema::event_log(
+                            LogEntry::ProcessChunkRequest,
+                            LogEvent::StorageUnavailable
+                        )
+                        .peer(&peer)
+                        .error(&err)
+                        .chunk_req(&request));
+                        counters::STORAGE_UNAVAILABLE_LABEL
+                    }
+                    Err(err) => {
+                        error!(
+                            LogSchema::event_log(LogEntry::ProcessChunkRequest, LogEvent::Fail)
+                                .peer(&peer)
+                                .error(&err)
+                                .local_li_version(
+                                    self.local_state.highest_local_li.ledger_info().version()
+                                )
+                                .chunk_req(&request)
+                        );
+                        counters::FAIL_LABEL
+                    }
+                    Ok(()) => counters::SUCCESS_LABEL,
+                };
+                counters::PROCESS_CHUNK_REQUEST_COUNT
+                    .with_label_values(&[
+                        &peer.raw_network_id().to_string(),
+                        &peer.peer_id().to_string(),
+                        result_label,
+                    ])
+                    .inc();
+            }
+            StateSynchronizerMsg::GetChunkResponse(response) => {
+                let _timer = counters::PROCESS_MSG_LATENCY
+                    .with_label_values(&[
+                        &peer.raw_network_id().to_string(),
+                        &peer.peer_id().to_string(),
+                        counters::CHUNK_RESPONSE_MSG_LABEL,
+                    ])
                     .start_timer();
                 self.process_chunk_response(&peer, *response).await;
             }
+            StateSynchronizerMsg::Capabilities(capabilities) => {
+                debug!(LogSchema::event_log(LogEntry::NewPeer, LogEvent::Capabilities).peer(&peer));
+                self.request_manager
+                    .set_peer_capabilities(peer, capabilities);
+            }
+            StateSynchronizerMsg::WarmUpProbe => {
+                let msg = StateSynchronizerMsg::WarmUpProbeResponse;
+                if let Err(e) = self
+                    .network_senders
+                    .get_mut(&peer.network_id())
+                    .expect("missing network sender")
+                    .send_to(peer.peer_id(), msg)
+                {
+                    warn!(LogSchema::event_log(
+                        LogEntry::ProcessChunkRequest,
+                        LogEvent::WarmUpProbe
+                    )
+                    .peer(&peer)
+                    .error(&e.into()));
+                }
+            }
+            StateSynchronizerMsg::WarmUpProbeResponse => {
+                if self.request_manager.process_warm_up_response(&peer) {
+                    debug!(
+                        LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::WarmUpProbe)
+                            .peer(&peer),
+                        "Received response to warm-up probe"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Persists the resumable coordinator state if `checkpoint_interval_ms` passed since the
+    /// latest checkpoint.
+    pub(crate) fn checkpoint_if_due(&mut self) {
+        if self.config.checkpoint_interval_ms == 0 {
+            return;
+        }
+        let checkpoint_interval = Duration::from_millis(self.config.checkpoint_interval_ms);
+        let is_due = SystemTime::now()
+            .duration_since(self.last_checkpoint_tst)
+            .map_or(false, |elapsed| elapsed >= checkpoint_interval);
+        if !is_due {
+            return;
+        }
+        self.last_checkpoint_tst = SystemTime::now();
+
+        let checkpoint = SyncCheckpoint {
+            pending_ledger_infos: self.pending_ledger_infos.ledger_infos(),
+            peer_scores: self
+                .request_manager
+                .peer_scores()
+                .into_iter()
+                .map(|(peer, score)| (peer, (score * 1000.0) as u64))
+                .collect(),
+        };
+        let result_label = if let Err(e) = self.executor_proxy.save_checkpoint(&checkpoint) {
+            error!(LogSchema::event_log(LogEntry::Checkpoint, LogEvent::Fail).error(&e));
+            counters::FAIL_LABEL
+        } else {
+            counters::SUCCESS_LABEL
+        };
+        counters::CHECKPOINT_COUNT
+            .with_label_values(&[result_label])
+            .inc();
+    }
+
+    /// Journals the reconfiguration events of a commit before the commit is processed, so that
+    /// they are not lost if the node crashes before publishing them.
+    pub(crate) fn journal_reconfig_events(&mut self, events: &[ContractEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        if let Err(e) = self.executor_proxy.save_reconfig_journal(events) {
+            error!(LogSchema::event_log(LogEntry::Reconfig, LogEvent::JournalFail).error(&e));
+        }
+    }
+
+    /// Publishes the reconfiguration events of a commit to the subscribed components and clears
+    /// the journal (if any).
+    fn publish_reconfig_events(&mut self, events: Vec<ContractEvent>) {
+        let has_events = !events.is_empty();
+        if let Err(e) = self.executor_proxy.publish_on_chain_config_updates(events) {
+            counters::RECONFIG_PUBLISH_COUNT
+                .with_label_values(&[counters::FAIL_LABEL])
+                .inc();
+            error!(LogSchema::event_log(LogEntry::Reconfig, LogEvent::Fail).error(&e));
+        }
+        if has_events && self.config.journal_reconfig_events {
+            if let Err(e) = self.executor_proxy.save_reconfig_journal(&[]) {
+                error!(LogSchema::event_log(LogEntry::Reconfig, LogEvent::JournalFail).error(&e));
+            }
         }
     }
 
+    /// Publishes the reconfiguration events that were journaled but possibly not published
+    /// before the node went down.
+    fn republish_journaled_reconfig_events(&mut self) {
+        let events = match self.executor_proxy.load_reconfig_journal() {
+            Ok(events) => events,
+            Err(e) => {
+                error!(LogSchema::event_log(LogEntry::Reconfig, LogEvent::JournalFail).error(&e));
+                return;
+            }
+        };
+        if events.is_empty() {
+            return;
+        }
+        info!(
+            LogSchema::event_log(LogEntry::Reconfig, LogEvent::Republish).count(events.len()),
+            "Publishing reconfiguration events journaled before the restart"
+        );
+        self.publish_reconfig_events(events);
+    }
+
+    /// Seeds the pending LIs with the target LI persisted before a restart, provided that it's
+    /// still verifiable against the local epoch state.
+    fn seed_target_li(&mut self, target_li: LedgerInfoWithSignatures) {
+        if let Err(e) = self.local_state.trusted_epoch.verify(&target_li) {
+            warn!(
+                LogSchema::event_log(LogEntry::PersistedTargetLi, LogEvent::Fail).error(&e),
+                "discarded persisted target LI at version {}",
+                target_li.ledger_info().version()
+            );
+            return;
+        }
+        info!(
+            LogSchema::event_log(LogEntry::PersistedTargetLi, LogEvent::Initialize),
+            "seeded persisted target LI at version {}",
+            target_li.ledger_info().version()
+        );
+        self.pending_ledger_infos.add_li(target_li);
+        self.pending_ledger_infos
+            .update(&self.local_state, self.chunk_limit_controller.limit());
+    }
+
+    pub(crate) fn best_target_li(&self) -> Option<LedgerInfoWithSignatures> {
+        self.pending_ledger_infos.highest_li()
+    }
+
+    /// Resumes the coordinator state from the latest checkpoint (if any).
+    fn restore_checkpoint(&mut self) {
+        let checkpoint = match self.executor_proxy.load_checkpoint() {
+            Ok(Some(checkpoint)) => checkpoint,
+            Ok(None) => return,
+            Err(e) => {
+                error!(LogSchema::event_log(LogEntry::Checkpoint, LogEvent::Fail).error(&e));
+                return;
+            }
+        };
+        for li in checkpoint.pending_ledger_infos {
+            // LIs that were committed or belong to an epoch that has ended since the checkpoint
+            // are not of any use anymore
+            if self.local_state.trusted_epoch.verify(&li).is_ok() {
+                self.pending_ledger_infos.add_li(li);
+            }
+        }
+        self.pending_ledger_infos
+            .update(&self.local_state, self.chunk_limit_controller.limit());
+        for (peer, score) in checkpoint.peer_scores {
+            self.request_manager
+                .restore_score(peer, score as f64 / 1000.0);
+        }
+        info!(LogSchema::event_log(
+            LogEntry::Checkpoint,
+            LogEvent::Initialize
+        ));
+    }
+
+    #[cfg(test)]
+    pub fn peer_score(&self, peer: &PeerNetworkId) -> Option<f64> {
+        self.request_manager.peer_score(peer)
+    }
+
+    #[cfg(test)]
+    pub fn peer_capabilities(&self, peer: &PeerNetworkId) -> SyncCapabilities {
+        self.request_manager.peer_capabilities(peer)
+    }
+
+    #[cfg(test)]
+    pub fn request_timeout(&self, version: u64) -> Duration {
+        self.request_manager.get_request_timeout(version)
+    }
+
     /// Sync up coordinator state with the local storage
     /// and updates the pending ledger info accordingly
     fn sync_state_with_local_storage(&mut self) -> Result<()> {
@@ -371,27 +1551,413 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             info!(LogSchema::new(LogEntry::EpochChange)
                 .old_epoch(self.local_state.epoch())
                 .new_epoch(new_state.epoch()));
+            self.notify_epoch_change(EpochChangeNotification {
+                old_epoch: self.local_state.epoch(),
+                new_epoch: new_state.epoch(),
+                version: new_state.highest_local_li.ledger_info().version(),
+            });
         }
+        let old_version = self.local_state.highest_local_li.ledger_info().version();
+        let old_epoch = self.local_state.epoch();
+        let regressed = new_state.highest_local_li.ledger_info().version() < old_version
+            || new_state.epoch() < old_epoch;
         self.local_state = new_state;
+        if self.waypoint_invalidated
+            && self.waypoint.version() <= self.local_state.highest_local_li.ledger_info().version()
+            && self.verify_waypoint_in_storage(&self.waypoint).is_ok()
+        {
+            self.waypoint_invalidated = false;
+        }
 
         self.pending_ledger_infos
-            .update(&self.local_state, self.config.chunk_limit);
-        Ok(())
+            .update(&self.local_state, self.chunk_limit_controller.limit());
+        let result = if regressed {
+            self.process_storage_regression(old_version, old_epoch)
+        } else {
+            Ok(())
+        };
+        self.check_phase_change();
+        result
     }
 
-    /// Verify that the local state's latest LI version (i.e. committed version) has reached the waypoint version.
-    fn is_initialized(&self) -> bool {
-        self.waypoint.version() <= self.local_state.highest_local_li.ledger_info().version()
+    /// Handles the committed version or epoch in storage moving backward, which should never
+    /// happen unless the storage got corrupted or was reset externally.
+    /// A node that fell behind its waypoint is back in the waypoint sync phase. Otherwise, if
+    /// `reverify_waypoint_on_regression` is set, the waypoint is verified again against the
+    /// epoch-ending LI that is now in storage: on a mismatch, the node is back in the waypoint
+    /// sync phase as well until the storage matches the waypoint again.
+    fn process_storage_regression(&mut self, old_version: Version, old_epoch: u64) -> Result<()> {
+        counters::STORAGE_REGRESSION_COUNT.inc();
+        error!(
+            LogSchema::event_log(LogEntry::LocalState, LogEvent::StorageRegression)
+                .local_li_version(self.local_state.highest_local_li.ledger_info().version())
+                .local_epoch(self.local_state.epoch()),
+            "committed state moved backward from version {} in epoch {}", old_version, old_epoch
+        );
+        if !self.config.reverify_waypoint_on_regression || !self.is_initialized() {
+            return Ok(());
+        }
+        self.verify_waypoint_in_storage(&self.waypoint)
+            .map_err(|e| {
+                self.waypoint_invalidated = true;
+                e
+            })
+    }
+
+    /// Verifies `waypoint` against the epoch-ending LI at its version in the local storage.
+    fn verify_waypoint_in_storage(&self, waypoint: &Waypoint) -> Result<()> {
+        let waypoint_li = self
+            .executor_proxy
+            .get_epoch_ending_ledger_info(waypoint.version())?;
+        waypoint.verify(waypoint_li.ledger_info()).map_err(|e| {
+            format_err!(
+                "[state sync] Waypoint {} does not match the local storage: {}",
+                waypoint,
+                e
+            )
+        })
     }
 
-    fn set_initialization_listener(&mut self, cb_sender: oneshot::Sender<Result<()>>) {
+    /// Sends `notification` to the epoch change sender (if any), without waiting for room in
+    /// the channel: the notification is dropped if the channel is full.
+    fn notify_epoch_change(&mut self, notification: EpochChangeNotification) {
+        if let Some(sender) = self.epoch_change_sender.as_mut() {
+            if let Err(e) = sender.try_send(notification) {
+                warn!(
+                    LogSchema::new(LogEntry::EpochChange),
+                    "failed to send epoch change notification: {}", e
+                );
+            }
+        }
+    }
+
+    fn current_phase(&self) -> SyncPhase {
         if self.is_initialized() {
-            if let Err(e) = Self::send_initialization_callback(cb_sender, Ok(())) {
-                error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail).error(&e));
+            SyncPhase::Normal
+        } else {
+            SyncPhase::WaypointSync
+        }
+    }
+
+    pub(crate) fn set_phase_change_sink(&mut self, sink: mpsc::Sender<SyncPhaseChange>) {
+        self.phase_change_sink = Some(sink);
+    }
+
+    pub(crate) fn set_low_peer_count_sink(&mut self, sink: mpsc::Sender<usize>) {
+        self.low_peer_count_sink = Some(sink);
+    }
+
+    pub(crate) fn set_completion_sink(&mut self, sink: mpsc::Sender<SyncRequestRecord>) {
+        self.completion_sink = Some(sink);
+    }
+
+    /// Replaces the serving policy. The new requests of the peers it disallows are rejected, and
+    /// so are their pending subscriptions if `revalidate_subscribers` is set.
+    pub(crate) fn set_serving_policy(&mut self, policy: ServingPolicy) {
+        self.serving_policy = policy;
+    }
+
+    /// Pushes the record of the pending sync request, completed with `outcome`, to the
+    /// completion sink (if any).
+    /// The request timeout raised for the long poll of the sync request (if any) is restored.
+    fn record_sync_request_completion(&mut self, outcome: SyncRequestOutcome) {
+        self.request_manager
+            .set_request_timeout(Duration::from_millis(Self::base_request_timeout_ms(
+                self.role,
+                &self.config,
+            )));
+        let progress = match self.sync_request_progress.take() {
+            Some(progress) => progress,
+            None => return,
+        };
+        if let Some(sink) = self.completion_sink.as_mut() {
+            let record = SyncRequestRecord {
+                target_version: progress.target_version,
+                start_time: progress.start_time,
+                end_time: SystemTime::now(),
+                chunks_applied: progress.chunks_applied,
+                bytes_transferred: progress.bytes_transferred,
+                outcome,
+            };
+            if sink.try_send(record).is_err() {
+                counters::SYNC_REQUEST_RECORD_DROP_COUNT.inc();
+            }
+        }
+    }
+
+    /// Signals the low peer count sink (if any) when fewer than `min_upstream_peers` upstream
+    /// peers are enabled, so that more peers are sought proactively.
+    fn check_low_peer_count(&mut self) {
+        let num_peers = self.request_manager.num_enabled_peers();
+        if num_peers >= self.config.min_upstream_peers {
+            return;
+        }
+        if let Some(sink) = self.low_peer_count_sink.as_mut() {
+            warn!(
+                LogSchema::event_log(LogEntry::LostPeer, LogEvent::LowPeerCount).count(num_peers),
+                "Only {} upstream peers left, below the minimum of {}",
+                num_peers,
+                self.config.min_upstream_peers
+            );
+            if sink.try_send(num_peers).is_err() {
+                counters::LOW_PEER_COUNT_SIGNAL_DROP_COUNT.inc();
+            }
+        }
+    }
+
+    pub(crate) fn set_read_replica(&mut self, read_replica: T) {
+        self.read_replica = Some(read_replica);
+    }
+
+    /// Returns the proxy chunks up to `version` are served from: the read replica (if any) once
+    /// it has caught up with `version`, else the primary storage, unless falling back to it is
+    /// disabled.
+    fn serving_proxy(&self, version: Version) -> Option<&T> {
+        let read_replica = match self.read_replica.as_ref() {
+            Some(read_replica) => read_replica,
+            None => return Some(&self.executor_proxy),
+        };
+        let replica_version = read_replica
+            .get_local_storage_state()
+            .map(|state| state.highest_local_li.ledger_info().version());
+        if matches!(replica_version, Ok(replica_version) if replica_version >= version) {
+            return Some(read_replica);
+        }
+        counters::READ_REPLICA_LAG_COUNT.inc();
+        if self.config.read_replica_fallback {
+            Some(&self.executor_proxy)
+        } else {
+            None
+        }
+    }
+
+    /// Epoch-ending LIs are served from the read replica (if any), falling back to the primary
+    /// storage if the replica doesn't have them yet.
+    /// Fails with `EpochLookupRateLimited` if the lookup budget is exhausted.
+    fn serve_epoch_proof(&mut self, epoch: u64) -> Result<LedgerInfoWithSignatures> {
+        self.acquire_epoch_lookup()?;
+        self.serve_from_replica(|proxy| proxy.get_epoch_proof(epoch))
+    }
+
+    fn serve_epoch_ending_ledger_info(
+        &mut self,
+        version: Version,
+    ) -> Result<LedgerInfoWithSignatures> {
+        self.acquire_epoch_lookup()?;
+        self.serve_from_replica(|proxy| proxy.get_epoch_ending_ledger_info(version))
+    }
+
+    fn acquire_epoch_lookup(&mut self) -> Result<()> {
+        if self.epoch_lookup_rate_limiter.try_acquire() {
+            Ok(())
+        } else {
+            counters::RATE_LIMITED_EPOCH_LOOKUP_COUNT.inc();
+            Err(EpochLookupRateLimited.into())
+        }
+    }
+
+    fn serve_from_replica<R>(&self, read: impl Fn(&T) -> Result<R>) -> Result<R> {
+        match self.read_replica.as_ref().map(&read) {
+            None => read(&self.executor_proxy),
+            Some(Ok(result)) => Ok(result),
+            Some(Err(e)) => {
+                counters::READ_REPLICA_LAG_COUNT.inc();
+                if self.config.read_replica_fallback {
+                    read(&self.executor_proxy)
+                } else {
+                    Err(e)
+                }
             }
+        }
+    }
+
+    /// Pushes the transition to the phase change sink (if any) when the phase changed since
+    /// the last check.
+    fn check_phase_change(&mut self) {
+        let new_phase = self.current_phase();
+        if new_phase == self.phase {
+            return;
+        }
+        let change = SyncPhaseChange {
+            old_phase: self.phase,
+            new_phase,
+            version: self.local_state.highest_local_li.ledger_info().version(),
+        };
+        self.phase = new_phase;
+        info!(
+            LogSchema::event_log(LogEntry::Waypoint, LogEvent::PhaseChange)
+                .local_li_version(change.version),
+            "sync phase changed from {:?} to {:?}", change.old_phase, change.new_phase
+        );
+        if let Some(sink) = self.phase_change_sink.as_mut() {
+            if sink.try_send(change).is_err() {
+                counters::PHASE_CHANGE_NOTIFICATION_DROP_COUNT.inc();
+            }
+        }
+    }
+
+    /// Verify that the local state's latest LI version (i.e. committed version) has reached the waypoint version.
+    fn is_initialized(&self) -> bool {
+        !self.waypoint_invalidated
+            && self.waypoint.version() <= self.local_state.highest_local_li.ledger_info().version()
+    }
+
+    pub(crate) fn set_initialization_listener(
+        &mut self,
+        cb_sender: oneshot::Sender<Result<InitializationResult>>,
+    ) {
+        let result = if self.is_initialized() {
+            Ok(InitializationResult::AlreadyInitialized)
+        } else if let Some(version) = self.waypoint_mismatch_version {
+            Err(self.waypoint_mismatch_error(version))
+        } else if self.waypoint_init_timed_out {
+            Err(self.waypoint_init_timeout_error())
         } else {
             self.initialization_listener = Some(cb_sender);
+            return;
+        };
+        if let Err(e) = Self::send_initialization_callback(cb_sender, result) {
+            error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail).error(&e));
+        }
+    }
+
+    /// Fails the initialization listener if the waypoint was not reached within
+    /// `waypoint_init_timeout_ms`. The node keeps syncing to the waypoint afterwards.
+    pub(crate) fn check_waypoint_init_timeout(&mut self) {
+        if self.config.waypoint_init_timeout_ms == 0
+            || self.waypoint_init_timed_out
+            || self.is_initialized()
+        {
+            return;
+        }
+        let init_timeout = Duration::from_millis(self.config.waypoint_init_timeout_ms);
+        let timed_out = SystemTime::now()
+            .duration_since(self.init_start_tst)
+            .map_or(false, |elapsed| elapsed >= init_timeout);
+        if !timed_out {
+            return;
+        }
+
+        self.waypoint_init_timed_out = true;
+        error!(
+            LogSchema::event_log(LogEntry::Waypoint, LogEvent::WaypointInitTimeout)
+                .waypoint(self.waypoint)
+                .local_li_version(self.local_state.highest_local_li.ledger_info().version()),
+            "waypoint not reached within {} ms", self.config.waypoint_init_timeout_ms
+        );
+        if let Some(listener) = self.initialization_listener.take() {
+            if let Err(e) = Self::send_initialization_callback(
+                listener,
+                Err(self.waypoint_init_timeout_error()),
+            ) {
+                error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail).error(&e));
+            }
+        }
+    }
+
+    /// Records that the waypoint LI at `version` sent by `peer` failed the waypoint verification.
+    /// Once enough distinct peers failed it at the same version, the waypoint itself is likely
+    /// wrong: the initialization listener is failed instead of retrying silently forever.
+    fn process_waypoint_mismatch(&mut self, peer: &PeerNetworkId, version: Version) {
+        counters::WAYPOINT_MISMATCH_COUNT.inc();
+        let threshold = self.config.waypoint_mismatch_peer_threshold;
+        if threshold == 0 || self.waypoint_mismatch_version.is_some() {
+            return;
+        }
+        let peers = self.waypoint_mismatch_peers.entry(version).or_default();
+        peers.insert(peer.clone());
+        if peers.len() < threshold {
+            return;
+        }
+
+        self.waypoint_mismatch_version = Some(version);
+        self.waypoint_mismatch_peers.clear();
+        error!(
+            LogSchema::event_log(LogEntry::Waypoint, LogEvent::WaypointMismatch)
+                .waypoint(self.waypoint),
+            "{} distinct peers failed the waypoint verification at version {}", threshold, version
+        );
+        if let Some(listener) = self.initialization_listener.take() {
+            if let Err(e) = Self::send_initialization_callback(
+                listener,
+                Err(self.waypoint_mismatch_error(version)),
+            ) {
+                error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail).error(&e));
+            }
+        }
+    }
+
+    fn waypoint_mismatch_error(&self, version: Version) -> anyhow::Error {
+        format_err!(
+            "[state sync] Waypoint {} failed verification against the LIs at version {} of {} \
+             distinct peers: the waypoint is likely misconfigured",
+            self.waypoint,
+            version,
+            self.config.waypoint_mismatch_peer_threshold
+        )
+    }
+
+    /// Replaces the waypoint of a node that hasn't reached it yet, e.g. to recover from a bootstrap
+    /// with a stale or wrong waypoint without a restart. The synced history is never rewritten:
+    /// the update is rejected once initialized, if the new waypoint is behind the current one, or
+    /// if the local storage is past the new waypoint already but doesn't match it.
+    /// The initialization listener (if any) is notified in case the local storage already reached
+    /// the new waypoint.
+    pub(crate) fn update_waypoint(&mut self, waypoint: Waypoint) -> Result<()> {
+        ensure!(
+            !self.is_initialized(),
+            "[state sync] Cannot update waypoint {} to {}: already initialized",
+            self.waypoint,
+            waypoint
+        );
+        ensure!(
+            waypoint.version() >= self.waypoint.version(),
+            "[state sync] Cannot update waypoint {} to {}: the waypoint cannot move backward",
+            self.waypoint,
+            waypoint
+        );
+        // the local storage might have moved past the new waypoint already, e.g. restored from a
+        // backup while the node was waiting for the waypoint
+        self.sync_state_with_local_storage()?;
+        let local_li_version = self.local_state.highest_local_li.ledger_info().version();
+        if waypoint.version() <= local_li_version {
+            self.verify_waypoint_in_storage(&waypoint)?;
+        }
+        info!(
+            LogSchema::event_log(LogEntry::Waypoint, LogEvent::WaypointUpdate).waypoint(waypoint),
+            "waypoint updated from {}", self.waypoint
+        );
+        self.waypoint = waypoint;
+        // the failures observed and the epoch proofs verified for the previous waypoint don't
+        // apply to the new one
+        self.waypoint_mismatch_version = None;
+        self.waypoint_mismatch_peers.clear();
+        self.waypoint_init_timed_out = false;
+        self.init_start_tst = SystemTime::now();
+        self.epoch_proofs.clear();
+        // the invalidation was relative to the previous waypoint, the storage was verified against
+        // the new one above if it reached it already
+        self.waypoint_invalidated = false;
+        self.check_phase_change();
+
+        if self.is_initialized() {
+            info!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::Complete)
+                .local_li_version(local_li_version)
+                .local_synced_version(self.local_state.highest_version_in_local_storage())
+                .local_epoch(self.local_state.epoch()));
+            if let Some(listener) = self.initialization_listener.take() {
+                Self::send_initialization_callback(listener, Ok(InitializationResult::Reached))?;
+            }
         }
+        Ok(())
+    }
+
+    fn waypoint_init_timeout_error(&self) -> anyhow::Error {
+        format_err!(
+            "[state sync] Waypoint {} not reached within {} ms",
+            self.waypoint,
+            self.config.waypoint_init_timeout_ms
+        )
     }
 
     /// In case there has been another pending request it's going to be overridden.
@@ -400,7 +1966,7 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
     /// target LI.
     /// StateSynchronizer assumes that it's the only one modifying the storage (consensus is not
     /// trying to commit transactions concurrently).
-    fn request_sync(&mut self, request: SyncRequest) -> Result<()> {
+    pub(crate) fn request_sync(&mut self, request: SyncRequest) -> Result<()> {
         let local_li_version = self.local_state.highest_local_li.ledger_info().version();
         let target_version = request.target.ledger_info().version();
         debug!(
@@ -409,11 +1975,17 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 .local_li_version(local_li_version)
         );
 
-        self.sync_state_with_local_storage()?;
-        ensure!(
-            self.is_initialized(),
-            "[state sync] Sync request but initialization is not complete!"
-        );
+        if let Err(e) = self.sync_state_with_local_storage() {
+            Self::send_sync_req_callback(
+                request,
+                Err(format_err!("Failed to read the local storage: {}", e)),
+            )?;
+            return Err(e);
+        }
+        if !self.is_initialized() {
+            Self::send_sync_req_callback(request, Err(SyncRequestError::NotInitialized.into()))?;
+            bail!("[state sync] Sync request but initialization is not complete!");
+        }
         if target_version == local_li_version {
             return Self::send_sync_req_callback(request, Ok(()));
         }
@@ -427,6 +1999,47 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             );
         }
 
+        // a target in a future epoch cannot be verified with the current trusted validator set:
+        // it's reached epoch by epoch, with each epoch-ending LI verified along the way
+        let local_epoch = self.local_state.epoch();
+        let target_epoch = request.target.ledger_info().epoch();
+        let epoch_transitions = target_epoch.saturating_sub(local_epoch);
+        if epoch_transitions > 0 {
+            let max_epochs = self.config.max_sync_request_epochs;
+            if max_epochs > 0 && epoch_transitions > max_epochs {
+                Self::send_sync_req_callback(
+                    request,
+                    Err(format_err!("Sync request target too many epochs ahead")),
+                )?;
+                bail!(
+                    "[state sync] Sync request target is {} epochs ahead of local epoch {} (max {})",
+                    epoch_transitions,
+                    local_epoch,
+                    max_epochs
+                );
+            }
+            info!(
+                LogSchema::event_log(LogEntry::SyncRequest, LogEvent::MultiEpochTarget)
+                    .old_epoch(local_epoch)
+                    .new_epoch(target_epoch)
+                    .target_version(target_version),
+                "sync request target requires {} epoch transitions", epoch_transitions
+            );
+        }
+        self.sync_request_epoch_transitions = epoch_transitions;
+        counters::SYNC_REQUEST_REMAINING_EPOCHS.set(epoch_transitions as i64);
+
+        if self.sync_request.is_some() {
+            // the pending request is overridden
+            self.record_sync_request_completion(SyncRequestOutcome::Cancelled);
+        }
+        self.sync_request_progress = Some(SyncRequestProgress {
+            target_version,
+            start_time: SystemTime::now(),
+            chunks_applied: 0,
+            bytes_transferred: 0,
+            attempts: 0,
+        });
         self.sync_request = Some(request);
         self.send_chunk_request(
             self.local_state.highest_version_in_local_storage(),
@@ -437,7 +2050,7 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
     /// The function is called after new txns have been applied to the local storage.
     /// As a result it might:
     /// 1) help remote subscribers with long poll requests, 2) finish local sync request
-    async fn process_commit(
+    pub(crate) async fn process_commit(
         &mut self,
         transactions: Vec<Transaction>,
         commit_callback: Option<oneshot::Sender<Result<CommitResponse>>>,
@@ -457,59 +2070,42 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             .with_label_values(&[counters::COMMITTED_VERSION_LABEL])
             .set(committed_version as i64);
         counters::EPOCH.set(local_epoch as i64);
+        if self.is_initialized() {
+            self.epoch_proofs.clear();
+        } else {
+            // only the proofs of epochs that are not yet stored locally are still of use
+            self.epoch_proofs = self.epoch_proofs.split_off(&local_epoch);
+        }
+        self.detect_caught_up(synced_version);
+        let version_advanced = self.commit_interval_tracker.observe(synced_version);
+        if version_advanced {
+            if let Some(interval_ms) = self.commit_interval_tracker.interval_ms() {
+                counters::COMMIT_INTERVAL_MS.set(interval_ms as i64);
+            }
+        }
         debug!(LogSchema::new(LogEntry::LocalState)
             .local_li_version(committed_version)
             .local_synced_version(synced_version)
             .local_epoch(local_epoch));
-        let block_timestamp_usecs = self
-            .local_state
-            .highest_local_li
-            .ledger_info()
-            .timestamp_usecs();
-
-        // send notif to shared mempool
-        // filter for user transactions here
-        let mut committed_user_txns = vec![];
-        for txn in transactions {
-            if let Transaction::UserTransaction(signed_txn) = txn {
-                committed_user_txns.push(CommittedTransaction {
-                    sender: signed_txn.sender(),
-                    sequence_number: signed_txn.sequence_number(),
-                });
-            }
-        }
-        let (callback, callback_rcv) = oneshot::channel();
-        let req = CommitNotification {
-            transactions: committed_user_txns,
-            block_timestamp_usecs,
-            callback,
-        };
-        let mut mempool_channel = self.state_sync_to_mempool_sender.clone();
-        let mut msg = "";
-        if let Err(e) = mempool_channel.try_send(req) {
-            error!(
-                LogSchema::new(LogEntry::CommitFlow).error(&e.into()),
-                "failed to notify mempool of commit"
-            );
-            counters::COMMIT_FLOW_FAIL
-                .with_label_values(&[counters::TO_MEMPOOL_LABEL])
-                .inc();
-            msg = "state sync failed to send commit notif to shared mempool";
-        } else if let Err(e) = timeout(Duration::from_secs(5), callback_rcv).await {
-            error!(
-                LogSchema::new(LogEntry::CommitFlow).error(&e.into()),
-                "did not receive ACK for commit notification sent to mempool"
+        // a commit that didn't advance the synced version (e.g. after a no-op chunk) has nothing
+        // new to report
+        let notify = version_advanced || !self.config.skip_noop_commit_notifications;
+        let notify_result = if notify {
+            self.notify_mempool(transactions, commit_callback.is_some())
+                .await
+        } else {
+            counters::NOOP_COMMIT_COUNT.inc();
+            debug!(
+                LogSchema::new(LogEntry::CommitFlow).local_synced_version(synced_version),
+                "synced version unchanged, skipping mempool notification"
             );
-            counters::COMMIT_FLOW_FAIL
-                .with_label_values(&[counters::FROM_MEMPOOL_LABEL])
-                .inc();
-            msg = "state sync did not receive ACK for commit notification sent to mempool";
-        }
+            Ok("")
+        };
 
         if let Some(cb) = commit_callback {
             // send back ACK to consensus
             if cb
-                .send(Ok(CommitResponse {
+                .send(notify_result.map(|msg| CommitResponse {
                     msg: msg.to_string(),
                 }))
                 .is_err()
@@ -524,14 +2120,32 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             }
         }
 
-        self.check_subscriptions();
+        if notify || self.deferred_subscription_deliveries > 0 {
+            self.check_subscriptions();
+        }
         self.request_manager.remove_requests(synced_version);
         if let Some(peer) = chunk_sender {
             self.request_manager.process_success_response(peer);
         }
+        self.notify_version_waiters(synced_version);
 
+        if version_advanced {
+            if let Some(progress) = self.sync_request_progress.as_mut() {
+                progress.attempts = 0;
+            }
+        }
         if let Some(mut req) = self.sync_request.as_mut() {
             req.last_progress_tst = SystemTime::now();
+            let remaining_epochs = req.target.ledger_info().epoch().saturating_sub(local_epoch);
+            if remaining_epochs > 0 {
+                debug!(
+                    LogSchema::new(LogEntry::SyncRequest).local_epoch(local_epoch),
+                    "{} of {} sync request epoch transitions remaining",
+                    remaining_epochs,
+                    self.sync_request_epoch_transitions
+                );
+            }
+            counters::SYNC_REQUEST_REMAINING_EPOCHS.set(remaining_epochs as i64);
         }
         let sync_request_complete = match self.sync_request.as_ref() {
             Some(sync_req) => {
@@ -560,6 +2174,7 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 .with_label_values(&[counters::COMPLETE_LABEL])
                 .inc();
             if let Some(sync_request) = self.sync_request.take() {
+                self.record_sync_request_completion(SyncRequestOutcome::Complete);
                 Self::send_sync_req_callback(sync_request, Ok(()))?;
             }
         }
@@ -574,12 +2189,164 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 .local_synced_version(synced_version)
                 .local_epoch(local_epoch));
             if let Some(listener) = self.initialization_listener.take() {
-                Self::send_initialization_callback(listener, Ok(()))?;
+                Self::send_initialization_callback(listener, Ok(InitializationResult::Reached))?;
             }
         }
         Ok(())
     }
 
+    /// Notifies mempool of the committed transactions. Returns an error message for consensus
+    /// (empty if the notification was successful), or an error if mempool didn't ACK a consensus
+    /// commit in time and `fail_commit_on_mempool_timeout` is set.
+    /// If `mempool_commit_batch_size` is set, the txns of chunk commits are batched until enough
+    /// of them are accumulated.
+    async fn notify_mempool(
+        &mut self,
+        transactions: Vec<Transaction>,
+        is_consensus_commit: bool,
+    ) -> Result<&'static str> {
+        // filter for user transactions here
+        let mut committed_user_txns = vec![];
+        for txn in transactions {
+            if let Transaction::UserTransaction(signed_txn) = txn {
+                committed_user_txns.push(CommittedTransaction {
+                    sender: signed_txn.sender(),
+                    sequence_number: signed_txn.sequence_number(),
+                });
+            }
+        }
+
+        let batch_size = self.config.mempool_commit_batch_size;
+        if is_consensus_commit || batch_size == 0 {
+            // the chunk commits batched so far are reported before the consensus commit
+            self.flush_mempool_commit_batch().await;
+            return self
+                .send_commit_notification(committed_user_txns, is_consensus_commit)
+                .await;
+        }
+        let batch = self.mempool_commit_batch.get_or_insert_with(Vec::new);
+        batch.extend(committed_user_txns);
+        if batch.len() >= batch_size {
+            self.flush_mempool_commit_batch().await;
+        }
+        Ok("")
+    }
+
+    /// Notifies mempool of the batched chunk commits (if any).
+    pub(crate) async fn flush_mempool_commit_batch(&mut self) {
+        if let Some(batch) = self.mempool_commit_batch.take() {
+            counters::MEMPOOL_COMMIT_BATCH_SIZE.observe(batch.len() as f64);
+            // notifying mempool of chunk commits never fails, failures to do so are only logged
+            let _ = self.send_commit_notification(batch, false).await;
+        }
+    }
+
+    async fn send_commit_notification(
+        &mut self,
+        committed_user_txns: Vec<CommittedTransaction>,
+        is_consensus_commit: bool,
+    ) -> Result<&'static str> {
+        let block_timestamp_usecs = self
+            .local_state
+            .highest_local_li
+            .ledger_info()
+            .timestamp_usecs();
+
+        // send notif to shared mempool
+        let (callback, callback_rcv) = oneshot::channel();
+        let req = CommitNotification {
+            transactions: committed_user_txns,
+            block_timestamp_usecs,
+            callback,
+            source: if is_consensus_commit {
+                CommitSource::Consensus
+            } else {
+                CommitSource::StateSync
+            },
+        };
+        let mut mempool_channel = self.state_sync_to_mempool_sender.clone();
+        let ack_timeout = Duration::from_millis(self.config.mempool_commit_ack_timeout_ms);
+        let mut msg = "";
+        if let Err(e) = mempool_channel.try_send(req) {
+            error!(
+                LogSchema::new(LogEntry::CommitFlow).error(&e.into()),
+                "failed to notify mempool of commit"
+            );
+            counters::COMMIT_FLOW_FAIL
+                .with_label_values(&[counters::TO_MEMPOOL_LABEL])
+                .inc();
+            msg = "state sync failed to send commit notif to shared mempool";
+        } else if is_consensus_commit {
+            // consensus expects to learn the outcome of the mempool notification
+            if !Self::wait_for_mempool_ack(callback_rcv, ack_timeout).await {
+                ensure!(
+                    !self.config.fail_commit_on_mempool_timeout,
+                    "state sync did not receive ACK for commit notification sent to mempool \
+                     within {} ms",
+                    self.config.mempool_commit_ack_timeout_ms
+                );
+                msg = "state sync did not receive ACK for commit notification sent to mempool";
+            }
+        } else if self.config.max_commit_pipeline_depth() == 0 {
+            // without a commit pipeline each chunk commit awaits its ACK in turn
+            if !Self::wait_for_mempool_ack(callback_rcv, ack_timeout).await {
+                msg = "state sync did not receive ACK for commit notification sent to mempool";
+            }
+        } else {
+            // the ACK for a chunk commit is awaited in the background: applying further chunks
+            // is only held back once the commit pipeline is full
+            self.pending_mempool_acks
+                .push(Self::wait_for_mempool_ack(callback_rcv, ack_timeout).boxed());
+            counters::COMMIT_PIPELINE_DEPTH.set(self.pending_mempool_acks.len() as i64);
+        }
+        Ok(msg)
+    }
+
+    /// Fires a one-time event the first time the synced version reaches the target LI after
+    /// the node has been lagging behind it.
+    fn detect_caught_up(&mut self, synced_version: Version) {
+        let target_version = self
+            .pending_ledger_infos
+            .target_li()
+            .map(|li| li.ledger_info().version());
+        match target_version {
+            Some(target_version) if target_version > synced_version => {
+                self.lagging_behind_target = true;
+            }
+            _ => {
+                if self.lagging_behind_target {
+                    self.lagging_behind_target = false;
+                    info!(
+                        LogSchema::event_log(LogEntry::LocalState, LogEvent::CaughtUp)
+                            .local_synced_version(synced_version)
+                            .local_epoch(self.local_state.epoch())
+                    );
+                    counters::CAUGHT_UP_COUNT.inc();
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_lagging_behind_target(&self) -> bool {
+        self.lagging_behind_target
+    }
+
+    #[cfg(test)]
+    pub fn chunk_limit(&self) -> u64 {
+        self.chunk_limit_controller.limit()
+    }
+
+    #[cfg(test)]
+    pub fn last_sent_target(&self) -> Option<TargetType> {
+        self.last_sent_target.clone()
+    }
+
+    #[cfg(test)]
+    pub fn is_subscribed(&self, peer: &PeerNetworkId) -> bool {
+        self.subscriptions.contains_key(peer)
+    }
+
     fn get_state(&mut self, callback: oneshot::Sender<SynchronizerState>) {
         if let Err(e) = self.sync_state_with_local_storage() {
             error!(
@@ -606,29 +2373,81 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 .chunk_req(&request)
                 .local_li_version(self.local_state.highest_local_li.ledger_info().version())
         );
-        self.sync_state_with_local_storage()?;
+        ensure!(
+            self.serving_policy.allows(&peer),
+            "[state sync] Chunk request from peer {} not allowed by the serving policy",
+            peer.peer_id()
+        );
+        if let Err(e) = self.sync_state_with_local_storage() {
+            // explicitly notify the requester so that it can retry with another peer instead of
+            // waiting for a timeout
+            self.deliver_unavailable(peer)?;
+            return Err(e.context(StorageUnavailable));
+        }
 
-        match request.target().clone() {
-            TargetType::TargetLedgerInfo(li) => self.process_request_target_li(peer, request, li),
+        let result = match request.target().clone() {
+            TargetType::TargetLedgerInfo(li) => {
+                self.process_request_target_li(peer.clone(), request, li, 0)
+            }
             TargetType::HighestAvailable {
                 target_li,
                 timeout_ms,
-            } => self.process_request_highest_available(peer, request, target_li, timeout_ms),
+            } => {
+                self.process_request_highest_available(peer.clone(), request, target_li, timeout_ms)
+            }
             TargetType::Waypoint(waypoint_version) => {
-                self.process_request_waypoint(peer, request, waypoint_version)
+                self.process_request_waypoint(peer.clone(), request, waypoint_version, None)
+            }
+            TargetType::WaypointWithEpochProofs {
+                version,
+                max_epoch_proofs,
+            } => self.process_request_waypoint(
+                peer.clone(),
+                request,
+                version,
+                Some(max_epoch_proofs),
+            ),
+            TargetType::Tip => self.deliver_tip(peer.clone()),
+            TargetType::EpochEnding { epoch } => {
+                self.process_request_epoch_ending(peer.clone(), request, epoch)
+            }
+            TargetType::TargetLedgerInfoWithTimeout {
+                target_li,
+                timeout_ms,
+            } => self.process_request_target_li(peer.clone(), request, target_li, timeout_ms),
+        };
+        match result {
+            // the requester retries once the lookup budget is replenished
+            Err(e) if e.downcast_ref::<EpochLookupRateLimited>().is_some() => {
+                self.deliver_unavailable(peer)
             }
+            result => result,
         }
     }
 
-    /// Processing requests with a specified target LedgerInfo.
+    /// Processing requests with a specified target LedgerInfo and potentially long polling.
     /// Assumes that the local state is uptodate with storage.
     fn process_request_target_li(
         &mut self,
         peer: PeerNetworkId,
         request: GetChunkRequest,
         target_li: LedgerInfoWithSignatures,
+        timeout_ms: u64,
     ) -> Result<()> {
         let limit = std::cmp::min(request.limit, self.config.max_chunk_limit);
+        let max_timeout_ms = self.effective_max_timeout_ms();
+        counters::EFFECTIVE_MAX_TIMEOUT_MS.set(max_timeout_ms as i64);
+        let timeout = std::cmp::min(timeout_ms, max_timeout_ms);
+
+        // If there is nothing a node can help with yet, and the request supports long polling,
+        // add it to the subscriptions.
+        let local_version = self.local_state.highest_local_li.ledger_info().version();
+        self.check_subscription_version_margin(&peer, &request)?;
+        if local_version <= request.known_version && timeout > 0 {
+            self.add_subscription(peer, &request, limit, timeout, Some(target_li));
+            return Ok(());
+        }
+
         let response_li = self.choose_response_li(request.current_epoch, Some(target_li))?;
         // In case known_version is lower than the requested ledger info an empty response might be
         // sent.
@@ -636,6 +2455,7 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             peer,
             request.known_version,
             ResponseLedgerInfo::VerifiableLedgerInfo(response_li),
+            request.limit,
             limit,
         )
     }
@@ -651,22 +2471,16 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         timeout_ms: u64,
     ) -> Result<()> {
         let limit = std::cmp::min(request.limit, self.config.max_chunk_limit);
-        let timeout = std::cmp::min(timeout_ms, self.config.max_timeout_ms);
+        let max_timeout_ms = self.effective_max_timeout_ms();
+        counters::EFFECTIVE_MAX_TIMEOUT_MS.set(max_timeout_ms as i64);
+        let timeout = std::cmp::min(timeout_ms, max_timeout_ms);
 
         // If there is nothing a node can help with, and the request supports long polling,
         // add it to the subscriptions.
         let local_version = self.local_state.highest_local_li.ledger_info().version();
+        self.check_subscription_version_margin(&peer, &request)?;
         if local_version <= request.known_version && timeout > 0 {
-            let expiration_time = SystemTime::now().checked_add(Duration::from_millis(timeout));
-            if let Some(time) = expiration_time {
-                let request_info = PendingRequestInfo {
-                    expiration_time: time,
-                    known_version: request.known_version,
-                    request_epoch: request.current_epoch,
-                    limit,
-                };
-                self.subscriptions.insert(peer, request_info);
-            }
+            self.add_subscription(peer, &request, limit, timeout, None);
             return Ok(());
         }
 
@@ -688,15 +2502,89 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 target_li,
                 highest_li,
             },
+            request.limit,
             limit,
         )
     }
 
+    /// Rejects the long poll requests whose known version is more than
+    /// `max_subscription_version_margin` ahead of the local LI (if limited).
+    fn check_subscription_version_margin(
+        &self,
+        peer: &PeerNetworkId,
+        request: &GetChunkRequest,
+    ) -> Result<()> {
+        let local_version = self.local_state.highest_local_li.ledger_info().version();
+        let max_margin = self.config.max_subscription_version_margin;
+        if max_margin > 0 && request.known_version > local_version.saturating_add(max_margin) {
+            // a confused or adversarial peer: don't let it occupy a subscription slot
+            counters::REJECTED_SUBSCRIPTION_COUNT
+                .with_label_values(&[&peer.raw_network_id().to_string()])
+                .inc();
+            warn!(LogSchema::event_log(
+                LogEntry::ProcessChunkRequest,
+                LogEvent::SubscriptionTooFarAhead
+            )
+            .peer(peer)
+            .local_li_version(local_version));
+            bail!(
+                "[state sync] Subscription known version {} too far ahead of local version {}",
+                request.known_version,
+                local_version
+            );
+        }
+        Ok(())
+    }
+
+    /// Holds `request` as a long poll subscription of `peer` for `timeout_ms`, to be served
+    /// relative to `target_li` (if any) once the local LI moves past the known version.
+    /// A request identical to the pending subscription of the peer only extends its expiration.
+    fn add_subscription(
+        &mut self,
+        peer: PeerNetworkId,
+        request: &GetChunkRequest,
+        limit: u64,
+        timeout_ms: u64,
+        target_li: Option<LedgerInfoWithSignatures>,
+    ) {
+        let now = SystemTime::now();
+        let expiration_time = now.checked_add(Duration::from_millis(timeout_ms));
+        if let Some(time) = expiration_time {
+            if let Some(request_info) = self.subscriptions.get_mut(&peer).filter(|request_info| {
+                !request_info.revoked
+                    && request_info.known_version == request.known_version
+                    && request_info.request_epoch == request.current_epoch
+                    && request_info.target_li == target_li
+            }) {
+                request_info.expiration_time = time;
+                counters::DUPLICATE_SUBSCRIPTION_COUNT
+                    .with_label_values(&[&peer.raw_network_id().to_string()])
+                    .inc();
+                return;
+            }
+            let request_info = PendingRequestInfo {
+                subscription_time: now,
+                expiration_time: time,
+                known_version: request.known_version,
+                request_epoch: request.current_epoch,
+                limit,
+                target_li,
+                revoked: false,
+            };
+            self.subscriptions.insert(peer, request_info);
+            self.shed_subscriptions();
+        }
+    }
+
+    /// Processing requests for chunks verifiable by a waypoint.
+    /// If `max_epoch_proofs` is set, the requester also asks for the epoch-ending LIs following
+    /// its current epoch.
     fn process_request_waypoint(
         &mut self,
         peer: PeerNetworkId,
         request: GetChunkRequest,
         waypoint_version: Version,
+        max_epoch_proofs: Option<u64>,
     ) -> Result<()> {
         let mut limit = std::cmp::min(request.limit, self.config.max_chunk_limit);
         ensure!(
@@ -713,27 +2601,89 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         );
 
         // Retrieve the waypoint LI.
-        let waypoint_li = self
-            .executor_proxy
-            .get_epoch_ending_ledger_info(waypoint_version)?;
+        let waypoint_li = self.serve_epoch_ending_ledger_info(waypoint_version)?;
 
         // Txns are up to the end of request epoch with the proofs relative to the waypoint LI.
         let end_of_epoch_li = if waypoint_li.ledger_info().epoch() > request.current_epoch {
-            Some(self.executor_proxy.get_epoch_proof(request.current_epoch)?)
+            Some(self.serve_epoch_proof(request.current_epoch)?)
         } else {
             None
         };
         if let Some(li) = end_of_epoch_li.as_ref() {
+            ensure!(
+                request.known_version < li.ledger_info().version(),
+                "Waypoint request version {} is not smaller than the end of its epoch {}",
+                request.known_version,
+                li.ledger_info().version()
+            );
             let num_txns_until_end_of_epoch = li.ledger_info().version() - request.known_version;
             limit = std::cmp::min(limit, num_txns_until_end_of_epoch);
         }
-        self.deliver_chunk(
-            peer,
-            request.known_version,
-            ResponseLedgerInfo::LedgerInfoForWaypoint {
+        let response_li = match max_epoch_proofs {
+            None => ResponseLedgerInfo::LedgerInfoForWaypoint {
                 waypoint_li,
                 end_of_epoch_li,
             },
+            Some(max_epoch_proofs) => {
+                // The epochs following the request epoch, up to (excluding) the waypoint epoch
+                // whose epoch-ending LI is the waypoint LI.
+                let first_epoch = request.current_epoch.saturating_add(1);
+                let end_epoch = std::cmp::min(
+                    waypoint_li.ledger_info().epoch(),
+                    first_epoch.saturating_add(std::cmp::min(
+                        max_epoch_proofs,
+                        MAX_EPOCH_PROOFS_PER_RESPONSE,
+                    )),
+                );
+                let epoch_proofs = (first_epoch..end_epoch)
+                    .map(|epoch| self.serve_epoch_proof(epoch))
+                    .collect::<Result<Vec<_>>>()?;
+                ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                    waypoint_li,
+                    end_of_epoch_li,
+                    epoch_proofs,
+                }
+            }
+        };
+        self.deliver_chunk(
+            peer,
+            request.known_version,
+            response_li,
+            request.limit,
+            limit,
+        )
+    }
+
+    /// Processing requests for the transactions of a completed epoch.
+    /// The response carries the txns up to the end of the requested epoch with the proofs relative
+    /// to its epoch-ending LI.
+    fn process_request_epoch_ending(
+        &mut self,
+        peer: PeerNetworkId,
+        request: GetChunkRequest,
+        epoch: u64,
+    ) -> Result<()> {
+        let limit = std::cmp::min(request.limit, self.config.max_chunk_limit);
+        ensure!(
+            epoch < self.local_state.epoch(),
+            "Requested epoch {} has not ended locally (local epoch {}).",
+            epoch,
+            self.local_state.epoch()
+        );
+
+        let end_of_epoch_li = self.serve_epoch_proof(epoch)?;
+        ensure!(
+            request.known_version < end_of_epoch_li.ledger_info().version(),
+            "Epoch ending request version {} is not smaller than the end of epoch {} at {}",
+            request.known_version,
+            epoch,
+            end_of_epoch_li.ledger_info().version()
+        );
+        self.deliver_chunk(
+            peer,
+            request.known_version,
+            ResponseLedgerInfo::VerifiableLedgerInfo(end_of_epoch_li),
+            request.limit,
             limit,
         )
     }
@@ -742,17 +2692,118 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
     /// The chunk response contains transactions from the local storage with the proofs relative to
     /// the given target ledger info.
     /// In case target is None, the ledger info is set to the local highest ledger info.
+    /// If `max_concurrent_chunk_serves` is set, the transactions are read from storage on the
+    /// blocking thread pool and the response is sent once the read completes.
+    /// The transactions are read from the read replica once it has caught up with the response LI.
+    /// `requested_limit` is the number of transactions the requester asked for, of which at most
+    /// `limit` are served.
     fn deliver_chunk(
         &mut self,
         peer: PeerNetworkId,
         known_version: u64,
         response_li: ResponseLedgerInfo,
+        requested_limit: u64,
         limit: u64,
     ) -> Result<()> {
-        let txns = self
-            .executor_proxy
-            .get_chunk(known_version, limit, response_li.version())?;
-        let chunk_response = GetChunkResponse::new(response_li, txns);
+        let max_serves = self.config.max_concurrent_chunk_serves();
+        if max_serves > 0 && self.pending_chunk_serves.len() >= max_serves {
+            // shed the load instead of queueing up more storage reads: the requester retries
+            counters::CHUNK_SERVES_REJECTED_COUNT.inc();
+            return self.deliver_unavailable(peer);
+        }
+        // a target this node hasn't committed yet is served up to the highest local LI, and the
+        // requesters supporting it are told they can ask for the rest later
+        let local_li = &self.local_state.highest_local_li;
+        let response_li = match response_li {
+            ResponseLedgerInfo::VerifiableLedgerInfo(li)
+                if li.ledger_info().version() > local_li.ledger_info().version() =>
+            {
+                if self
+                    .request_manager
+                    .peer_capabilities(&peer)
+                    .supports(SyncCapabilities::PARTIAL_RESPONSES)
+                {
+                    ResponseLedgerInfo::PartialLedgerInfo(local_li.clone())
+                } else {
+                    ResponseLedgerInfo::VerifiableLedgerInfo(local_li.clone())
+                }
+            }
+            response_li => response_li,
+        };
+        let proxy = match self.serving_proxy(response_li.version()) {
+            Some(proxy) => proxy,
+            // the read replica lags behind: the requester retries
+            None => return self.deliver_unavailable(peer),
+        };
+        let max_bytes = self.config.max_chunk_response_bytes;
+        if max_serves == 0 {
+            let txns = get_chunk_within_bytes(
+                |limit| proxy.get_chunk(known_version, limit, response_li.version()),
+                limit,
+                max_bytes,
+            )?;
+            Self::observe_served_chunk_size(requested_limit, &txns);
+            return self.send_chunk_response(peer, GetChunkResponse::new(response_li, txns));
+        }
+        let chunk_reader = proxy.chunk_reader();
+        self.pending_chunk_serves.push(spawn_blocking(move || {
+            let txns = get_chunk_within_bytes(
+                |limit| chunk_reader.get_chunk(known_version, limit, response_li.version()),
+                limit,
+                max_bytes,
+            );
+            ServedChunk {
+                peer,
+                response_li,
+                requested_limit,
+                txns,
+            }
+        }));
+        counters::CHUNK_SERVES_IN_PROGRESS.set(self.pending_chunk_serves.len() as i64);
+        Ok(())
+    }
+
+    fn observe_served_chunk_size(requested_limit: u64, txns: &TransactionListWithProof) {
+        counters::SERVED_CHUNK_SIZE
+            .with_label_values(&[counters::REQUESTED_LABEL])
+            .observe(requested_limit as f64);
+        counters::SERVED_CHUNK_SIZE
+            .with_label_values(&[counters::SERVED_LABEL])
+            .observe(txns.len() as f64);
+    }
+
+    /// Sends the chunk read from storage by a completed serve to its requester.
+    fn process_served_chunk(&mut self, served_chunk: Result<ServedChunk, JoinError>) {
+        counters::CHUNK_SERVES_IN_PROGRESS.set(self.pending_chunk_serves.len() as i64);
+        let result = served_chunk
+            .map_err(|e| format_err!("chunk serve task failed: {}", e))
+            .and_then(|served_chunk| {
+                let txns = served_chunk.txns?;
+                Self::observe_served_chunk_size(served_chunk.requested_limit, &txns);
+                self.send_chunk_response(
+                    served_chunk.peer,
+                    GetChunkResponse::new(served_chunk.response_li, txns),
+                )
+            });
+        if let Err(e) = result {
+            error!(
+                LogSchema::event_log(LogEntry::ProcessChunkRequest, LogEvent::DeliverChunk)
+                    .error(&e)
+            );
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn wait_for_chunk_serve(&mut self) {
+        let served_chunk = self.pending_chunk_serves.select_next_some().await;
+        self.process_served_chunk(served_chunk);
+    }
+
+    fn send_chunk_response(
+        &mut self,
+        peer: PeerNetworkId,
+        chunk_response: GetChunkResponse,
+    ) -> Result<()> {
         let log = LogSchema::event_log(LogEntry::ProcessChunkRequest, LogEvent::DeliverChunk)
             .chunk_resp(&chunk_response)
             .peer(&peer);
@@ -783,19 +2834,66 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         })
     }
 
+    /// Sends a response without transactions to the given peer, signalling that this node is not
+    /// able to serve its request right now (e.g., the local storage is temporarily unavailable).
+    /// The peers that don't support the explicit signal are sent a plain empty chunk.
+    fn deliver_unavailable(&mut self, peer: PeerNetworkId) -> Result<()> {
+        let highest_li = self.local_state.highest_local_li.clone();
+        let response_li = if self
+            .request_manager
+            .peer_capabilities(&peer)
+            .supports(SyncCapabilities::UNAVAILABLE_RESPONSES)
+        {
+            ResponseLedgerInfo::UnavailableLedgerInfo(highest_li)
+        } else {
+            ResponseLedgerInfo::VerifiableLedgerInfo(highest_li)
+        };
+        self.deliver_empty(peer, response_li)
+    }
+
+    /// Answers with the highest local LI and no transactions.
+    fn deliver_tip(&mut self, peer: PeerNetworkId) -> Result<()> {
+        let response_li =
+            ResponseLedgerInfo::TipLedgerInfo(self.local_state.highest_local_li.clone());
+        self.deliver_empty(peer, response_li)
+    }
+
+    /// Sends a response carrying `response_li` and no transactions: the response is cheap to
+    /// build whatever the size of the requested chunk.
+    fn deliver_empty(
+        &mut self,
+        peer: PeerNetworkId,
+        response_li: ResponseLedgerInfo,
+    ) -> Result<()> {
+        let chunk_response =
+            GetChunkResponse::new(response_li, TransactionListWithProof::new_empty());
+        let msg = StateSynchronizerMsg::GetChunkResponse(Box::new(chunk_response));
+        self.network_senders
+            .get_mut(&peer.network_id())
+            .expect("missing network sender")
+            .send_to(peer.peer_id(), msg)
+            .map_err(|e| {
+                format_err!(
+                    "Network error in sending empty chunk response to {}: {}",
+                    peer,
+                    e
+                )
+            })
+    }
+
     /// The choice of the LedgerInfo in the response follows the following logic:
     /// * response LI is either the requested target or the highest local LI if target is None.
     /// * if the response LI would not belong to `request_epoch`, change
     /// the response LI to the LI that is terminating `request_epoch`.
     fn choose_response_li(
-        &self,
+        &mut self,
         request_epoch: u64,
         target: Option<LedgerInfoWithSignatures>,
     ) -> Result<LedgerInfoWithSignatures> {
         let mut target_li = target.unwrap_or_else(|| self.local_state.highest_local_li.clone());
         let target_epoch = target_li.ledger_info().epoch();
         if target_epoch > request_epoch {
-            let end_of_epoch_li = self.executor_proxy.get_epoch_proof(request_epoch)?;
+            let end_of_epoch_li = self.serve_epoch_proof(request_epoch)?;
             debug!(LogSchema::event_log(
                 LogEntry::ProcessChunkRequest,
                 LogEvent::PastEpochRequested
@@ -814,8 +2912,9 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
     /// - verify LIs in chunk response against local state
     /// - execute and commit chunk
     /// Returns error if above chunk response checks fail or chunk was not able to be stored to storage, else
-    /// return Ok(()) if above checks all pass and chunk was stored to storage
-    fn apply_chunk(&mut self, peer: &PeerNetworkId, response: GetChunkResponse) -> Result<()> {
+    /// return whether the chunk was requested from `peer` if above checks all pass and chunk was
+    /// stored to storage
+    fn apply_chunk(&mut self, peer: &PeerNetworkId, response: GetChunkResponse) -> Result<bool> {
         debug!(
             LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::Received)
                 .chunk_resp(&response)
@@ -833,27 +2932,79 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
 
         let txn_list_with_proof = response.txn_list_with_proof.clone();
         let known_version = self.local_state.highest_version_in_local_storage();
+        let network_receive = self
+            .request_manager
+            .get_last_request_time(known_version)
+            .and_then(|request_tst| SystemTime::now().duration_since(request_tst).ok());
+        self.request_manager
+            .record_response_time(peer, known_version);
+        let requested = self.request_manager.is_requested_peer(known_version, peer);
+        if self.config.reject_network_mismatch_responses
+            && self
+                .request_manager
+                .is_network_mismatch(known_version, peer)
+        {
+            counters::RESPONSE_NETWORK_MISMATCH_COUNT
+                .with_label_values(&[
+                    &peer.raw_network_id().to_string(),
+                    &peer.peer_id().to_string(),
+                ])
+                .inc();
+            bail!(
+                "received chunk response from {:?} on a network the request was not sent on",
+                peer
+            );
+        }
         let chunk_start_version =
             txn_list_with_proof
                 .first_transaction_version
                 .ok_or_else(|| {
-                    self.request_manager
-                        .update_score(&peer, PeerScoreUpdateType::EmptyChunk);
+                    // a partial response is not a failure to serve: the peer has nothing newer
+                    if !matches!(
+                        response.response_li,
+                        ResponseLedgerInfo::PartialLedgerInfo(_)
+                    ) {
+                        self.request_manager
+                            .update_score(&peer, PeerScoreUpdateType::EmptyChunk);
+                    }
                     format_err!("[state sync] Empty chunk from {:?}", peer)
                 })?;
 
         if chunk_start_version != known_version + 1 {
-            // Old / wrong chunk.
+            // Old / wrong chunk: only a chunk behind the synced version is stale, one ahead of it
+            // leaves a gap
+            let update_type = if chunk_start_version < known_version + 1 {
+                PeerScoreUpdateType::StaleChunk
+            } else {
+                PeerScoreUpdateType::ChunkVersionCannotBeApplied
+            };
             self.request_manager.process_chunk_version_mismatch(
                 peer,
                 chunk_start_version,
                 known_version,
+                update_type,
             )?;
         }
 
+        if self.config.reject_forked_chunks {
+            self.check_chunk_extends_local_accumulator(peer, &txn_list_with_proof)?;
+        }
+        self.check_upstream_freshness(peer, &response.response_li);
+
         let chunk_size = txn_list_with_proof.len() as u64;
+        // only measured when it's going to be reported
+        let chunk_bytes = if self.completion_sink.is_some() && self.sync_request_progress.is_some()
+        {
+            lcs::to_bytes(&txn_list_with_proof).map_or(0, |bytes| bytes.len() as u64)
+        } else {
+            0
+        };
+        let process_start = Instant::now();
+        self.chunk_store_duration = Duration::from_secs(0);
+        let duration_label = sync_duration_label(&response.response_li);
         match response.response_li {
-            ResponseLedgerInfo::VerifiableLedgerInfo(li) => {
+            ResponseLedgerInfo::VerifiableLedgerInfo(li)
+            | ResponseLedgerInfo::PartialLedgerInfo(li) => {
                 self.process_response_with_verifiable_li(txn_list_with_proof, li, None)
             }
             ResponseLedgerInfo::ProgressiveLedgerInfo {
@@ -880,13 +3031,69 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 txn_list_with_proof,
                 waypoint_li,
                 end_of_epoch_li,
+                vec![],
             ),
+            ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                waypoint_li,
+                end_of_epoch_li,
+                epoch_proofs,
+            } => self.process_response_with_waypoint_li(
+                txn_list_with_proof,
+                waypoint_li,
+                end_of_epoch_li,
+                epoch_proofs,
+            ),
+            ResponseLedgerInfo::UnavailableLedgerInfo(_) => Err(format_err!(
+                "[state sync] Unavailable response carrying transactions from {:?}",
+                peer
+            )),
+            ResponseLedgerInfo::TipLedgerInfo(_) => Err(format_err!(
+                "[state sync] Tip response carrying transactions from {:?}",
+                peer
+            )),
         }
         .map_err(|e| {
-            self.request_manager
-                .update_score(peer, PeerScoreUpdateType::InvalidChunk);
+            let update_type = if e.downcast_ref::<StaleEpochResponse>().is_some() {
+                counters::STALE_EPOCH_RESPONSE_COUNT
+                    .with_label_values(&[
+                        &peer.raw_network_id().to_string(),
+                        &peer.peer_id().to_string(),
+                    ])
+                    .inc();
+                PeerScoreUpdateType::StaleEpoch
+            } else {
+                if let Some(mismatch) = e.downcast_ref::<WaypointMismatch>() {
+                    self.process_waypoint_mismatch(peer, mismatch.version);
+                }
+                PeerScoreUpdateType::InvalidChunk
+            };
+            self.request_manager.update_score(peer, update_type);
             format_err!("[state sync] failed to apply chunk: {}", e)
         })?;
+        if !requested {
+            // the chunk is valid, yet the peer was never asked for it
+            counters::UNREQUESTED_RESPONSE_COUNT
+                .with_label_values(&[
+                    &peer.raw_network_id().to_string(),
+                    &peer.peer_id().to_string(),
+                ])
+                .inc();
+            self.request_manager
+                .update_score(peer, PeerScoreUpdateType::UnrequestedResponse);
+        }
+        if self.config.record_chunk_timings {
+            let store = self.chunk_store_duration;
+            self.record_chunk_timing(ChunkTiming {
+                first_version: chunk_start_version,
+                num_txns: chunk_size,
+                network_receive,
+                verify: process_start
+                    .elapsed()
+                    .checked_sub(store)
+                    .unwrap_or_else(|| Duration::from_secs(0)),
+                store,
+            });
+        }
 
         counters::STATE_SYNC_CHUNK_SIZE
             .with_label_values(&[
@@ -894,6 +3101,10 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                 &peer.peer_id().to_string(),
             ])
             .observe(chunk_size as f64);
+        if let Some(progress) = self.sync_request_progress.as_mut() {
+            progress.chunks_applied += 1;
+            progress.bytes_transferred += chunk_bytes;
+        }
         debug!(
             LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::ApplyChunkSuccess),
             "Applied chunk of size {}. Previous version: {}, new version {}",
@@ -908,34 +3119,212 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         {
             if let Ok(duration) = SystemTime::now().duration_since(first_attempt_tst) {
                 counters::SYNC_PROGRESS_DURATION.observe_duration(duration);
+                counters::SYNC_PROGRESS_DURATION_BY_TYPE
+                    .with_label_values(&[duration_label])
+                    .observe(duration.as_secs_f64());
             }
         }
-        Ok(())
+        Ok(requested)
+    }
+
+    /// Returns true if mempool ACKed the commit notification within the timeout.
+    async fn wait_for_mempool_ack(
+        callback_rcv: oneshot::Receiver<Result<CommitResponse>>,
+        ack_timeout: Duration,
+    ) -> bool {
+        if let Err(e) = timeout(ack_timeout, callback_rcv).await {
+            error!(
+                LogSchema::new(LogEntry::CommitFlow).error(&e.into()),
+                "did not receive ACK for commit notification sent to mempool"
+            );
+            counters::COMMIT_FLOW_FAIL
+                .with_label_values(&[counters::FROM_MEMPOOL_LABEL])
+                .inc();
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Waits for mempool to ACK pending chunk commits until the commit pipeline is below
+    /// `max_commit_pipeline_depth` (with a cap of 0 no ACK is ever left pending).
+    async fn drain_commit_pipeline(&mut self) {
+        if !self.pending_mempool_acks.is_empty()
+            && self.pending_mempool_acks.len() >= self.config.max_commit_pipeline_depth()
+        {
+            counters::COMMIT_PIPELINE_FULL_COUNT.inc();
+            debug!(
+                LogSchema::new(LogEntry::CommitFlow),
+                "commit pipeline full with {} pending mempool ACKs",
+                self.pending_mempool_acks.len()
+            );
+        }
+        while self.pending_mempool_acks.len() >= self.config.max_commit_pipeline_depth() {
+            if self.pending_mempool_acks.next().await.is_none() {
+                break;
+            }
+        }
+        counters::COMMIT_PIPELINE_DEPTH.set(self.pending_mempool_acks.len() as i64);
+    }
+
+    /// Notifies mempool of the batched chunk commits and awaits all the pending ACKs, so that
+    /// mempool learns of every commit before the coordinator stops.
+    async fn flush_commit_notifications(&mut self) {
+        self.flush_mempool_commit_batch().await;
+        while self.pending_mempool_acks.next().await.is_some() {}
+        counters::COMMIT_PIPELINE_DEPTH.set(0);
+    }
+
+    #[cfg(test)]
+    pub fn commit_pipeline_depth(&self) -> usize {
+        self.pending_mempool_acks.len()
+    }
+
+    #[cfg(test)]
+    pub fn reorder_buffer_len(&self) -> usize {
+        self.reorder_buffer.len()
+    }
+
+    /// Buffers the chunks from upstream peers that start ahead of the next local version (if
+    /// `max_reorder_buffer_chunks` allows it), otherwise applies the chunk along with the buffered
+    /// chunks that become contiguous with the local storage.
+    async fn process_chunk_response(&mut self, peer: &PeerNetworkId, response: GetChunkResponse) {
+        match &response.response_li {
+            ResponseLedgerInfo::UnavailableLedgerInfo(_) => {
+                self.process_unavailable_response(peer);
+                return;
+            }
+            // the answer to a tip query: the peer is neither penalized nor asked again
+            ResponseLedgerInfo::TipLedgerInfo(li) => {
+                debug!(
+                    LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::Tip)
+                        .peer(peer)
+                        .version(li.ledger_info().version())
+                );
+                return;
+            }
+            _ => {}
+        }
+        if self.is_future_chunk(peer, &response) {
+            self.buffer_chunk(peer.clone(), response);
+            return;
+        }
+        let mut next_chunk = Some((peer.clone(), response));
+        while let Some((peer, response)) = next_chunk {
+            next_chunk = if self.process_contiguous_chunk(&peer, response).await {
+                self.take_buffered_chunk()
+            } else {
+                None
+            };
+        }
+    }
+
+    /// An upstream peer explicitly signalled that it can't serve the request right now: it's not
+    /// penalized, and the request it was sent is retried at the next progress check instead of
+    /// waiting for it to time out.
+    fn process_unavailable_response(&mut self, peer: &PeerNetworkId) {
+        let known_version = self.local_state.highest_version_in_local_storage();
+        debug!(
+            LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::PeerUnavailable)
+                .peer(peer)
+                .version(known_version)
+        );
+        if self.request_manager.is_known_upstream_peer(peer)
+            && self.request_manager.is_requested_peer(known_version, peer)
+        {
+            self.request_manager
+                .process_unavailable_response(peer, known_version);
+        }
+    }
+
+    fn is_future_chunk(&self, peer: &PeerNetworkId, response: &GetChunkResponse) -> bool {
+        let known_version = self.local_state.highest_version_in_local_storage();
+        self.config.max_reorder_buffer_chunks > 0
+            && self.request_manager.is_known_upstream_peer(peer)
+            && response
+                .txn_list_with_proof
+                .first_transaction_version
+                .map_or(false, |version| version > known_version + 1)
+    }
+
+    /// Adds the chunk to the reorder buffer: if the buffer is full, the oldest buffered chunk is
+    /// dropped and its sender penalized, as the chunk could not be stitched to the local storage.
+    fn buffer_chunk(&mut self, peer: PeerNetworkId, response: GetChunkResponse) {
+        if self.reorder_buffer.len() >= self.config.max_reorder_buffer_chunks {
+            if let Some((dropped_peer, dropped)) = self.reorder_buffer.pop_front() {
+                self.request_manager.update_score(
+                    &dropped_peer,
+                    PeerScoreUpdateType::ChunkVersionCannotBeApplied,
+                );
+                counters::REORDER_BUFFER_OVERFLOW_COUNT.inc();
+                warn!(
+                    LogSchema::new(LogEntry::ProcessChunkResponse).peer(&dropped_peer),
+                    "reorder buffer reached max capacity {}, dropped chunk starting at version {:?}",
+                    self.config.max_reorder_buffer_chunks,
+                    dropped.txn_list_with_proof.first_transaction_version
+                );
+            }
+        }
+        self.reorder_buffer.push_back((peer, response));
+        counters::REORDER_BUFFER_SIZE.set(self.reorder_buffer.len() as i64);
+    }
+
+    /// Removes the buffered chunks that are not ahead of the local storage anymore and returns the
+    /// one that directly follows it (if any).
+    fn take_buffered_chunk(&mut self) -> Option<(PeerNetworkId, GetChunkResponse)> {
+        let known_version = self.local_state.highest_version_in_local_storage();
+        let mut next_chunk = None;
+        let buffer = std::mem::replace(&mut self.reorder_buffer, VecDeque::new());
+        for (peer, response) in buffer {
+            match response.txn_list_with_proof.first_transaction_version {
+                Some(version) if version > known_version + 1 => {
+                    self.reorder_buffer.push_back((peer, response))
+                }
+                Some(version) if version == known_version + 1 && next_chunk.is_none() => {
+                    next_chunk = Some((peer, response))
+                }
+                _ => {}
+            }
+        }
+        counters::REORDER_BUFFER_SIZE.set(self.reorder_buffer.len() as i64);
+        next_chunk
     }
 
     /// * Verifies and stores chunk in response
     /// * Triggers post-commit actions based on new local state after successful chunk processing in above step
-    async fn process_chunk_response(&mut self, peer: &PeerNetworkId, response: GetChunkResponse) {
+    ///
+    /// Returns whether the chunk was applied.
+    async fn process_contiguous_chunk(
+        &mut self,
+        peer: &PeerNetworkId,
+        response: GetChunkResponse,
+    ) -> bool {
+        // Part 0: hold back applying new chunks for as long as mempool lags behind
+        self.drain_commit_pipeline().await;
         let new_txns = response.txn_list_with_proof.transactions.clone();
         // Part 1: check response, validate and store chunk
         // any errors thrown here should be for detecting actual bad chunks
-        if let Err(e) = self.apply_chunk(peer, response) {
-            // count, log, and exit
-            error!(
-                LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::ApplyChunkFail)
-                    .peer(peer)
-                    .error(&e)
-            );
+        let requested = match self.apply_chunk(peer, response) {
+            Ok(requested) => requested,
+            Err(e) => {
+                // count, log, and exit
+                error!(LogSchema::event_log(
+                    LogEntry::ProcessChunkResponse,
+                    LogEvent::ApplyChunkFail
+                )
+                .peer(peer)
+                .error(&e));
 
-            counters::APPLY_CHUNK_COUNT
-                .with_label_values(&[
-                    &peer.raw_network_id().to_string(),
-                    &peer.peer_id().to_string(),
-                    counters::FAIL_LABEL,
-                ])
-                .inc();
-            return;
-        }
+                counters::APPLY_CHUNK_COUNT
+                    .with_label_values(&[
+                        &peer.raw_network_id().to_string(),
+                        &peer.peer_id().to_string(),
+                        counters::FAIL_LABEL,
+                    ])
+                    .inc();
+                return false;
+            }
+        };
 
         counters::APPLY_CHUNK_COUNT
             .with_label_values(&[
@@ -946,12 +3335,84 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             .inc();
 
         // Part 2: post-chunk-process stage: process commit
-        if let Err(e) = self.process_commit(new_txns, None, Some(peer)).await {
+        // an unsolicited chunk doesn't earn its sender the success credit
+        let chunk_sender = if requested { Some(peer) } else { None };
+        if let Err(e) = self.process_commit(new_txns, None, chunk_sender).await {
             error!(
                 LogSchema::event_log(LogEntry::ProcessChunkResponse, LogEvent::PostCommitFail)
                     .error(&e)
             );
         }
+        true
+    }
+
+    /// While syncing to a target, the responses are expected to progress one epoch at a time:
+    /// the response LI belongs to the epoch of the highest local LI, or to the next one if the
+    /// highest local LI ends its epoch. Larger jumps indicate a buggy or adversarial upstream.
+    fn validate_sync_response_epoch(&self, response_li: &LedgerInfoWithSignatures) -> Result<()> {
+        let local_li = self.local_state.highest_local_li.ledger_info();
+        let expected_epoch = if local_li.ends_epoch() {
+            local_li.epoch() + 1
+        } else {
+            local_li.epoch()
+        };
+        let response_epoch = response_li.ledger_info().epoch();
+        if response_epoch > expected_epoch {
+            counters::UNEXPECTED_EPOCH_JUMP_COUNT.inc();
+            bail!(
+                "[state sync] Response LI epoch {} jumps beyond expected epoch {}",
+                response_epoch,
+                expected_epoch
+            );
+        }
+        Ok(())
+    }
+
+    /// The timestamp of the freshest LI in a response is a proof of how up to date its sender is:
+    /// a response older than `max_upstream_staleness_ms` means that the upstream is lagging
+    /// behind the network, so its score is lowered for other upstream peers to be preferred.
+    /// The LIs are only trusted once verified, but a forged LI fails verification anyway.
+    fn check_upstream_freshness(&mut self, peer: &PeerNetworkId, response_li: &ResponseLedgerInfo) {
+        if self.config.max_upstream_staleness_ms == 0 || !self.is_initialized() {
+            return;
+        }
+        let freshest_li = match response_li {
+            ResponseLedgerInfo::VerifiableLedgerInfo(li)
+            | ResponseLedgerInfo::UnavailableLedgerInfo(li)
+            | ResponseLedgerInfo::TipLedgerInfo(li)
+            | ResponseLedgerInfo::PartialLedgerInfo(li) => li,
+            ResponseLedgerInfo::ProgressiveLedgerInfo {
+                target_li,
+                highest_li,
+            } => highest_li.as_ref().unwrap_or(target_li),
+            // waypoint LIs are not expected to be fresh
+            ResponseLedgerInfo::LedgerInfoForWaypoint { .. }
+            | ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs { .. } => return,
+        };
+        let li_timestamp =
+            UNIX_EPOCH + Duration::from_micros(freshest_li.ledger_info().timestamp_usecs());
+        let is_stale = SystemTime::now()
+            .duration_since(li_timestamp)
+            .map_or(false, |age| {
+                age > Duration::from_millis(self.config.max_upstream_staleness_ms)
+            });
+        if is_stale {
+            counters::STALE_UPSTREAM_RESPONSE_COUNT
+                .with_label_values(&[
+                    &peer.raw_network_id().to_string(),
+                    &peer.peer_id().to_string(),
+                ])
+                .inc();
+            debug!(
+                LogSchema::new(LogEntry::ProcessChunkResponse)
+                    .peer(peer)
+                    .local_li_version(freshest_li.ledger_info().version()),
+                "stale upstream: freshest LI timestamp {} usecs",
+                freshest_li.ledger_info().timestamp_usecs()
+            );
+            self.request_manager
+                .update_score(peer, PeerScoreUpdateType::StaleUpstream);
+        }
     }
 
     /// Processing chunk responses that carry a LedgerInfo that should be verified using the
@@ -964,20 +3425,9 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         // may be the same as response_li
         pending_li: Option<LedgerInfoWithSignatures>,
     ) -> Result<()> {
-        ensure!(
-            self.is_initialized(),
-            "Response with a non-waypoint LI while still not initialized"
-        );
-        if let Some(sync_req) = self.sync_request.as_ref() {
-            // Valid responses should not exceed the LI version of the request.
-            if sync_req.target.ledger_info().version() < response_li.ledger_info().version() {
-                bail!(
-                    "[state sync] Response has an LI version {} higher than requested version {}.",
-                    response_li.ledger_info().version(),
-                    sync_req.target.ledger_info().version(),
-                );
-            }
-        }
+        let verify_start = Instant::now();
+        self.verify_response_li(&response_li, pending_li.as_ref())?;
+        let verify_duration = verify_start.elapsed();
         // Optimistically fetch the next chunk assuming the current chunk is going to be applied
         // successfully.
         let new_version =
@@ -992,19 +3442,28 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             // Remain in the current epoch
             self.local_state.epoch()
         };
-        self.local_state.trusted_epoch.verify(&response_li)?;
         if let Some(li) = pending_li {
-            if li != response_li {
-                self.local_state.trusted_epoch.verify(&li)?;
-            }
             self.pending_ledger_infos.add_li(li);
         }
         self.validate_and_store_chunk(txn_list_with_proof, response_li, None)?;
+        if new_epoch != self.local_state.epoch() {
+            counters::EPOCH_TRANSITION_VERIFY_DURATION.observe_duration(verify_duration);
+        }
 
         // need to sync with local storage to see whether response LI was actually committed
         // and update pending_ledger_infos accordingly
         self.sync_state_with_local_storage()?;
         let new_version = self.local_state.highest_version_in_local_storage();
+        if new_epoch != self.local_state.epoch() {
+            // the next request is sent for an epoch the node did not end up in
+            counters::OPTIMISTIC_EPOCH_MISPREDICTION_COUNT.inc();
+            debug!(
+                LogSchema::new(LogEntry::ProcessChunkResponse)
+                    .new_epoch(new_epoch)
+                    .local_epoch(self.local_state.epoch()),
+                "optimistic epoch of the next chunk request mispredicted"
+            );
+        }
 
         // don't throw error for failed chunk request send, as this failure is not related to
         // validity of the chunk response itself
@@ -1019,17 +3478,126 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         Ok(())
     }
 
-    /// Processing chunk responses that carry a LedgerInfo corresponding to the waypoint.
+    /// Verifies the (non-waypoint) LI of a chunk response, along with the LI to add to the
+    /// pending LIs (if any), against the local epoch state and the pending sync request.
+    fn verify_response_li(
+        &self,
+        response_li: &LedgerInfoWithSignatures,
+        pending_li: Option<&LedgerInfoWithSignatures>,
+    ) -> Result<()> {
+        ensure!(
+            self.is_initialized(),
+            "Response with a non-waypoint LI while still not initialized"
+        );
+        let response_epoch = response_li.ledger_info().epoch();
+        if self.config.reject_stale_epoch_responses && response_epoch < self.local_state.epoch() {
+            // the LI can't be verified against the local epoch anyway: skip the verification
+            return Err(StaleEpochResponse {
+                response_epoch,
+                local_epoch: self.local_state.epoch(),
+            }
+            .into());
+        }
+        if let Some(sync_req) = self.sync_request.as_ref() {
+            // Valid responses should not exceed the LI version of the request.
+            if sync_req.target.ledger_info().version() < response_li.ledger_info().version() {
+                bail!(
+                    "[state sync] Response has an LI version {} higher than requested version {}.",
+                    response_li.ledger_info().version(),
+                    sync_req.target.ledger_info().version(),
+                );
+            }
+            if self.config.validate_sync_request_epochs {
+                self.validate_sync_response_epoch(response_li)?;
+            }
+        }
+        self.local_state.trusted_epoch.verify(response_li)?;
+        if let Some(li) = pending_li {
+            if li != response_li {
+                self.local_state.trusted_epoch.verify(li)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the chunk response against the local epoch state or the waypoint, along with the
+    /// proof of its transactions, without executing the chunk or changing any local state. As when
+    /// applying it, the chunk must directly follow the synced version.
+    pub(crate) fn verify_chunk(&self, response: &GetChunkResponse) -> Result<()> {
+        let txn_list_with_proof = &response.txn_list_with_proof;
+        let target_li = match &response.response_li {
+            ResponseLedgerInfo::VerifiableLedgerInfo(li)
+            | ResponseLedgerInfo::UnavailableLedgerInfo(li)
+            | ResponseLedgerInfo::TipLedgerInfo(li)
+            | ResponseLedgerInfo::PartialLedgerInfo(li) => {
+                self.verify_response_li(li, None)?;
+                li
+            }
+            ResponseLedgerInfo::ProgressiveLedgerInfo {
+                target_li,
+                highest_li,
+            } => {
+                self.verify_response_li(target_li, highest_li.as_ref())?;
+                target_li
+            }
+            ResponseLedgerInfo::LedgerInfoForWaypoint {
+                waypoint_li,
+                end_of_epoch_li,
+            } => {
+                self.verify_waypoint_li(waypoint_li, end_of_epoch_li.as_ref(), &[])?;
+                waypoint_li
+            }
+            ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                waypoint_li,
+                end_of_epoch_li,
+                epoch_proofs,
+            } => {
+                self.verify_waypoint_li(waypoint_li, end_of_epoch_li.as_ref(), epoch_proofs)?;
+                waypoint_li
+            }
+        };
+        Self::check_chunk_within_target(txn_list_with_proof, target_li.ledger_info().version())?;
+        let first_version = self.local_state.highest_version_in_local_storage() + 1;
+        self.executor_proxy
+            .verify_chunk(txn_list_with_proof, target_li, first_version)
+    }
+
+    /// Verifies the waypoint LI of a chunk response along with the prefetched epoch proofs.
+    fn verify_waypoint_li(
+        &self,
+        waypoint_li: &LedgerInfoWithSignatures,
+        end_of_epoch_li: Option<&LedgerInfoWithSignatures>,
+        epoch_proofs: &[LedgerInfoWithSignatures],
+    ) -> Result<()> {
+        ensure!(
+            !self.is_initialized(),
+            "Response with a waypoint LI but we're already initialized"
+        );
+        self.waypoint
+            .verify(waypoint_li.ledger_info())
+            .map_err(|e| WaypointMismatch {
+                version: waypoint_li.ledger_info().version(),
+                reason: e.to_string(),
+            })?;
+        self.verify_epoch_proofs(waypoint_li, end_of_epoch_li, epoch_proofs)
+    }
+
+    /// Processing chunk responses that carry a LedgerInfo corresponding to the waypoint
+    /// (and possibly prefetched epoch proofs).
     fn process_response_with_waypoint_li(
         &mut self,
         txn_list_with_proof: TransactionListWithProof,
         waypoint_li: LedgerInfoWithSignatures,
         end_of_epoch_li: Option<LedgerInfoWithSignatures>,
+        epoch_proofs: Vec<LedgerInfoWithSignatures>,
     ) -> Result<()> {
-        ensure!(
-            !self.is_initialized(),
-            "Response with a waypoint LI but we're already initialized"
-        );
+        let verify_start = Instant::now();
+        self.verify_waypoint_li(&waypoint_li, end_of_epoch_li.as_ref(), &epoch_proofs)?;
+        let verify_duration = verify_start.elapsed();
+        // The epoch proofs are added before the optimistic request is sent, so that the request
+        // doesn't ask for them again.
+        self.add_epoch_proofs(&waypoint_li, epoch_proofs);
+
         // Optimistically fetch the next chunk.
         let new_version =
             self.local_state.highest_version_in_local_storage() + txn_list_with_proof.len() as u64;
@@ -1054,11 +3622,121 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             }
         }
 
-        self.waypoint.verify(waypoint_li.ledger_info())?;
+        // the chunk ends an epoch if it reaches either the end of the current epoch or the
+        // waypoint (which is always at an epoch boundary)
+        let is_epoch_transition = new_epoch != self.local_state.epoch()
+            || waypoint_li.ledger_info().version() == new_version;
         self.validate_and_store_chunk(txn_list_with_proof, waypoint_li, end_of_epoch_li)
+            .map_err(|e| {
+                // the epoch proofs were verified relative to the end of epoch LI of this chunk
+                self.epoch_proofs.clear();
+                e
+            })?;
+        if is_epoch_transition {
+            counters::EPOCH_TRANSITION_VERIFY_DURATION.observe_duration(verify_duration);
+        }
+        Ok(())
+    }
+
+    /// Verifies the prefetched `epoch_proofs`:
+    /// * all the LIs must match the epoch proofs that are already known
+    /// * the first epoch proof must be verifiable by the epoch state of `end_of_epoch_li`,
+    /// every following one by the epoch state of the previous one
+    /// * the last epoch proof must verify the waypoint LI in case it ends the preceding epoch
+    fn verify_epoch_proofs(
+        &self,
+        waypoint_li: &LedgerInfoWithSignatures,
+        end_of_epoch_li: Option<&LedgerInfoWithSignatures>,
+        epoch_proofs: &[LedgerInfoWithSignatures],
+    ) -> Result<()> {
+        for li in end_of_epoch_li.into_iter().chain(epoch_proofs.iter()) {
+            if let Some(known_li) = self.epoch_proofs.get(&li.ledger_info().epoch()) {
+                ensure!(
+                    known_li == li,
+                    "Epoch ending LI {} does not match the prefetched {}",
+                    li,
+                    known_li
+                );
+            }
+        }
+        if !epoch_proofs.is_empty() {
+            let mut prev_li = end_of_epoch_li
+                .ok_or_else(|| format_err!("Epoch proofs without an end of epoch LI"))?;
+            for li in epoch_proofs.iter().chain(std::iter::once(waypoint_li)) {
+                let epoch_state = prev_li
+                    .ledger_info()
+                    .next_epoch_state()
+                    .ok_or_else(|| format_err!("LI {} does not end an epoch", prev_li))?;
+                if li == waypoint_li && li.ledger_info().epoch() != epoch_state.epoch {
+                    // the epoch proofs stop before reaching the waypoint epoch
+                    break;
+                }
+                epoch_state.verify(li)?;
+                prev_li = li;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the (verified) prefetched `epoch_proofs` along with the waypoint LI to the known
+    /// epoch proofs.
+    fn add_epoch_proofs(
+        &mut self,
+        waypoint_li: &LedgerInfoWithSignatures,
+        epoch_proofs: Vec<LedgerInfoWithSignatures>,
+    ) {
+        if !epoch_proofs.is_empty() {
+            counters::EPOCH_PROOFS_PREFETCHED.inc_by(epoch_proofs.len() as i64);
+        }
+        for li in epoch_proofs
+            .into_iter()
+            .chain(std::iter::once(waypoint_li.clone()))
+        {
+            self.epoch_proofs.insert(li.ledger_info().epoch(), li);
+        }
+    }
+
+    /// Returns the number of epoch proofs to prefetch with the next waypoint chunk request,
+    /// if any: the epoch proofs are requested only when they are not known for the epoch
+    /// following `known_epoch` and it precedes the waypoint epoch.
+    fn epoch_proofs_to_prefetch(&self, known_epoch: u64) -> Option<u64> {
+        if self.config.max_epoch_prefetch() == 0 {
+            return None;
+        }
+        let next_epoch = known_epoch + 1;
+        // the waypoint LI is the epoch proof of the highest epoch (if known)
+        let waypoint_epoch = self.epoch_proofs.keys().next_back();
+        if waypoint_epoch.map_or(false, |epoch| next_epoch >= *epoch)
+            || self.epoch_proofs.contains_key(&next_epoch)
+        {
+            None
+        } else {
+            Some(self.config.max_epoch_prefetch())
+        }
     }
 
     // Assumes that the target LI has been already verified by the caller.
+    /// Ensures the chunk doesn't extend beyond the version of its target LI: the proof of an
+    /// over-long chunk would fail to verify anyway, but it's cheaper and clearer to reject it
+    /// before handing it to the executor.
+    fn check_chunk_within_target(
+        txn_list_with_proof: &TransactionListWithProof,
+        target_version: Version,
+    ) -> Result<()> {
+        if let Some(first_version) = txn_list_with_proof.first_transaction_version {
+            let last_version = first_version
+                .saturating_add(txn_list_with_proof.len() as u64)
+                .saturating_sub(1);
+            ensure!(
+                last_version <= target_version,
+                "chunk ends at version {}, beyond the version {} of its response LI",
+                last_version,
+                target_version
+            );
+        }
+        Ok(())
+    }
+
     fn validate_and_store_chunk(
         &mut self,
         txn_list_with_proof: TransactionListWithProof,
@@ -1079,9 +3757,85 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             );
             return Ok(());
         }
+        Self::check_chunk_within_target(&txn_list_with_proof, target_version)?;
 
-        self.executor_proxy
-            .execute_chunk(txn_list_with_proof, target, intermediate_end_of_epoch_li)
+        let store_start = Instant::now();
+        let result = self.executor_proxy.execute_chunk(
+            txn_list_with_proof,
+            target,
+            intermediate_end_of_epoch_li,
+        );
+        self.chunk_store_duration = store_start.elapsed();
+        if result.is_ok() {
+            self.chunk_limit_controller.record_success();
+        } else {
+            self.chunk_limit_controller.record_failure();
+        }
+        result
+    }
+
+    /// Checks that the chunk starts right at the frontier of the local transaction accumulator:
+    /// the left siblings of the first leaf in the chunk's range proof must be exactly the frozen
+    /// subtree roots of the local accumulator. The siblings are ordered from the leaf up, while the
+    /// frozen subtree roots are ordered from the largest (leftmost) subtree down, so the former
+    /// are compared in reverse. A proof that is valid in isolation but fails this check belongs to
+    /// a fork of the local history, so the chunk is rejected before it is executed.
+    fn check_chunk_extends_local_accumulator(
+        &mut self,
+        peer: &PeerNetworkId,
+        txn_list_with_proof: &TransactionListWithProof,
+    ) -> Result<()> {
+        let frozen_subtree_roots = self
+            .local_state
+            .synced_trees
+            .txn_accumulator()
+            .frozen_subtree_roots();
+        if txn_list_with_proof
+            .proof
+            .left_siblings()
+            .iter()
+            .rev()
+            .eq(frozen_subtree_roots.iter())
+        {
+            return Ok(());
+        }
+        counters::FORK_DETECTED_COUNT
+            .with_label_values(&[
+                &peer.raw_network_id().to_string(),
+                &peer.peer_id().to_string(),
+            ])
+            .inc();
+        error!(
+            SecurityEvent::StateSyncForkDetected,
+            remote_peer = peer,
+            first_version = txn_list_with_proof.first_transaction_version,
+            local_num_leaves = self.local_state.synced_trees.txn_accumulator().num_leaves(),
+        );
+        self.request_manager
+            .update_score(peer, PeerScoreUpdateType::InvalidChunk);
+        bail!(
+            "[state sync] chunk from {:?} doesn't extend the local accumulator",
+            peer
+        )
+    }
+
+    fn record_chunk_timing(&mut self, chunk_timing: ChunkTiming) {
+        if self.recent_chunk_timings.len() >= MAX_RECENT_CHUNK_TIMINGS {
+            self.recent_chunk_timings.pop_front();
+        }
+        self.recent_chunk_timings.push_back(chunk_timing);
+    }
+
+    pub(crate) fn recent_chunk_timings(&self) -> Vec<ChunkTiming> {
+        self.recent_chunk_timings.iter().cloned().collect()
+    }
+
+    pub(crate) fn sync_metrics(&self) -> SyncMetrics {
+        SyncMetrics {
+            synced_version: self.local_state.highest_version_in_local_storage(),
+            highest_target_version: self.pending_ledger_infos.highest_version(),
+            versions_per_sec: self.commit_interval_tracker.versions_per_sec(),
+        }
     }
 
     /// Ensures that StateSynchronizer is making progress:
@@ -1115,6 +3869,7 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             ));
 
             if let Some(sync_request) = self.sync_request.take() {
+                self.record_sync_request_completion(SyncRequestOutcome::Timeout);
                 if let Err(e) = Self::send_sync_req_callback(
                     sync_request,
                     Err(format_err!("request timed out")),
@@ -1135,6 +3890,10 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             // log and count timeout
             counters::TIMEOUT.inc();
             warn!(LogSchema::new(LogEntry::Timeout).version(known_version));
+            if self.sync_attempts_exhausted() {
+                self.fail_stuck_sync_request(known_version);
+                return;
+            }
             if let Err(e) = self.send_chunk_request(known_version, self.local_state.epoch()) {
                 error!(
                     LogSchema::event_log(LogEntry::Timeout, LogEvent::SendChunkRequestFail)
@@ -1145,6 +3904,109 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         }
     }
 
+    /// Fails the pending sync request with a cancellation error, after which the coordinator is
+    /// idle until the next sync request.
+    pub(crate) fn cancel_sync_request(&mut self) -> Result<()> {
+        let sync_request = self
+            .sync_request
+            .take()
+            .ok_or_else(|| format_err!("[state sync] No sync request to cancel"))?;
+        counters::SYNC_REQUEST_RESULT
+            .with_label_values(&[counters::CANCELLED_LABEL])
+            .inc();
+        info!(
+            LogSchema::event_log(LogEntry::SyncRequest, LogEvent::Cancelled)
+                .target_version(sync_request.target.ledger_info().version())
+        );
+        self.record_sync_request_completion(SyncRequestOutcome::Cancelled);
+        self.sync_request_epoch_transitions = 0;
+        counters::SYNC_REQUEST_REMAINING_EPOCHS.set(0);
+        // the caller may have stopped waiting for the request already
+        if let Err(e) =
+            Self::send_sync_req_callback(sync_request, Err(format_err!("sync cancelled")))
+        {
+            error!(LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail).error(&e));
+        }
+        Ok(())
+    }
+
+    /// Fails the pending sync request, initialization listener and version waiters (if any), so
+    /// that their callers are not left waiting when the coordinator stops.
+    fn drain_pending_callbacks(&mut self) {
+        if let Some(sync_request) = self.sync_request.take() {
+            self.record_sync_request_completion(SyncRequestOutcome::Cancelled);
+            if let Err(e) =
+                Self::send_sync_req_callback(sync_request, Err(format_err!("state sync shut down")))
+            {
+                error!(
+                    LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail).error(&e)
+                );
+            }
+        }
+        if let Some(listener) = self.initialization_listener.take() {
+            if let Err(e) = Self::send_initialization_callback(
+                listener,
+                Err(format_err!("state sync shut down")),
+            ) {
+                error!(LogSchema::event_log(LogEntry::Waypoint, LogEvent::CallbackFail).error(&e));
+            }
+        }
+        let version_waiters = std::mem::replace(&mut self.version_waiters, BTreeMap::new());
+        for callback in version_waiters
+            .into_iter()
+            .flat_map(|(_, callbacks)| callbacks)
+        {
+            Self::send_version_wait_callback(callback, Err(format_err!("state sync shut down")));
+        }
+    }
+
+    #[cfg(test)]
+    pub fn has_sync_request(&self) -> bool {
+        self.sync_request.is_some()
+    }
+
+    /// Whether `max_sync_attempts` chunk requests were sent for the pending sync request without
+    /// the synced version advancing.
+    fn sync_attempts_exhausted(&self) -> bool {
+        let max_attempts = self.config.max_sync_attempts;
+        max_attempts > 0
+            && self.sync_request.is_some()
+            && self
+                .sync_request_progress
+                .as_ref()
+                .map_or(false, |progress| progress.attempts >= max_attempts)
+    }
+
+    /// Fails the pending sync request that can't make progress past `stuck_version`, after which
+    /// the coordinator is idle until the next sync request.
+    fn fail_stuck_sync_request(&mut self, stuck_version: Version) {
+        counters::SYNC_REQUEST_RESULT
+            .with_label_values(&[counters::ATTEMPTS_EXHAUSTED_LABEL])
+            .inc();
+        warn!(
+            LogSchema::event_log(LogEntry::SyncRequest, LogEvent::AttemptsExhausted)
+                .version(stuck_version),
+            "no progress after {} chunk requests", self.config.max_sync_attempts
+        );
+        if let Some(sync_request) = self.sync_request.take() {
+            self.record_sync_request_completion(SyncRequestOutcome::AttemptsExhausted);
+            self.sync_request_epoch_transitions = 0;
+            counters::SYNC_REQUEST_REMAINING_EPOCHS.set(0);
+            if let Err(e) = Self::send_sync_req_callback(
+                sync_request,
+                Err(format_err!(
+                    "sync request stuck at version {} after {} attempts",
+                    stuck_version,
+                    self.config.max_sync_attempts
+                )),
+            ) {
+                error!(
+                    LogSchema::event_log(LogEntry::SyncRequest, LogEvent::CallbackFail).error(&e)
+                );
+            }
+        }
+    }
+
     /// Sends a chunk request with a given `known_version` and `known_epoch`
     /// (might be chosen optimistically).
     fn send_chunk_request(&mut self, known_version: u64, known_epoch: u64) -> Result<()> {
@@ -1158,15 +4020,30 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
 
         let target = if !self.is_initialized() {
             let waypoint_version = self.waypoint.version();
-            TargetType::Waypoint(waypoint_version)
+            match self.epoch_proofs_to_prefetch(known_epoch) {
+                Some(max_epoch_proofs) => TargetType::WaypointWithEpochProofs {
+                    version: waypoint_version,
+                    max_epoch_proofs,
+                },
+                None => TargetType::Waypoint(waypoint_version),
+            }
         } else {
             match self.sync_request.as_ref() {
                 None => {
+                    let timeout_ms = self.long_poll_timeout_ms();
+                    if self.role == RoleType::FullNode {
+                        // keep the retry timeout in line with the long poll timeout, so a request
+                        // held by upstream is not considered timed out prematurely
+                        self.request_manager
+                            .set_request_timeout(Duration::from_millis(
+                                self.config.tick_interval_ms + timeout_ms,
+                            ));
+                    }
                     TargetType::HighestAvailable {
                         // here, we need to ensure pending_ledger_infos is up-to-date with storage
                         // this is the responsibility of the caller of send_chunk_request
                         target_li: self.pending_ledger_infos.target_li(),
-                        timeout_ms: self.config.long_poll_timeout_ms,
+                        timeout_ms,
                     }
                 }
                 Some(sync_req) => {
@@ -1181,29 +4058,199 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
                         .local_synced_version(known_version), "Sync request is already fulfilled, so no need to send chunk requests for this sync request");
                         return Ok(());
                     }
-                    TargetType::TargetLedgerInfo(sync_req.target.clone())
+                    if let Some(progress) = self.sync_request_progress.as_mut() {
+                        progress.attempts += 1;
+                    }
+                    let timeout_ms = self.config.sync_request_long_poll_timeout_ms;
+                    if timeout_ms > 0 {
+                        // the request may be held by upstream for as long as the long poll lasts
+                        // (restored once the sync request is done)
+                        self.request_manager
+                            .set_request_timeout(Duration::from_millis(
+                                self.config.tick_interval_ms + timeout_ms,
+                            ));
+                        TargetType::TargetLedgerInfoWithTimeout {
+                            target_li: sync_req.target.clone(),
+                            timeout_ms,
+                        }
+                    } else {
+                        TargetType::TargetLedgerInfo(sync_req.target.clone())
+                    }
                 }
             }
         };
 
-        let req = GetChunkRequest::new(known_version, known_epoch, self.config.chunk_limit, target);
-        self.request_manager.send_chunk_request(req)
+        self.last_sent_target = Some(target.clone());
+        let fanout = match target {
+            TargetType::TargetLedgerInfo(_) | TargetType::TargetLedgerInfoWithTimeout { .. } => {
+                self.config.sync_request_fanout()
+            }
+            _ => 1,
+        };
+        let req = GetChunkRequest::new(
+            known_version,
+            known_epoch,
+            self.chunk_limit_controller.limit(),
+            target,
+        );
+        if fanout > 1 {
+            self.send_chunk_request_multi(req, fanout)
+        } else {
+            self.request_manager.send_chunk_request(req)
+        }
+    }
+
+    /// Sends `req` to the `fanout` highest scored upstream peers at once. This trades bandwidth
+    /// for latency: the responses for versions applied already are simply dropped as old chunks.
+    fn send_chunk_request_multi(&mut self, req: GetChunkRequest, fanout: usize) -> Result<()> {
+        let peers = self.request_manager.pick_top_peers(fanout);
+        self.request_manager.send_chunk_request_to_peers(req, peers)
+    }
+
+    /// Returns the timeout of the chunk requests, before any long poll adjustment.
+    fn base_request_timeout_ms(role: RoleType, config: &StateSyncConfig) -> u64 {
+        match role {
+            RoleType::FullNode => config.tick_interval_ms + config.long_poll_timeout_ms,
+            RoleType::Validator => 2 * config.tick_interval_ms,
+        }
+    }
+
+    /// Returns the timeout to use for long poll requests sent to upstream peers.
+    /// If adaptive long polling is enabled, the timeout is scaled to the observed interval between
+    /// commits, bounded by `tick_interval_ms` and `max_timeout_ms`.
+    fn long_poll_timeout_ms(&self) -> u64 {
+        if !self.config.adaptive_long_poll_timeout {
+            return self.config.long_poll_timeout_ms;
+        }
+        self.commit_interval_tracker.interval_ms().map_or(
+            self.config.long_poll_timeout_ms,
+            |interval_ms| {
+                let timeout_ms = std::cmp::max(
+                    interval_ms.saturating_mul(LONG_POLL_COMMIT_INTERVAL_MULTIPLIER),
+                    self.config.tick_interval_ms,
+                );
+                std::cmp::min(timeout_ms, self.config.max_timeout_ms)
+            },
+        )
+    }
+
+    /// Returns the max long poll timeout granted to subscribers. Beyond
+    /// `subscription_load_threshold_pct` percent of the subscription capacity, it's lowered
+    /// linearly from `max_timeout_ms` down to `min_subscription_timeout_ms` at full capacity, so
+    /// that the subscribers cycle faster and free up their slots.
+    pub(crate) fn effective_max_timeout_ms(&self) -> u64 {
+        let max_timeout_ms = self.config.max_timeout_ms;
+        let min_timeout_ms = std::cmp::min(self.config.min_subscription_timeout_ms, max_timeout_ms);
+        let capacity = self.config.max_subscription_memory_bytes / SUBSCRIPTION_MEMORY_ESTIMATE;
+        let threshold_pct = self.config.subscription_load_threshold_pct;
+        if min_timeout_ms == 0 || capacity == 0 || threshold_pct >= 100 {
+            return max_timeout_ms;
+        }
+        let load_pct = std::cmp::min(self.subscriptions.len() * 100 / capacity, 100) as u64;
+        if load_pct <= threshold_pct {
+            return max_timeout_ms;
+        }
+        let reduction = u128::from(max_timeout_ms - min_timeout_ms)
+            * u128::from(load_pct - threshold_pct)
+            / u128::from(100 - threshold_pct);
+        max_timeout_ms - reduction as u64
     }
 
+    /// The response LI of a subscription is chosen from the local state at delivery time (not at
+    /// subscription time), so a subscriber is always served relative to the freshest LI that can
+    /// be verified in its epoch, no matter how long it has been waiting.
     fn deliver_subscription(
         &mut self,
         peer: PeerNetworkId,
         request_info: PendingRequestInfo,
     ) -> Result<()> {
-        let response_li = self.choose_response_li(request_info.request_epoch, None)?;
+        let response_li =
+            self.choose_response_li(request_info.request_epoch, request_info.target_li.clone())?;
+        if self.is_stale_subscription(&request_info) {
+            counters::STALE_SUBSCRIPTION_DELIVERY_COUNT.inc();
+            debug!(LogSchema::event_log(
+                LogEntry::ProcessChunkRequest,
+                LogEvent::StaleSubscription
+            )
+            .peer(&peer)
+            .version(request_info.known_version)
+            .local_li_version(response_li.ledger_info().version()));
+        }
         self.deliver_chunk(
             peer,
             request_info.known_version,
             ResponseLedgerInfo::VerifiableLedgerInfo(response_li),
             request_info.limit,
+            request_info.limit,
         )
     }
 
+    pub(crate) fn subscriptions_snapshot(&self) -> Vec<SubscriptionInfo> {
+        let now = SystemTime::now();
+        self.subscriptions
+            .iter()
+            .filter_map(|(peer, request_info)| {
+                let remaining = request_info.expiration_time.duration_since(now).ok()?;
+                Some(SubscriptionInfo {
+                    peer: peer.clone(),
+                    known_version: request_info.known_version,
+                    request_epoch: request_info.request_epoch,
+                    limit: request_info.limit,
+                    remaining,
+                })
+            })
+            .collect()
+    }
+
+    /// Sheds the oldest pending subscriptions while their estimated memory exceeds
+    /// `max_subscription_memory_bytes`, instead of waiting for them to expire.
+    fn shed_subscriptions(&mut self) {
+        let max_bytes = self.config.max_subscription_memory_bytes;
+        let max_subscriptions = max_bytes / SUBSCRIPTION_MEMORY_ESTIMATE;
+        if max_bytes > 0 && self.subscriptions.len() > max_subscriptions {
+            let mut by_age: Vec<_> = self
+                .subscriptions
+                .iter()
+                .map(|(peer, request_info)| (request_info.subscription_time, peer.clone()))
+                .collect();
+            by_age.sort_by_key(|(subscription_time, _peer)| *subscription_time);
+            let num_shed = self.subscriptions.len() - max_subscriptions;
+            for (_subscription_time, peer) in by_age.into_iter().take(num_shed) {
+                self.subscriptions.remove(&peer);
+                counters::SHED_SUBSCRIPTION_COUNT
+                    .with_label_values(&[&peer.raw_network_id().to_string()])
+                    .inc();
+                warn!(LogSchema::event_log(
+                    LogEntry::ProcessChunkRequest,
+                    LogEvent::SubscriptionShed
+                )
+                .peer(&peer));
+            }
+        }
+        counters::SUBSCRIPTION_MEMORY_BYTES
+            .set((self.subscriptions.len() * SUBSCRIPTION_MEMORY_ESTIMATE) as i64);
+    }
+
+    /// A subscription is stale if it has been pending for longer than
+    /// `subscription_staleness_pct` percent of its timeout, and the local LI has meanwhile advanced
+    /// beyond what a single chunk can carry to the subscriber.
+    fn is_stale_subscription(&self, request_info: &PendingRequestInfo) -> bool {
+        let timeout = request_info
+            .expiration_time
+            .duration_since(request_info.subscription_time)
+            .unwrap_or_default();
+        let pending = SystemTime::now()
+            .duration_since(request_info.subscription_time)
+            .unwrap_or_default();
+        let highest_li_version = self.local_state.highest_local_li.ledger_info().version();
+        pending.as_millis() * 100
+            > timeout.as_millis() * u128::from(self.config.subscription_staleness_pct)
+            && highest_li_version
+                > request_info
+                    .known_version
+                    .saturating_add(request_info.limit)
+    }
+
     /// The function is called after the local storage is updated with new transactions:
     /// it might deliver chunks for the subscribers that have been waiting with the long polls.
     ///
@@ -1211,6 +4258,8 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
     /// the highest ledger info in the local storage (some committed transactions are ahead of the
     /// latest ledger info and are not going to be used for helping the remote subscribers).
     /// The function assumes that the local state has been synced with storage.
+    /// At most `max_subscription_deliveries` subscriptions are served at once, oldest first: the
+    /// remaining ready ones are deferred to the next check.
     fn check_subscriptions(&mut self) {
         let highest_li_version = self.local_state.highest_local_li.ledger_info().version();
 
@@ -1231,6 +4280,32 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
             }
         });
 
+        ready.sort_by_key(|(_peer, request_info)| request_info.subscription_time);
+        let max_deliveries = self.config.max_subscription_deliveries;
+        let deferred = if max_deliveries > 0 && ready.len() > max_deliveries {
+            ready.split_off(max_deliveries)
+        } else {
+            vec![]
+        };
+        self.deferred_subscription_deliveries = deferred.len();
+        counters::SUBSCRIPTION_DELIVERY_BACKLOG.set(deferred.len() as i64);
+        self.subscriptions.extend(deferred);
+        counters::SUBSCRIPTION_MEMORY_BYTES
+            .set((self.subscriptions.len() * SUBSCRIPTION_MEMORY_ESTIMATE) as i64);
+
+        if self.config.revalidate_subscribers {
+            // the serving policy may have changed since the subscriptions were registered
+            let serving_policy = &self.serving_policy;
+            ready.retain(|(peer, request_info)| {
+                let eligible = !request_info.revoked && serving_policy.allows(peer);
+                if !eligible {
+                    counters::REVOKED_SUBSCRIPTION_COUNT
+                        .with_label_values(&[&peer.raw_network_id().to_string()])
+                        .inc();
+                }
+                eligible
+            });
+        }
         ready.into_iter().for_each(|(peer, request_info)| {
             let result_label =
                 if let Err(err) = self.deliver_subscription(peer.clone(), request_info) {
@@ -1263,9 +4338,48 @@ impl<T: ExecutorProxyTrait> SyncCoordinator<T> {
         })
     }
 
+    /// Registers `callback` to be called once the synced version reaches `version` (right away
+    /// if it already did).
+    pub(crate) fn add_version_waiter(
+        &mut self,
+        version: Version,
+        callback: oneshot::Sender<Result<()>>,
+    ) {
+        if version <= self.local_state.highest_version_in_local_storage() {
+            Self::send_version_wait_callback(callback, Ok(()));
+        } else {
+            self.version_waiters
+                .entry(version)
+                .or_insert_with(Vec::new)
+                .push(callback);
+        }
+    }
+
+    /// Calls all the version waiters satisfied by `synced_version`.
+    fn notify_version_waiters(&mut self, synced_version: Version) {
+        let pending = self.version_waiters.split_off(&(synced_version + 1));
+        let satisfied = std::mem::replace(&mut self.version_waiters, pending);
+        for callback in satisfied.into_iter().flat_map(|(_, callbacks)| callbacks) {
+            Self::send_version_wait_callback(callback, Ok(()));
+        }
+    }
+
+    fn send_version_wait_callback(callback: oneshot::Sender<Result<()>>, msg: Result<()>) {
+        if callback.send(msg).is_err() {
+            // the waiter gave up waiting
+            counters::FAILED_CHANNEL_SEND
+                .with_label_values(&[counters::VERSION_WAIT_CALLBACK])
+                .inc();
+            debug!(LogSchema::event_log(
+                LogEntry::CommitFlow,
+                LogEvent::CallbackFail
+            ));
+        }
+    }
+
     fn send_initialization_callback(
-        cb: oneshot::Sender<Result<()>>,
-        msg: Result<()>,
+        cb: oneshot::Sender<Result<InitializationResult>>,
+        msg: Result<InitializationResult>,
     ) -> Result<()> {
         cb.send(msg).map_err(|failed_msg| {
             counters::FAILED_CHANNEL_SEND
@@ -26,10 +26,12 @@ pub const TARGET_VERSION_LABEL: &str = "target"; // Version a node is trying to
 // failed channel send type labels
 pub const CONSENSUS_SYNC_REQ_CALLBACK: &str = "consensus_sync_req_callback";
 pub const WAYPOINT_INIT_CALLBACK: &str = "waypoint_init_callback";
+pub const VERSION_WAIT_CALLBACK: &str = "version_wait_callback";
 
 // result labels
 pub const SUCCESS_LABEL: &str = "success";
 pub const FAIL_LABEL: &str = "fail";
+pub const STORAGE_UNAVAILABLE_LABEL: &str = "storage_unavailable";
 
 // commit flow fail component label
 pub const TO_MEMPOOL_LABEL: &str = "to_mempool";
@@ -40,6 +42,8 @@ pub const STATE_SYNC_LABEL: &str = "state_sync";
 // sync request result labels
 pub const COMPLETE_LABEL: &str = "complete";
 pub const TIMEOUT_LABEL: &str = "timeout";
+pub const ATTEMPTS_EXHAUSTED_LABEL: &str = "attempts_exhausted";
+pub const CANCELLED_LABEL: &str = "cancelled";
 
 /// Counter of pending network events to State Synchronizer
 pub static PENDING_STATE_SYNCHRONIZER_NETWORK_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
@@ -73,6 +77,16 @@ pub static RESPONSES_SENT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Time taken by an upstream peer to respond to a chunk request
+pub static PEER_RESPONSE_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "libra_state_sync_peer_response_time_s",
+        "Time taken by an upstream peer to respond to a chunk request",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
 pub static RESPONSE_FROM_DOWNSTREAM_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
         "libra_state_sync_responses_from_downstream_total",
@@ -82,6 +96,128 @@ pub static RESPONSE_FROM_DOWNSTREAM_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of chunk responses received on a network the matching request was not sent on
+pub static RESPONSE_NETWORK_MISMATCH_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_responses_network_mismatch_total",
+        "Number of chunk responses received on a network the request was not sent on",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
+/// Number of valid chunk responses received from peers no outstanding request was sent to
+pub static UNREQUESTED_RESPONSE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_unrequested_responses_total",
+        "Number of valid chunk responses received from peers that were not asked for them",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses rejected because their proof doesn't extend the local accumulator
+pub static FORK_DETECTED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_fork_detected_total",
+        "Number of chunk responses whose proof doesn't extend the local accumulator",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
+/// Number of epoch-ending LI lookups rejected because the lookup rate limit was exceeded
+pub static RATE_LIMITED_EPOCH_LOOKUP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_rate_limited_epoch_lookups_total",
+        "Number of epoch-ending LI lookups rejected by the lookup rate limit"
+    )
+    .unwrap()
+});
+
+/// Number of epoch-ending LI lookups performed in the current one-second window
+pub static EPOCH_LOOKUP_RATE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_epoch_lookup_rate",
+        "Number of epoch-ending LI lookups performed in the current second"
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses rejected because their waypoint LI failed the waypoint verification
+pub static WAYPOINT_MISMATCH_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_waypoint_mismatch_total",
+        "Number of chunk responses whose waypoint LI failed the waypoint verification"
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses rejected because their LI epoch is behind the local epoch
+pub static STALE_EPOCH_RESPONSE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_stale_epoch_responses_total",
+        "Number of chunk responses whose LI epoch is behind the local epoch",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
+/// Number of epoch transitions left before reaching the target of the current sync request
+pub static SYNC_REQUEST_REMAINING_EPOCHS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_sync_request_remaining_epochs",
+        "Number of epoch transitions left before reaching the target of the current sync request"
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses for a sync request rejected for jumping over epochs
+pub static UNEXPECTED_EPOCH_JUMP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_unexpected_epoch_jump_total",
+        "Number of chunk responses for a sync request whose LI jumped over epochs"
+    )
+    .unwrap()
+});
+
+/// Number of chunk requests being served off the coordinator loop
+pub static CHUNK_SERVES_IN_PROGRESS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_chunk_serves_in_progress",
+        "Number of chunk requests being served off the coordinator loop"
+    )
+    .unwrap()
+});
+
+/// Number of chunk requests answered with an empty chunk because too many serves were in progress
+pub static CHUNK_SERVES_REJECTED_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_chunk_serves_rejected_total",
+        "Number of chunk requests rejected because too many serves were in progress"
+    )
+    .unwrap()
+});
+
+/// Number of served chunks shrunk to fit in `max_chunk_response_bytes`
+pub static SHRUNK_CHUNK_RESPONSE_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_shrunk_chunk_response_total",
+        "Number of served chunks shrunk to fit in the max chunk response size"
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses whose freshest LI was older than `max_upstream_staleness_ms`
+pub static STALE_UPSTREAM_RESPONSE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_stale_upstream_responses_total",
+        "Number of chunk responses carrying an LI older than the max upstream staleness",
+        &["network", "peer"]
+    )
+    .unwrap()
+});
+
 /// Number of attempts to apply a chunk
 pub static APPLY_CHUNK_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -111,6 +247,21 @@ pub static STATE_SYNC_CHUNK_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
     .unwrap()
 });
 
+// served chunk size labels
+pub const REQUESTED_LABEL: &str = "requested";
+pub const SERVED_LABEL: &str = "served";
+
+/// Number of transactions requested by and served to downstream peers, the gap between the two
+/// showing how often requests are under-filled (e.g. near the tip or at epoch boundaries)
+pub static SERVED_CHUNK_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "libra_state_sync_served_chunk_size",
+        "Number of transactions requested by and served to downstream peers",
+        &["type"]
+    )
+    .unwrap()
+});
+
 /// Number of peers that are currently active and upstream.
 /// They are the set of nodes a node can make sync requests to
 pub static ACTIVE_UPSTREAM_PEERS: Lazy<IntGaugeVec> = Lazy::new(|| {
@@ -122,6 +273,27 @@ pub static ACTIVE_UPSTREAM_PEERS: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of warm-up probes sent to newly enabled upstream peers
+pub static WARM_UP_PROBE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_warm_up_probes_total",
+        "Number of warm-up probes sent to newly enabled upstream peers",
+        &["network", "result"]
+    )
+    .unwrap()
+});
+
+/// Number of times an already active upstream peer was enabled again, e.g. due to
+/// reconnection races
+pub static DUPLICATE_PEER_ENABLE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_duplicate_peer_enable_count",
+        "Number of times an already active upstream peer was enabled again",
+        &["network"]
+    )
+    .unwrap()
+});
+
 /// Number of networks this node is sending chunk requests to. It is usually 1
 /// but can be >1 if the node's primary network is unhealthy/all peers are dead
 /// and the node fails over to other networks
@@ -161,6 +333,22 @@ pub static SYNC_PROGRESS_DURATION: Lazy<DurationHistogram> = Lazy::new(|| {
     )
 });
 
+// sync duration labels, by the type of LI the applied chunk was verified against
+pub const WAYPOINT_RESPONSE_LABEL: &str = "waypoint";
+pub const VERIFIABLE_RESPONSE_LABEL: &str = "verifiable";
+pub const PROGRESSIVE_RESPONSE_LABEL: &str = "progressive";
+
+/// Same as `SYNC_PROGRESS_DURATION`, by the type of LI the chunk was verified against, which
+/// tells the waypoint sync, the sync to a target and the sync to the highest available LI apart
+pub static SYNC_PROGRESS_DURATION_BY_TYPE: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "libra_state_sync_sync_progress_duration_by_type_s",
+        "Histogram of time it takes to sync a chunk by the type of LI of the chunk response",
+        &["type"]
+    )
+    .unwrap()
+});
+
 /// Number of timeouts that occur during sync
 pub static TIMEOUT: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
@@ -208,6 +396,206 @@ pub static EXECUTE_CHUNK_DURATION: Lazy<Histogram> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Time it takes to verify the LIs of a chunk that crosses an epoch boundary (including the
+/// prefetched epoch proofs), the execution and commit of the chunk are not included
+pub static EPOCH_TRANSITION_VERIFY_DURATION: Lazy<DurationHistogram> = Lazy::new(|| {
+    DurationHistogram::new(
+        register_histogram!(
+            "libra_state_sync_epoch_transition_verify_duration_s",
+            "Histogram of time it takes to verify the ledger infos of a chunk that ends an epoch"
+        )
+        .unwrap(),
+    )
+});
+
+/// Number of chunks currently held in the reorder buffer
+pub static REORDER_BUFFER_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_reorder_buffer_size",
+        "Number of chunks received ahead of the local version that are waiting to be applied"
+    )
+    .unwrap()
+});
+
+/// Number of buffered chunks dropped because the reorder buffer was full
+pub static REORDER_BUFFER_OVERFLOW_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_reorder_buffer_overflow_count",
+        "Number of buffered chunks dropped because the reorder buffer was full"
+    )
+    .unwrap()
+});
+
+/// Number of times the node caught up with its target LI after lagging behind it
+pub static CAUGHT_UP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_caught_up_count",
+        "Number of times the synced version reached the target LI after lagging behind it"
+    )
+    .unwrap()
+});
+
+/// Serialized size of the LIs pending commit
+pub static PENDING_LI_QUEUE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_pending_li_queue_bytes",
+        "Serialized size in bytes of the queue of LIs pending commit"
+    )
+    .unwrap()
+});
+
+/// Number of sync phase transitions that could not be pushed to the phase change sink
+pub static PHASE_CHANGE_NOTIFICATION_DROP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_phase_change_notification_drop_count",
+        "Number of sync phase transitions dropped because the sink was full or closed"
+    )
+    .unwrap()
+});
+
+/// Number of low peer count signals that could not be pushed to the low peer count sink
+pub static LOW_PEER_COUNT_SIGNAL_DROP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_low_peer_count_signal_drop_count",
+        "Number of low peer count signals dropped because the sink was full or closed"
+    )
+    .unwrap()
+});
+
+/// Number of sync request records that could not be pushed to the completion sink
+pub static SYNC_REQUEST_RECORD_DROP_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_sync_request_record_drop_count",
+        "Number of sync request records dropped because the sink was full or closed"
+    )
+    .unwrap()
+});
+
+/// Number of reads the read replica could not serve because it lagged behind
+pub static READ_REPLICA_LAG_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_read_replica_lag_count",
+        "Number of reads the read replica could not serve because it lagged behind"
+    )
+    .unwrap()
+});
+
+/// Number of long poll requests that repeated the pending subscription of their peer, which was
+/// only refreshed
+pub static DUPLICATE_SUBSCRIPTION_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_duplicate_subscription_count",
+        "Number of long poll requests identical to the pending subscription of their peer",
+        &["network"]
+    )
+    .unwrap()
+});
+
+/// Number of subscriptions rejected because their known version was too far ahead of the local
+/// version
+pub static REJECTED_SUBSCRIPTION_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_rejected_subscription_count",
+        "Number of subscriptions with a known version too far ahead of the local version",
+        &["network"]
+    )
+    .unwrap()
+});
+
+/// Number of times the requested chunk limit was halved after repeated failures to apply chunks
+pub static CHUNK_LIMIT_DOWNGRADE_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_chunk_limit_downgrade_count",
+        "Number of times the requested chunk limit was downgraded"
+    )
+    .unwrap()
+});
+
+/// Number of times a downgraded chunk limit was doubled back after sustained successes
+pub static CHUNK_LIMIT_RECOVERY_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_chunk_limit_recovery_count",
+        "Number of times a downgraded chunk limit was recovered"
+    )
+    .unwrap()
+});
+
+/// Chunk limit currently requested from upstream peers
+pub static EFFECTIVE_CHUNK_LIMIT: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_effective_chunk_limit",
+        "Chunk limit currently requested from upstream peers"
+    )
+    .unwrap()
+});
+
+/// Estimated memory in bytes held by the pending long-poll subscriptions
+pub static SUBSCRIPTION_MEMORY_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_subscription_memory_bytes",
+        "Estimated memory held by the pending long-poll subscriptions"
+    )
+    .unwrap()
+});
+
+/// Max long poll timeout currently granted to subscribers
+pub static EFFECTIVE_MAX_TIMEOUT_MS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_effective_max_timeout_ms",
+        "Max long poll timeout currently granted to subscribers"
+    )
+    .unwrap()
+});
+
+/// Number of pending subscriptions shed because the subscriptions exceeded their memory limit
+pub static SHED_SUBSCRIPTION_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_shed_subscription_count",
+        "Number of pending subscriptions shed because of the subscription memory limit",
+        &["network"]
+    )
+    .unwrap()
+});
+
+/// Number of ready subscriptions dropped because the subscriber was no longer eligible to be
+/// served
+pub static REVOKED_SUBSCRIPTION_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_revoked_subscription_count",
+        "Number of ready subscriptions dropped because the subscriber was no longer eligible",
+        &["network"]
+    )
+    .unwrap()
+});
+
+/// Number of chunk responses after which the optimistically predicted epoch of the next chunk
+/// request differed from the local epoch after the chunk was processed
+pub static OPTIMISTIC_EPOCH_MISPREDICTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_optimistic_epoch_misprediction_count",
+        "Number of times the optimistic epoch of the next chunk request was mispredicted"
+    )
+    .unwrap()
+});
+
+/// Number of commits that did not advance the synced version and skipped the mempool notification
+pub static NOOP_COMMIT_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_noop_commit_count",
+        "Number of commits that did not advance the synced version"
+    )
+    .unwrap()
+});
+
+/// Number of ready long-poll subscriptions whose delivery was deferred to the next check
+pub static SUBSCRIPTION_DELIVERY_BACKLOG: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_subscription_delivery_backlog",
+        "Number of ready subscriptions whose delivery was deferred because of the delivery cap"
+    )
+    .unwrap()
+});
+
 /// Number of times a long-poll subscription is successfully delivered
 pub static SUBSCRIPTION_DELIVERY_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -218,6 +606,16 @@ pub static SUBSCRIPTION_DELIVERY_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of long-poll subscriptions that were delivered after being pending for a large fraction
+/// of their timeout while the local LI advanced far beyond their known version
+pub static STALE_SUBSCRIPTION_DELIVERY_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_stale_subscription_delivery_count",
+        "Number of stale long-poll subscriptions delivered"
+    )
+    .unwrap()
+});
+
 /// Time it takes to process a coordinator msg from consensus
 pub static PROCESS_COORDINATOR_MSG_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
@@ -263,6 +661,15 @@ pub static STORAGE_READ_FAIL_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of times the committed version or epoch read from storage moved backward
+pub static STORAGE_REGRESSION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_storage_regression_count",
+        "Number of times the committed state in storage moved backward"
+    )
+    .unwrap()
+});
+
 pub static NETWORK_ERROR_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
         "libra_state_sync_network_error_count",
@@ -270,3 +677,67 @@ pub static NETWORK_ERROR_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+/// Smoothed interval between consecutive commits that advanced the synced version
+pub static COMMIT_INTERVAL_MS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_commit_interval_ms",
+        "Smoothed interval in ms between commits that advanced the synced version"
+    )
+    .unwrap()
+});
+
+/// Number of committed user transactions per batched mempool commit notification
+pub static MEMPOOL_COMMIT_BATCH_SIZE: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "libra_state_sync_mempool_commit_batch_size",
+        "Number of committed user transactions in a batched mempool commit notification"
+    )
+    .unwrap()
+});
+
+/// Number of pending chunk requests served ahead of a progress check (`prioritize_serving`)
+pub static PRIORITIZED_CHUNK_REQUESTS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "libra_state_sync_prioritized_chunk_requests",
+        "Number of pending chunk requests served ahead of a progress check"
+    )
+    .unwrap()
+});
+
+/// Number of chunk commits that were sent to mempool and are still awaiting its ACK
+pub static COMMIT_PIPELINE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "libra_state_sync_commit_pipeline_depth",
+        "Number of chunk commits awaiting an ACK from mempool"
+    )
+    .unwrap()
+});
+
+/// Number of times applying a chunk was held back because the commit pipeline was full
+pub static COMMIT_PIPELINE_FULL_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_commit_pipeline_full_count",
+        "Number of times applying a chunk waited for mempool ACKs to drain"
+    )
+    .unwrap()
+});
+
+/// Number of epoch-ending LIs prefetched and verified during waypoint sync
+pub static EPOCH_PROOFS_PREFETCHED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "libra_state_sync_epoch_proofs_prefetched_total",
+        "Number of epoch-ending LIs prefetched and verified during waypoint sync"
+    )
+    .unwrap()
+});
+
+/// Number of coordinator checkpoints written to storage
+pub static CHECKPOINT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "libra_state_sync_checkpoint_count",
+        "Number of times the coordinator state was checkpointed to storage",
+        &["result"]
+    )
+    .unwrap()
+});
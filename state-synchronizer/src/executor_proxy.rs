@@ -9,6 +9,7 @@ use crate::{
 use anyhow::{format_err, Result};
 use executor_types::{ChunkExecutor, ExecutedTrees};
 use itertools::Itertools;
+use libra_config::config::PeerNetworkId;
 use libra_logger::prelude::*;
 use libra_types::{
     account_state::AccountState,
@@ -16,9 +17,10 @@ use libra_types::{
     ledger_info::LedgerInfoWithSignatures,
     move_resource::MoveStorage,
     on_chain_config::{config_address, OnChainConfigPayload, ON_CHAIN_CONFIG_REGISTRY},
-    transaction::TransactionListWithProof,
+    transaction::{TransactionListWithProof, Version},
 };
-use std::{collections::HashSet, convert::TryFrom, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, convert::TryFrom, fs, path::PathBuf, sync::Arc};
 use storage_interface::DbReader;
 use subscription_service::ReconfigSubscription;
 
@@ -35,6 +37,15 @@ pub trait ExecutorProxyTrait: Send {
         intermediate_end_of_epoch_li: Option<LedgerInfoWithSignatures>,
     ) -> Result<()>;
 
+    /// Verifies that a batch of transactions starts at `first_version` and its proof against the
+    /// verified target LI, without executing it
+    fn verify_chunk(
+        &self,
+        txn_list_with_proof: &TransactionListWithProof,
+        verified_target_li: &LedgerInfoWithSignatures,
+        first_version: Version,
+    ) -> Result<()>;
+
     /// Gets chunk of transactions given the known version, target version and the max limit.
     fn get_chunk(
         &self,
@@ -58,6 +69,60 @@ pub trait ExecutorProxyTrait: Send {
 
     /// publishes on-chain config updates to subscribed components
     fn publish_on_chain_config_updates(&mut self, events: Vec<ContractEvent>) -> Result<()>;
+
+    /// Persists a checkpoint of the coordinator state, replacing the previous one
+    fn save_checkpoint(&mut self, checkpoint: &SyncCheckpoint) -> Result<()>;
+
+    /// Loads the latest persisted checkpoint of the coordinator state (if any)
+    fn load_checkpoint(&self) -> Result<Option<SyncCheckpoint>>;
+
+    /// Persists the reconfiguration events of a commit until they are published, replacing the
+    /// previously journaled ones (no events clear the journal)
+    fn save_reconfig_journal(&mut self, events: &[ContractEvent]) -> Result<()>;
+
+    /// Loads the journaled reconfiguration events that might not have been published
+    fn load_reconfig_journal(&self) -> Result<Vec<ContractEvent>>;
+
+    /// Returns a reader of chunks that can be used outside of the coordinator, e.g. for serving
+    /// chunk requests on a separate thread
+    fn chunk_reader(&self) -> Arc<dyn ChunkReader>;
+}
+
+/// Reads chunks of transactions from storage
+pub trait ChunkReader: Send + Sync {
+    /// Gets chunk of transactions given the known version, target version and the max limit.
+    fn get_chunk(
+        &self,
+        known_version: u64,
+        limit: u64,
+        target_version: u64,
+    ) -> Result<TransactionListWithProof>;
+}
+
+pub(crate) struct StorageChunkReader {
+    storage: Arc<dyn DbReader>,
+}
+
+impl ChunkReader for StorageChunkReader {
+    fn get_chunk(
+        &self,
+        known_version: u64,
+        limit: u64,
+        target_version: u64,
+    ) -> Result<TransactionListWithProof> {
+        self.storage
+            .get_transactions(known_version + 1, limit, target_version, false)
+    }
+}
+
+/// The in-memory coordinator state that can be resumed after a restart: everything else is
+/// either in storage or rebuilt from scratch.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SyncCheckpoint {
+    /// Ledger infos in the future that have not been committed yet
+    pub pending_ledger_infos: Vec<LedgerInfoWithSignatures>,
+    /// Scores of the upstream peers in thousandths (LCS doesn't support floats)
+    pub peer_scores: Vec<(PeerNetworkId, u64)>,
 }
 
 pub(crate) struct ExecutorProxy {
@@ -65,6 +130,10 @@ pub(crate) struct ExecutorProxy {
     executor: Box<dyn ChunkExecutor>,
     reconfig_subscriptions: Vec<ReconfigSubscription>,
     on_chain_configs: OnChainConfigPayload,
+    // file the coordinator checkpoints are persisted to, if any
+    checkpoint_path: Option<PathBuf>,
+    // file the reconfiguration events are journaled to until they are published, if any
+    reconfig_journal_path: Option<PathBuf>,
 }
 
 impl ExecutorProxy {
@@ -72,6 +141,8 @@ impl ExecutorProxy {
         storage: Arc<dyn DbReader>,
         executor: Box<dyn ChunkExecutor>,
         mut reconfig_subscriptions: Vec<ReconfigSubscription>,
+        checkpoint_path: Option<PathBuf>,
+        reconfig_journal_path: Option<PathBuf>,
     ) -> Self {
         let on_chain_configs = Self::fetch_all_configs(&*storage)
             .expect("[state sync] Failed initial read of on-chain configs");
@@ -85,6 +156,8 @@ impl ExecutorProxy {
             executor,
             reconfig_subscriptions,
             on_chain_configs,
+            checkpoint_path,
+            reconfig_journal_path,
         }
     }
 
@@ -173,6 +246,15 @@ impl ExecutorProxyTrait for ExecutorProxy {
         Ok(())
     }
 
+    fn verify_chunk(
+        &self,
+        txn_list_with_proof: &TransactionListWithProof,
+        verified_target_li: &LedgerInfoWithSignatures,
+        first_version: Version,
+    ) -> Result<()> {
+        txn_list_with_proof.verify(verified_target_li.ledger_info(), Some(first_version))
+    }
+
     fn get_chunk(
         &self,
         known_version: u64,
@@ -268,4 +350,54 @@ impl ExecutorProxyTrait for ExecutorProxy {
             Err(format_err!("failed to publish at least one subscription"))
         }
     }
+
+    fn save_checkpoint(&mut self, checkpoint: &SyncCheckpoint) -> Result<()> {
+        let path = self
+            .checkpoint_path
+            .as_ref()
+            .ok_or_else(|| format_err!("[state sync] No checkpoint path"))?;
+        // write to a temporary file first so that a crash never leaves a partial checkpoint
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, lcs::to_bytes(checkpoint)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn load_checkpoint(&self) -> Result<Option<SyncCheckpoint>> {
+        match self.checkpoint_path.as_ref() {
+            Some(path) if path.exists() => Ok(Some(lcs::from_bytes(&fs::read(path)?)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn save_reconfig_journal(&mut self, events: &[ContractEvent]) -> Result<()> {
+        let path = self
+            .reconfig_journal_path
+            .as_ref()
+            .ok_or_else(|| format_err!("[state sync] No reconfig journal path"))?;
+        if events.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        // same as for checkpoints, a crash never leaves a partial journal
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, lcs::to_bytes(events)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn load_reconfig_journal(&self) -> Result<Vec<ContractEvent>> {
+        match self.reconfig_journal_path.as_ref() {
+            Some(path) if path.exists() => Ok(lcs::from_bytes(&fs::read(path)?)?),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn chunk_reader(&self) -> Arc<dyn ChunkReader> {
+        Arc::new(StorageChunkReader {
+            storage: self.storage.clone(),
+        })
+    }
 }
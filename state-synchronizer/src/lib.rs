@@ -7,8 +7,13 @@
 //! Used for node restarts, network partitions, full node syncs
 #![recursion_limit = "1024"]
 
+pub use coordinator::{
+    ChunkTiming, EpochChangeNotification, InitializationResult, ServingPolicy, SubscriptionInfo,
+    SyncMetrics, SyncPhase, SyncPhaseChange, SyncRequestOutcome, SyncRequestRecord,
+};
 use executor_types::ExecutedTrees;
 use libra_types::{epoch_state::EpochState, ledger_info::LedgerInfoWithSignatures};
+pub use request_manager::{NetworkHealth, PeerReliability};
 pub use synchronizer::{StateSyncClient, StateSynchronizer};
 
 pub mod chunk_request;
@@ -101,6 +101,7 @@ pub enum LogEntry {
     LostPeer,
     Waypoint,
     RuntimeStart,
+    RuntimeShutdown,
     ConsensusCommit,
     SyncRequest,
     Timeout,
@@ -113,6 +114,9 @@ pub enum LogEntry {
     CommitFlow,
     Multicast,
     SubscriptionDeliveryFail,
+    Checkpoint,
+    PersistedTargetLi,
+    ResetPeerScore,
 }
 
 #[derive(Clone, Copy, Serialize)]
@@ -125,6 +129,19 @@ pub enum LogEvent {
     PublishError,
     Fail,
 
+    // NewPeer events
+    AlreadyEnabled,
+    WarmUpProbe,
+    Capabilities,
+
+    // LostPeer events
+    LowPeerCount,
+
+    // SyncRequest events
+    MultiEpochTarget,
+    AttemptsExhausted,
+    Cancelled,
+
     // SendChunkRequest events
     MissingPeers,
     OldSyncRequest,
@@ -139,12 +156,33 @@ pub enum LogEvent {
     ApplyChunkFail,
     PostCommitFail,
     OldResponseLI,
+    PeerUnavailable,
+    Tip,
 
     // ProcessChunkRequest events
     PastEpochRequested,
     DeliverChunk,
+    StorageUnavailable,
+    StaleSubscription,
+    SubscriptionTooFarAhead,
+    SubscriptionShed,
+
+    // LocalState events
+    CaughtUp,
+    StorageRegression,
 
     // Multicast network events
     Failover,
     Recover,
+    ForceMulticast,
+
+    // Reconfig events
+    JournalFail,
+    Republish,
+
+    // Waypoint events
+    PhaseChange,
+    WaypointInitTimeout,
+    WaypointMismatch,
+    WaypointUpdate,
 }
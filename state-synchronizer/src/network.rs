@@ -20,6 +20,48 @@ use serde::{Deserialize, Serialize};
 pub enum StateSynchronizerMsg {
     GetChunkRequest(Box<GetChunkRequest>),
     GetChunkResponse(Box<GetChunkResponse>),
+    /// Advertises the optional protocol features supported by the sender. With
+    /// `advertise_capabilities` set, it's sent to every peer upon connection: older peers drop
+    /// it, and are only sent the messages every version understands.
+    Capabilities(SyncCapabilities),
+    /// Asks the receiver for a `WarmUpProbeResponse`, so that the connection is established by
+    /// the time the first chunk request is sent to it. Older peers drop it.
+    WarmUpProbe,
+    WarmUpProbeResponse,
+}
+
+/// Optional state sync protocol features supported by a node, as a bitmask. Bits unknown to the
+/// receiver are ignored, so that new features don't break the older nodes.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SyncCapabilities(u64);
+
+impl SyncCapabilities {
+    /// `TargetType::WaypointWithEpochProofs` chunk requests.
+    pub const EPOCH_PROOFS: u64 = 1;
+    /// `ResponseLedgerInfo::UnavailableLedgerInfo` chunk responses.
+    pub const UNAVAILABLE_RESPONSES: u64 = 1 << 1;
+    /// `ResponseLedgerInfo::PartialLedgerInfo` chunk responses.
+    pub const PARTIAL_RESPONSES: u64 = 1 << 2;
+    /// `TargetType::TargetLedgerInfoWithTimeout` chunk requests.
+    pub const LONG_POLL_TARGETS: u64 = 1 << 3;
+
+    pub fn new(features: u64) -> Self {
+        Self(features)
+    }
+
+    /// The features supported by this node.
+    pub fn local() -> Self {
+        Self(
+            Self::EPOCH_PROOFS
+                | Self::UNAVAILABLE_RESPONSES
+                | Self::PARTIAL_RESPONSES
+                | Self::LONG_POLL_TARGETS,
+        )
+    }
+
+    pub fn supports(self, feature: u64) -> bool {
+        self.0 & feature == feature
+    }
 }
 
 /// The interface from Network to StateSynchronizer layer.
@@ -2,10 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    chunk_request::GetChunkRequest,
+    chunk_request::{GetChunkRequest, TargetType},
     counters,
     logging::{LogEntry, LogEvent, LogSchema},
-    network::{StateSynchronizerMsg, StateSynchronizerSender},
+    network::{StateSynchronizerMsg, StateSynchronizerSender, SyncCapabilities},
 };
 use anyhow::{bail, Result};
 use itertools::Itertools;
@@ -20,23 +20,82 @@ use rand::{
     thread_rng,
 };
 use std::{
-    collections::{BTreeMap, HashMap},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 const MAX_SCORE: f64 = 100.0;
 const MIN_SCORE: f64 = 1.0;
+// Multiplier applied to the score of a peer once per stale chunk it sent in a row, i.e. the n-th
+// consecutive stale chunk multiplies the score by `STALE_CHUNK_MULTIPLIER^n`
+const STALE_CHUNK_MULTIPLIER: f64 = 0.5;
+const MAX_STALE_CHUNK_EXPONENT: u32 = 10;
 const MIN_UPSTREAM_NETWORK_CT: usize = 1;
+// Max number of the latest response times of a peer its mean response time is computed over
+const MAX_RESPONSE_TIME_HISTORY: usize = 100;
+// Max number of peers to keep reliability stats for, beyond which the stats of the peers that
+// are not alive anymore are dropped
+const MAX_RELIABILITY_PEERS: usize = 1000;
 
 #[derive(Default, Debug, Clone)]
 pub struct PeerInfo {
     is_alive: bool,
     score: f64,
+    // number of stale chunks received in a row from this peer
+    stale_chunks: u32,
 }
 
 impl PeerInfo {
     pub fn new(is_alive: bool, score: f64) -> Self {
-        Self { is_alive, score }
+        Self {
+            is_alive,
+            score,
+            stale_chunks: 0,
+        }
+    }
+}
+
+/// Reliability of an upstream peer, as observed by this node
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerReliability {
+    /// Number of chunks successfully applied
+    pub successes: u64,
+    /// Number of requests that timed out and of chunks that could not be applied
+    pub failures: u64,
+    /// Number of empty chunks received
+    pub empty_chunks: u64,
+    /// Number of chunks that failed verification
+    pub invalid_chunks: u64,
+    /// Mean response time over the latest responses (if any)
+    pub mean_response_time: Option<Duration>,
+}
+
+/// Sync health of one of the networks this node participates in, as observed by this node
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkHealth {
+    /// Number of enabled upstream peers of the network whose score is high enough to be picked
+    pub available_peers: usize,
+    /// Seconds since a chunk from a peer of the network was last successfully applied, none if
+    /// none ever was
+    pub secs_since_last_success: Option<u64>,
+}
+
+#[derive(Default)]
+struct ReliabilityStats {
+    reliability: PeerReliability,
+    // latest response times, oldest first
+    response_times: VecDeque<Duration>,
+}
+
+impl ReliabilityStats {
+    fn add_response_time(&mut self, response_time: Duration) {
+        if self.response_times.len() >= MAX_RESPONSE_TIME_HISTORY {
+            self.response_times.pop_front();
+        }
+        self.response_times.push_back(response_time);
+        let total: Duration = self.response_times.iter().sum();
+        self.reliability.mean_response_time = Some(total / self.response_times.len() as u32);
     }
 }
 
@@ -49,11 +108,19 @@ pub struct ChunkRequestInfo {
     multicast_level: usize,
     multicast_start_time: SystemTime,
     last_request_peers: Vec<PeerNetworkId>,
+    // all networks this request has been sent on across its attempts
+    request_networks: HashSet<NodeNetworkId>,
+    // all peers this request has been sent to across its attempts
+    requested_peers: HashSet<PeerNetworkId>,
+    // number of consecutive timeouts of this request, the retry timeout is backed off accordingly
+    timeouts: u32,
 }
 
 impl ChunkRequestInfo {
     pub fn new(version: u64, peers: Vec<PeerNetworkId>, multicast_level: usize) -> Self {
         let now = SystemTime::now();
+        let request_networks = peers.iter().map(PeerNetworkId::network_id).collect();
+        let requested_peers = peers.iter().cloned().collect();
         Self {
             version,
             first_request_time: now,
@@ -61,6 +128,9 @@ impl ChunkRequestInfo {
             multicast_level,
             multicast_start_time: now,
             last_request_peers: peers,
+            request_networks,
+            requested_peers,
+            timeouts: 0,
         }
     }
 }
@@ -74,6 +144,15 @@ pub enum PeerScoreUpdateType {
     ChunkVersionCannotBeApplied,
     InvalidChunk,
     TimeOut,
+    // The freshest LI carried by a chunk is too old: the peer is lagging behind itself.
+    StaleUpstream,
+    // The LI carried by a chunk belongs to an epoch the node has already left.
+    StaleEpoch,
+    // A received chunk starts at or below the synced version, i.e. it's already applied.
+    // Penalized increasingly while the peer keeps sending such chunks if the escalation is on.
+    StaleChunk,
+    // A valid chunk was received from a peer that no outstanding request was sent to.
+    UnrequestedResponse,
 }
 
 pub struct RequestManager {
@@ -81,10 +160,13 @@ pub struct RequestManager {
     // grouped by network preference
     eligible_peers: BTreeMap<usize, (Vec<PeerNetworkId>, Option<WeightedIndex<f64>>)>,
     peers: HashMap<PeerNetworkId, PeerInfo>,
+    reliability: HashMap<PeerNetworkId, ReliabilityStats>,
     requests: BTreeMap<u64, ChunkRequestInfo>,
     upstream_config: UpstreamConfig,
     // duration with the same version before the next attempt to get the next chunk
     request_timeout: Duration,
+    // ceiling of `request_timeout` doubled on every consecutive timeout of the same request
+    max_request_timeout: Duration,
     // duration with the same version before multicasting, i.e. sending the next chunk request to more networks
     multicast_timeout: Duration,
     // number of networks to try to multicast the same chunk request to
@@ -92,12 +174,25 @@ pub struct RequestManager {
     // available, in order of preference specified by the upstream config
     multicast_level: usize,
     network_senders: HashMap<NodeNetworkId, StateSynchronizerSender>,
+    // upstream peers that were sent a warm-up probe whose response hasn't been received yet
+    warm_up_probes: HashSet<PeerNetworkId>,
+    // time a chunk from a peer of the network was last successfully applied, per network
+    last_success_times: HashMap<NodeNetworkId, SystemTime>,
+    // optional protocol features advertised by the connected peers, the peers missing here are
+    // only sent the requests every version understands
+    peer_capabilities: HashMap<PeerNetworkId, SyncCapabilities>,
+    // score below which a peer is only picked for chunk requests when none of the peers of its
+    // network is above it
+    availability_score_threshold: f64,
+    // whether the penalty of consecutive stale chunks escalates
+    escalate_stale_chunk_penalty: bool,
 }
 
 impl RequestManager {
     pub fn new(
         upstream_config: UpstreamConfig,
         request_timeout: Duration,
+        max_request_timeout: Duration,
         multicast_timeout: Duration,
         network_senders: HashMap<NodeNetworkId, StateSynchronizerSender>,
     ) -> Self {
@@ -105,34 +200,54 @@ impl RequestManager {
         Self {
             eligible_peers: BTreeMap::new(),
             peers: HashMap::new(),
+            reliability: HashMap::new(),
             requests: BTreeMap::new(),
             upstream_config,
             request_timeout,
+            max_request_timeout,
             multicast_timeout,
             multicast_level: MIN_UPSTREAM_NETWORK_CT,
             network_senders,
+            warm_up_probes: HashSet::new(),
+            last_success_times: HashMap::new(),
+            peer_capabilities: HashMap::new(),
+            availability_score_threshold: 0.0,
+            escalate_stale_chunk_penalty: false,
         }
     }
 
-    pub fn enable_peer(&mut self, peer: PeerNetworkId, origin: ConnectionOrigin) {
+    /// Returns whether `peer` was newly enabled as an upstream peer.
+    pub fn enable_peer(&mut self, peer: PeerNetworkId, origin: ConnectionOrigin) -> bool {
         let is_upstream_peer = self.is_upstream_peer(&peer, origin);
         debug!(LogSchema::new(LogEntry::NewPeer)
             .peer(&peer)
             .is_upstream_peer(is_upstream_peer));
 
         if !is_upstream_peer {
-            return;
+            return false;
         }
 
-        counters::ACTIVE_UPSTREAM_PEERS
-            .with_label_values(&[&peer.raw_network_id().to_string()])
-            .inc();
         if let Some(peer_info) = self.peers.get_mut(&peer) {
+            if peer_info.is_alive {
+                // e.g. a reconnection race: the peer keeps its state
+                debug!(
+                    LogSchema::event_log(LogEntry::NewPeer, LogEvent::AlreadyEnabled).peer(&peer)
+                );
+                counters::DUPLICATE_PEER_ENABLE_COUNT
+                    .with_label_values(&[&peer.raw_network_id().to_string()])
+                    .inc();
+                return false;
+            }
             peer_info.is_alive = true;
         } else {
-            self.peers.insert(peer, PeerInfo::new(true, MAX_SCORE));
+            self.peers
+                .insert(peer.clone(), PeerInfo::new(true, MAX_SCORE));
         }
+        counters::ACTIVE_UPSTREAM_PEERS
+            .with_label_values(&[&peer.raw_network_id().to_string()])
+            .inc();
         self.update_peer_selection_data();
+        true
     }
 
     pub fn disable_peer(&mut self, peer: &PeerNetworkId, origin: ConnectionOrigin) {
@@ -140,33 +255,226 @@ impl RequestManager {
             .peer(&peer)
             .is_upstream_peer(self.is_upstream_peer(&peer, origin)));
 
-        if let Some(peer_info) = self.peers.get_mut(peer) {
+        if let Some(peer_info) = self
+            .peers
+            .get_mut(peer)
+            .filter(|peer_info| peer_info.is_alive)
+        {
             counters::ACTIVE_UPSTREAM_PEERS
                 .with_label_values(&[&peer.raw_network_id().to_string()])
                 .dec();
             peer_info.is_alive = false;
         }
+        self.warm_up_probes.remove(peer);
+        self.peer_capabilities.remove(peer);
+        if self.reliability.len() > MAX_RELIABILITY_PEERS {
+            let peers = &self.peers;
+            self.reliability.retain(|peer, _| {
+                peers
+                    .get(peer)
+                    .map_or(false, |peer_info| peer_info.is_alive)
+            });
+        }
+        self.update_peer_selection_data();
+    }
+
+    /// Records the protocol features advertised by `peer` for the lifetime of its connection.
+    pub fn set_peer_capabilities(&mut self, peer: PeerNetworkId, capabilities: SyncCapabilities) {
+        self.peer_capabilities.insert(peer, capabilities);
+    }
+
+    /// Returns the protocol features advertised by `peer`, none if it didn't advertise any.
+    pub fn peer_capabilities(&self, peer: &PeerNetworkId) -> SyncCapabilities {
+        self.peer_capabilities
+            .get(peer)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the version of `req` to send to `peer`: the targets relying on features the peer
+    /// didn't advertise fall back to the targets they extend.
+    fn request_for_peer(&self, req: &GetChunkRequest, peer: &PeerNetworkId) -> GetChunkRequest {
+        let capabilities = self.peer_capabilities(peer);
+        let target = match req.target() {
+            TargetType::WaypointWithEpochProofs { version, .. }
+                if !capabilities.supports(SyncCapabilities::EPOCH_PROOFS) =>
+            {
+                TargetType::Waypoint(*version)
+            }
+            TargetType::TargetLedgerInfoWithTimeout { target_li, .. }
+                if !capabilities.supports(SyncCapabilities::LONG_POLL_TARGETS) =>
+            {
+                TargetType::TargetLedgerInfo(target_li.clone())
+            }
+            _ => return req.clone(),
+        };
+        GetChunkRequest::new(req.known_version, req.current_epoch, req.limit, target)
+    }
+
+    /// Gives a known peer a clean slate by resetting its score to the default, e.g. once the
+    /// transient issues it was penalized for have been resolved.
+    pub fn reset_score(&mut self, peer: &PeerNetworkId) -> Result<()> {
+        let peer_info = match self.peers.get_mut(peer) {
+            Some(peer_info) => peer_info,
+            None => bail!("Cannot reset score of unknown peer {:?}", peer),
+        };
+        let old_score = peer_info.score;
+        peer_info.score = MAX_SCORE;
+        info!(
+            LogSchema::new(LogEntry::ResetPeerScore).peer(peer),
+            "Manually reset peer score from {} to {}", old_score, MAX_SCORE
+        );
+        self.update_peer_selection_data();
+        Ok(())
+    }
+
+    /// Restores the score of `peer`, e.g. from a checkpoint taken before a restart: the peer
+    /// is going to be eligible for requests once it's enabled.
+    pub fn restore_score(&mut self, peer: PeerNetworkId, score: f64) {
+        let score = score.max(MIN_SCORE).min(MAX_SCORE);
+        match self.peers.get_mut(&peer) {
+            Some(peer_info) => peer_info.score = score,
+            None => {
+                self.peers.insert(peer, PeerInfo::new(false, score));
+            }
+        }
+        self.update_peer_selection_data();
+    }
+
+    pub fn peer_scores(&self) -> Vec<(PeerNetworkId, f64)> {
+        self.peers
+            .iter()
+            .map(|(peer, peer_info)| (peer.clone(), peer_info.score))
+            .collect()
+    }
+
+    /// Records the time `peer` took to respond to the request for `version`
+    pub fn record_response_time(&mut self, peer: &PeerNetworkId, version: u64) {
+        let request_time = match self.requests.get(&version) {
+            Some(req) if req.last_request_peers.contains(peer) => req.last_request_time,
+            _ => return,
+        };
+        if !self.peers.contains_key(peer) {
+            return;
+        }
+        if let Ok(response_time) = SystemTime::now().duration_since(request_time) {
+            counters::PEER_RESPONSE_TIME
+                .with_label_values(&[
+                    &peer.raw_network_id().to_string(),
+                    &peer.peer_id().to_string(),
+                ])
+                .observe(response_time.as_secs_f64());
+            self.reliability
+                .entry(peer.clone())
+                .or_default()
+                .add_response_time(response_time);
+        }
+    }
+
+    pub fn peer_reliability(&self) -> HashMap<PeerNetworkId, PeerReliability> {
+        self.reliability
+            .iter()
+            .map(|(peer, stats)| (peer.clone(), stats.reliability.clone()))
+            .collect()
+    }
+
+    /// Returns the sync health of every network this node participates in (or knows upstream
+    /// peers of), so that a dead network isn't masked by a healthy one.
+    pub fn network_health(&self) -> HashMap<NodeNetworkId, NetworkHealth> {
+        let mut health: HashMap<_, _> = self
+            .network_senders
+            .keys()
+            .map(|network_id| (network_id.clone(), NetworkHealth::default()))
+            .collect();
+        for (peer, peer_info) in self.peers.iter() {
+            let network_health = health.entry(peer.network_id()).or_default();
+            if peer_info.is_alive && peer_info.score >= self.availability_score_threshold {
+                network_health.available_peers += 1;
+            }
+        }
+        let now = SystemTime::now();
+        for (network_id, last_success_time) in self.last_success_times.iter() {
+            let since_last_success = now.duration_since(*last_success_time).unwrap_or_default();
+            health
+                .entry(network_id.clone())
+                .or_default()
+                .secs_since_last_success = Some(since_last_success.as_secs());
+        }
+        health
+    }
+
+    pub fn set_request_timeout(&mut self, request_timeout: Duration) {
+        self.request_timeout = request_timeout;
+    }
+
+    /// Sets the score below which a peer is left out of the peer selection as long as its
+    /// network has peers above it (0 disables it).
+    pub fn set_availability_score_threshold(&mut self, threshold: f64) {
+        self.availability_score_threshold = threshold;
         self.update_peer_selection_data();
     }
 
+    /// Sets whether the n-th stale chunk in a row multiplies the score of its sender by 0.5^n,
+    /// instead of the flat penalty of the chunks that can't be applied.
+    pub fn set_escalate_stale_chunk_penalty(&mut self, escalate: bool) {
+        self.escalate_stale_chunk_penalty = escalate;
+    }
+
+    /// Returns the number of upstream peers that are currently enabled
+    pub fn num_enabled_peers(&self) -> usize {
+        self.peers
+            .values()
+            .filter(|peer_info| peer_info.is_alive)
+            .count()
+    }
+
     pub fn no_available_peers(&self) -> bool {
         self.eligible_peers.is_empty()
     }
 
     pub fn update_score(&mut self, peer: &PeerNetworkId, update_type: PeerScoreUpdateType) {
+        let escalate_stale_chunk_penalty = self.escalate_stale_chunk_penalty;
         if let Some(peer_info) = self.peers.get_mut(peer) {
             let old_score = peer_info.score;
+            let reliability = &mut self
+                .reliability
+                .entry(peer.clone())
+                .or_default()
+                .reliability;
+            match update_type {
+                PeerScoreUpdateType::Success => reliability.successes += 1,
+                PeerScoreUpdateType::EmptyChunk => reliability.empty_chunks += 1,
+                PeerScoreUpdateType::InvalidChunk => reliability.invalid_chunks += 1,
+                PeerScoreUpdateType::TimeOut
+                | PeerScoreUpdateType::ChunkVersionCannotBeApplied
+                | PeerScoreUpdateType::StaleChunk => reliability.failures += 1,
+                PeerScoreUpdateType::StaleUpstream
+                | PeerScoreUpdateType::StaleEpoch
+                | PeerScoreUpdateType::UnrequestedResponse => {}
+            }
             match update_type {
                 PeerScoreUpdateType::Success => {
                     let new_score = peer_info.score + 1.0;
                     peer_info.score = new_score.min(MAX_SCORE);
+                    peer_info.stale_chunks = 0;
+                }
+                PeerScoreUpdateType::StaleChunk if escalate_stale_chunk_penalty => {
+                    peer_info.stale_chunks += 1;
+                    let exponent = std::cmp::min(peer_info.stale_chunks, MAX_STALE_CHUNK_EXPONENT);
+                    let new_score = peer_info.score * STALE_CHUNK_MULTIPLIER.powi(exponent as i32);
+                    peer_info.score = new_score.max(MIN_SCORE);
                 }
                 PeerScoreUpdateType::InvalidChunk
-                | PeerScoreUpdateType::ChunkVersionCannotBeApplied => {
+                | PeerScoreUpdateType::ChunkVersionCannotBeApplied
+                | PeerScoreUpdateType::StaleChunk => {
                     let new_score = peer_info.score * 0.8;
                     peer_info.score = new_score.max(MIN_SCORE);
                 }
-                PeerScoreUpdateType::TimeOut | PeerScoreUpdateType::EmptyChunk => {
+                PeerScoreUpdateType::TimeOut
+                | PeerScoreUpdateType::EmptyChunk
+                | PeerScoreUpdateType::StaleUpstream
+                | PeerScoreUpdateType::StaleEpoch
+                | PeerScoreUpdateType::UnrequestedResponse => {
                     let new_score = peer_info.score * 0.95;
                     peer_info.score = new_score.max(MIN_SCORE);
                 }
@@ -196,9 +504,21 @@ impl RequestManager {
             .into_group_map();
 
         // for each network, compute peer selection data
+        let threshold = self.availability_score_threshold;
         self.eligible_peers = active_peers
             .into_iter()
             .map(|(network_pref, peers)| {
+                // the peers with a score below the availability threshold are left out as long as
+                // the network has other peers to pick from
+                let has_available_peer = peers
+                    .iter()
+                    .any(|(_peer, peer_info)| peer_info.score >= threshold);
+                let peers: Vec<_> = peers
+                    .into_iter()
+                    .filter(|(_peer, peer_info)| {
+                        !has_available_peer || peer_info.score >= threshold
+                    })
+                    .collect();
                 let mut eligible_peers = vec![];
                 let weights: Vec<_> = peers
                     .iter()
@@ -242,11 +562,48 @@ impl RequestManager {
             .collect::<Vec<_>>()
     }
 
+    /// Returns up to `num_peers` eligible peers with the highest scores, the peers of the preferred
+    /// networks first on equal scores
+    pub fn pick_top_peers(&self, num_peers: usize) -> Vec<PeerNetworkId> {
+        let mut peers: Vec<_> = self
+            .eligible_peers
+            .iter()
+            .flat_map(|(network_pref, (peers, _))| {
+                peers.iter().map(move |peer| (*network_pref, peer))
+            })
+            .filter_map(|(network_pref, peer)| {
+                self.peers
+                    .get(peer)
+                    .map(|peer_info| (network_pref, peer_info.score, peer.clone()))
+            })
+            .collect();
+        peers.sort_by(|(pref_a, score_a, _), (pref_b, score_b, _)| {
+            score_b
+                .partial_cmp(score_a)
+                .unwrap_or(Ordering::Equal)
+                .then(pref_a.cmp(pref_b))
+        });
+        peers
+            .into_iter()
+            .take(num_peers)
+            .map(|(_, _, peer)| peer)
+            .collect()
+    }
+
     pub fn send_chunk_request(&mut self, req: GetChunkRequest) -> Result<()> {
+        let peers = self.pick_peers();
+        self.send_chunk_request_to_peers(req, peers)
+    }
+
+    /// Sends `req` to every peer of `peers`, tracking them all as the peers of the request
+    pub fn send_chunk_request_to_peers(
+        &mut self,
+        req: GetChunkRequest,
+        peers: Vec<PeerNetworkId>,
+    ) -> Result<()> {
         let log = LogSchema::new(LogEntry::SendChunkRequest).chunk_req(&req);
 
         // update internal state
-        let peers = self.pick_peers();
         if peers.is_empty() {
             warn!(log.event(LogEvent::MissingPeers));
             bail!("No peers to send chunk request to");
@@ -260,15 +617,16 @@ impl RequestManager {
 
         // actually execute network send
         let target_version = req.target().version();
-        let msg = StateSynchronizerMsg::GetChunkRequest(Box::new(req));
         let mut failed_peer_sends = vec![];
         for peer in peers {
+            let msg =
+                StateSynchronizerMsg::GetChunkRequest(Box::new(self.request_for_peer(&req, &peer)));
             let sender = self
                 .network_senders
                 .get_mut(&peer.network_id())
                 .expect("missing network sender for peer");
             let peer_id = peer.peer_id();
-            let send_result = sender.send_to(peer_id, msg.clone());
+            let send_result = sender.send_to(peer_id, msg);
             let curr_log = log.clone().peer(&peer);
             let result_label = if let Err(e) = send_result {
                 failed_peer_sends.push(peer.clone());
@@ -300,6 +658,36 @@ impl RequestManager {
         }
     }
 
+    /// Sends a warm-up probe to the upstream `peer` to establish the connection before chunk
+    /// requests are sent to it. The probe is not tracked as an outstanding request and at most one
+    /// probe is in flight per peer.
+    pub fn send_warm_up_probe(&mut self, peer: &PeerNetworkId) -> Result<()> {
+        if !self.is_known_upstream_peer(peer) || !self.warm_up_probes.insert(peer.clone()) {
+            return Ok(());
+        }
+        let sender = self
+            .network_senders
+            .get_mut(&peer.network_id())
+            .expect("missing network sender for peer");
+        let send_result = sender.send_to(peer.peer_id(), StateSynchronizerMsg::WarmUpProbe);
+        let result_label = if send_result.is_ok() {
+            counters::SEND_SUCCESS_LABEL
+        } else {
+            self.warm_up_probes.remove(peer);
+            counters::SEND_FAIL_LABEL
+        };
+        counters::WARM_UP_PROBE_COUNT
+            .with_label_values(&[&peer.raw_network_id().to_string(), result_label])
+            .inc();
+        send_result.map_err(|e| e.into())
+    }
+
+    /// Returns whether a warm-up probe sent to `peer` was waiting for a response, in which case
+    /// the probe is considered answered.
+    pub fn process_warm_up_response(&mut self, peer: &PeerNetworkId) -> bool {
+        self.warm_up_probes.remove(peer)
+    }
+
     pub fn add_request(&mut self, version: u64, peers: Vec<PeerNetworkId>) -> ChunkRequestInfo {
         if let Some(prev_request) = self.requests.get_mut(&version) {
             let now = SystemTime::now();
@@ -308,6 +696,10 @@ impl RequestManager {
                 prev_request.multicast_level = self.multicast_level;
                 prev_request.multicast_start_time = now;
             }
+            prev_request
+                .request_networks
+                .extend(peers.iter().map(PeerNetworkId::network_id));
+            prev_request.requested_peers.extend(peers.iter().cloned());
             prev_request.last_request_peers = peers;
             prev_request.last_request_time = now;
             prev_request.clone()
@@ -338,6 +730,9 @@ impl RequestManager {
             counters::MULTICAST_LEVEL.set(self.multicast_level as i64);
         }
 
+        self.last_success_times
+            .insert(peer.network_id(), SystemTime::now());
+
         // update score
         self.update_score(peer, PeerScoreUpdateType::Success);
     }
@@ -348,6 +743,7 @@ impl RequestManager {
         peer: &PeerNetworkId,
         chunk_version: u64,
         synced_version: u64,
+        update_type: PeerScoreUpdateType,
     ) -> Result<()> {
         if self.is_multicast_response(chunk_version, peer) {
             // This chunk response was in response to a past multicast response that another
@@ -360,7 +756,7 @@ impl RequestManager {
                 chunk_version
             );
         } else {
-            self.update_score(&peer, PeerScoreUpdateType::ChunkVersionCannotBeApplied);
+            self.update_score(&peer, update_type);
             bail!(
                 "[state sync] Non sequential chunk from {:?}: known_version: {}, received: {}",
                 peer,
@@ -376,6 +772,22 @@ impl RequestManager {
         })
     }
 
+    /// Returns true if there is an outstanding request for `version` and it was never sent on
+    /// the network of `peer`
+    pub fn is_network_mismatch(&self, version: u64, peer: &PeerNetworkId) -> bool {
+        self.requests.get(&version).map_or(false, |req| {
+            !req.request_networks.contains(&peer.network_id())
+        })
+    }
+
+    /// Returns true if the outstanding request for `version` has been sent to `peer` in any of
+    /// its attempts
+    pub fn is_requested_peer(&self, version: u64, peer: &PeerNetworkId) -> bool {
+        self.requests
+            .get(&version)
+            .map_or(false, |req| req.requested_peers.contains(peer))
+    }
+
     pub fn get_last_request_time(&self, version: u64) -> Option<SystemTime> {
         self.requests
             .get(&version)
@@ -394,6 +806,25 @@ impl RequestManager {
             .map(|req_info| req_info.multicast_start_time)
     }
 
+    /// Makes the request for `version` eligible for a retry at the next progress check,
+    /// e.g. because a peer explicitly signalled that it is not able to serve it.
+    pub fn expedite_retry(&mut self, version: u64) {
+        if let Some(req) = self.requests.get_mut(&version) {
+            req.last_request_time = UNIX_EPOCH;
+        }
+    }
+
+    /// `peer` explicitly signalled that it is not able to serve the request for `version` right
+    /// now: the request is retried at the next progress check, without penalizing `peer` for
+    /// the request timing out.
+    pub fn process_unavailable_response(&mut self, peer: &PeerNetworkId, version: u64) {
+        if let Some(req) = self.requests.get_mut(&version) {
+            req.last_request_peers
+                .retain(|request_peer| request_peer != peer);
+        }
+        self.expedite_retry(version);
+    }
+
     /// Removes requests whose known_version < `version` if they are older than now - `timeout`
     /// We keep the requests that have not timed out so we don't penalize
     /// peers who send chunks after the first peer who sends the first successful chunk response for a
@@ -405,7 +836,7 @@ impl RequestManager {
             .requests
             .range(..version)
             .filter_map(|(version, req)| {
-                if Self::is_timeout(req.last_request_time, self.request_timeout) {
+                if Self::is_timeout(req.last_request_time, self.backoff_timeout(req.timeouts)) {
                     Some(*version)
                 } else {
                     None
@@ -423,14 +854,17 @@ impl RequestManager {
     pub fn check_timeout(&mut self, version: u64) -> bool {
         let last_request_time = self.get_last_request_time(version).unwrap_or(UNIX_EPOCH);
 
-        let is_timeout = Self::is_timeout(last_request_time, self.request_timeout);
+        let is_timeout = Self::is_timeout(last_request_time, self.get_request_timeout(version));
         if !is_timeout {
             return is_timeout;
         }
 
-        // update peer info based on timeout
-        let peers_to_penalize = match self.requests.get(&version) {
-            Some(prev_request) => prev_request.last_request_peers.clone(),
+        // update peer info based on timeout and back off the next retry
+        let peers_to_penalize = match self.requests.get_mut(&version) {
+            Some(prev_request) => {
+                prev_request.timeouts = prev_request.timeouts.saturating_add(1);
+                prev_request.last_request_peers.clone()
+            }
             None => {
                 return is_timeout;
             }
@@ -458,6 +892,42 @@ impl RequestManager {
         is_timeout
     }
 
+    /// Returns the current retry timeout of the request for `version`: `request_timeout` doubled
+    /// for every consecutive timeout of the request, up to `max_request_timeout`
+    pub fn get_request_timeout(&self, version: u64) -> Duration {
+        let timeouts = self.requests.get(&version).map_or(0, |req| req.timeouts);
+        self.backoff_timeout(timeouts)
+    }
+
+    fn backoff_timeout(&self, timeouts: u32) -> Duration {
+        let backoff = self
+            .request_timeout
+            .checked_mul(2u32.saturating_pow(timeouts))
+            .unwrap_or(self.max_request_timeout);
+        std::cmp::max(
+            self.request_timeout,
+            std::cmp::min(backoff, self.max_request_timeout),
+        )
+    }
+
+    /// Escalates to the highest multicast level right away, restarting the multicast timer of
+    /// the request for `version`
+    pub fn force_multicast(&mut self, version: u64) {
+        let prev_multicast_level = self.multicast_level;
+        self.multicast_level = self.upstream_config.upstream_count();
+        if let Some(req) = self.requests.get_mut(&version) {
+            req.multicast_level = self.multicast_level;
+            req.multicast_start_time = SystemTime::now();
+        }
+        info!(
+            LogSchema::event_log(LogEntry::Multicast, LogEvent::ForceMulticast)
+                .old_multicast_level(prev_multicast_level)
+                .new_multicast_level(self.multicast_level),
+            request_version = version,
+        );
+        counters::MULTICAST_LEVEL.set(self.multicast_level as i64);
+    }
+
     pub fn is_upstream_peer(&self, peer: &PeerNetworkId, origin: ConnectionOrigin) -> bool {
         let is_network_upstream = self
             .upstream_config
@@ -1,11 +1,15 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 use crate::{
+    chunk_request::TargetType,
+    chunk_response::GetChunkResponse,
     coordinator::{CoordinatorMessage, SyncCoordinator, SyncRequest},
     counters,
     executor_proxy::{ExecutorProxy, ExecutorProxyTrait},
     network::{StateSynchronizerEvents, StateSynchronizerSender},
-    SynchronizerState,
+    request_manager::{NetworkHealth, PeerReliability},
+    ChunkTiming, EpochChangeNotification, InitializationResult, ServingPolicy, SubscriptionInfo,
+    SyncMetrics, SyncPhaseChange, SyncRequestRecord, SynchronizerState,
 };
 use anyhow::{format_err, Result};
 use executor_types::ChunkExecutor;
@@ -15,12 +19,14 @@ use futures::{
     SinkExt,
 };
 use libra_config::{
-    config::{NodeConfig, RoleType, StateSyncConfig, UpstreamConfig},
+    config::{NodeConfig, PeerNetworkId, RoleType, StateSyncConfig, UpstreamConfig},
     network_id::NodeNetworkId,
 };
 use libra_mempool::{CommitNotification, CommitResponse};
 use libra_types::{
-    contract_event::ContractEvent, ledger_info::LedgerInfoWithSignatures, transaction::Transaction,
+    contract_event::ContractEvent,
+    ledger_info::LedgerInfoWithSignatures,
+    transaction::{Transaction, Version},
     waypoint::Waypoint,
 };
 use std::{
@@ -36,6 +42,12 @@ use tokio::{
     time::timeout,
 };
 
+// Name of the file in the storage directory that coordinator checkpoints are persisted to
+const CHECKPOINT_FILE_NAME: &str = "state_sync_checkpoint";
+// Name of the file in the storage directory that reconfiguration events are journaled to until
+// they are published
+const RECONFIG_JOURNAL_FILE_NAME: &str = "state_sync_reconfig_journal";
+
 pub struct StateSynchronizer {
     _runtime: Runtime,
     coordinator_sender: mpsc::UnboundedSender<CoordinatorMessage>,
@@ -63,7 +75,13 @@ impl StateSynchronizer {
             .build()
             .expect("[state synchronizer] failed to create runtime");
 
-        let executor_proxy = ExecutorProxy::new(storage, executor, reconfig_event_subscriptions);
+        let executor_proxy = ExecutorProxy::new(
+            storage,
+            executor,
+            reconfig_event_subscriptions,
+            Some(config.storage.dir().join(CHECKPOINT_FILE_NAME)),
+            Some(config.storage.dir().join(RECONFIG_JOURNAL_FILE_NAME)),
+        );
         Self::bootstrap_with_executor_proxy(
             runtime,
             network,
@@ -73,6 +91,9 @@ impl StateSynchronizer {
             &config.state_sync,
             config.upstream.clone(),
             executor_proxy,
+            None,
+            None,
+            None,
         )
     }
 
@@ -89,6 +110,12 @@ impl StateSynchronizer {
         state_sync_config: &StateSyncConfig,
         upstream_config: UpstreamConfig,
         executor_proxy: E,
+        // optional read-only replica the chunk requests of other nodes are served from
+        read_replica: Option<E>,
+        // optional sender of the epoch changes observed in the local storage
+        epoch_change_sender: Option<mpsc::Sender<EpochChangeNotification>>,
+        // optional target LI persisted before a restart (see `StateSyncClient::get_best_target_li`)
+        persisted_target_li: Option<LedgerInfoWithSignatures>,
     ) -> Self {
         let (coordinator_sender, coordinator_receiver) = mpsc::unbounded();
 
@@ -101,7 +128,7 @@ impl StateSynchronizer {
             .map(|(network_id, sender, _events)| (network_id.clone(), sender.clone()))
             .collect();
 
-        let coordinator = SyncCoordinator::new(
+        let mut coordinator = SyncCoordinator::new(
             coordinator_receiver,
             state_sync_to_mempool_sender,
             network_senders,
@@ -111,7 +138,12 @@ impl StateSynchronizer {
             upstream_config,
             executor_proxy,
             initial_state,
+            epoch_change_sender,
+            persisted_target_li,
         );
+        if let Some(read_replica) = read_replica {
+            coordinator.set_read_replica(read_replica);
+        }
         runtime.spawn(coordinator.start(network));
 
         Self {
@@ -125,8 +157,9 @@ impl StateSynchronizer {
     }
 
     /// The function returns a future that is fulfilled when the state synchronizer is
-    /// caught up with the waypoint specified in the local config.
-    pub async fn wait_until_initialized(&self) -> Result<()> {
+    /// caught up with the waypoint specified in the local config. The result tells whether
+    /// the waypoint was already reached when the call was made or only reached later.
+    pub async fn wait_until_initialized(&self) -> Result<InitializationResult> {
         let mut sender = self.coordinator_sender.clone();
         let (cb_sender, cb_receiver) = oneshot::channel();
         sender
@@ -167,6 +200,45 @@ impl StateSyncClient {
         }
     }
 
+    /// Cancels the pending sync request (its caller gets an error), e.g. when its target is no
+    /// longer needed. Fails if there is no pending sync request.
+    pub fn cancel_sync(&self) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::CancelSync(cb_sender))
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Returns a future that is fulfilled once the synced version of the local storage reaches
+    /// `version`. Unlike `sync_to`, it doesn't drive syncing: it only watches the progress.
+    pub fn wait_for_version(&self, version: Version) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (callback, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::WaitForVersion { version, callback })
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Stops state synchronizer, failing the pending sync request and listeners first and
+    /// notifying mempool of the pending commits. The returned future is fulfilled once the
+    /// shutdown is complete.
+    pub fn shutdown(&self) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender.send(CoordinatorMessage::Shutdown(cb_sender)).await?;
+            cb_receiver.await?;
+            Ok(())
+        }
+    }
+
     /// Notifies state synchronizer about new version
     pub fn commit(
         &self,
@@ -214,4 +286,230 @@ impl StateSyncClient {
             Ok(info)
         }
     }
+
+    /// Resets the score of a known upstream peer to the default, e.g. after a transient issue
+    /// the peer was penalized for has been fixed.
+    pub fn reset_peer_score(&self, peer: PeerNetworkId) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::ResetPeerScore(peer, cb_sender))
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Returns the reliability of the upstream peers observed by this node
+    pub fn get_peer_reliability(
+        &self,
+    ) -> impl Future<Output = Result<HashMap<PeerNetworkId, PeerReliability>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetPeerReliability(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Sends the current chunk request to all the upstream networks right away instead of
+    /// waiting for the multicast level to escalate on timeouts.
+    pub fn force_multicast(&self) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::ForceMulticast(cb_sender))
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Registers a sink every transition between sync phases (e.g. from waypoint sync to
+    /// normal operation) is pushed to. Transitions are dropped if the sink is full.
+    pub fn set_phase_change_sink(
+        &self,
+        sink: mpsc::Sender<SyncPhaseChange>,
+    ) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        async move {
+            sender
+                .send(CoordinatorMessage::SetPhaseChangeSink(sink))
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Returns the time spent in the phases of applying the recently applied chunks (oldest
+    /// first). Only recorded if `record_chunk_timings` is set in the config.
+    pub fn get_recent_chunk_timings(&self) -> impl Future<Output = Result<Vec<ChunkTiming>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetRecentChunks(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Returns the recent sync rate along with the synced version and the highest pending
+    /// target, e.g. for estimating the time to sync completion
+    pub fn get_sync_metrics(&self) -> impl Future<Output = Result<SyncMetrics>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetSyncMetrics(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Returns the highest LI the state synchronizer is pending to sync to, if any. It can be
+    /// persisted by the caller and passed back on restart to resume syncing towards it.
+    pub fn get_best_target_li(
+        &self,
+    ) -> impl Future<Output = Result<Option<LedgerInfoWithSignatures>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetBestTargetLi(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Returns the sync health of every network the state synchronizer participates in: a
+    /// network without available upstream peers or without recent successful responses may be
+    /// dead even though syncing progresses through another one.
+    pub fn get_network_health(
+        &self,
+    ) -> impl Future<Output = Result<HashMap<NodeNetworkId, NetworkHealth>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetNetworkHealth(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Replaces the waypoint state synchronizer is syncing to, e.g. to recover from a bootstrap
+    /// with a stale or wrong waypoint without a restart. Fails if the node already reached its
+    /// waypoint or if the new waypoint is behind the current one.
+    pub fn update_waypoint(&self, waypoint: Waypoint) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (callback, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::UpdateWaypoint { waypoint, callback })
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Verifies the LI and the transaction proofs of a chunk response the same way state
+    /// synchronizer would before applying it, without executing or committing the chunk.
+    pub fn verify_chunk(&self, response: GetChunkResponse) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (callback, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::VerifyChunk {
+                    response: Box::new(response),
+                    callback,
+                })
+                .await?;
+            cb_receiver.await?
+        }
+    }
+
+    /// Returns the config the state synchronizer is running with
+    pub fn get_config(&self) -> impl Future<Output = Result<StateSyncConfig>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetConfig(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Returns the target of the latest chunk request sent to upstream, which tells whether the
+    /// node is syncing to the waypoint, to a sync request target or polling for the highest
+    /// available version
+    pub fn get_current_target(&self) -> impl Future<Output = Result<Option<TargetType>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetCurrentTarget(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
+
+    /// Registers a sink the number of enabled upstream peers is pushed to whenever losing a peer
+    /// leaves fewer than `min_upstream_peers` of them, e.g. to make a discovery subsystem seek
+    /// more peers. Signals are dropped if the sink is full.
+    pub fn set_low_peer_count_sink(
+        &self,
+        sink: mpsc::Sender<usize>,
+    ) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        async move {
+            sender
+                .send(CoordinatorMessage::SetLowPeerCountSink(sink))
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Registers a sink the record of every completed sync request (reached, timed out or
+    /// cancelled) is pushed to, e.g. for keeping an audit trail of the sync requests. Records
+    /// are dropped if the sink is full.
+    pub fn set_completion_sink(
+        &self,
+        sink: mpsc::Sender<SyncRequestRecord>,
+    ) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        async move {
+            sender
+                .send(CoordinatorMessage::SetCompletionSink(sink))
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Restricts the peers this node serves chunks to, e.g. to stop serving a network or a
+    /// misbehaving peer. The policy applies to the subsequent requests, and to the pending
+    /// subscriptions if `revalidate_subscribers` is set.
+    pub fn set_serving_policy(&self, policy: ServingPolicy) -> impl Future<Output = Result<()>> {
+        let mut sender = self.coordinator_sender.clone();
+        async move {
+            sender
+                .send(CoordinatorMessage::SetServingPolicy(policy))
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Returns a snapshot of the long poll subscriptions pending on this node, e.g. for
+    /// diagnosing why downstream nodes are not being served
+    pub fn get_subscriptions(&self) -> impl Future<Output = Result<Vec<SubscriptionInfo>>> {
+        let mut sender = self.coordinator_sender.clone();
+        let (cb_sender, cb_receiver) = oneshot::channel();
+        async move {
+            sender
+                .send(CoordinatorMessage::GetSubscriptions(cb_sender))
+                .await?;
+            Ok(cb_receiver.await?)
+        }
+    }
 }
@@ -5,7 +5,7 @@ use crate::{
     chunk_request::{GetChunkRequest, TargetType},
     chunk_response::{GetChunkResponse, ResponseLedgerInfo},
     coordinator::SyncCoordinator,
-    network::{StateSynchronizerMsg, StateSynchronizerSender},
+    network::{StateSynchronizerMsg, StateSynchronizerSender, SyncCapabilities},
     tests::{
         helpers::{MockExecutorProxy, SynchronizerEnvHelper},
         mock_storage::MockStorage,
@@ -84,6 +84,8 @@ pub fn test_state_sync_msg_fuzzer_impl(msg: StateSynchronizerMsg) {
         config.upstream,
         MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), storage_proxy),
         initial_state,
+        None,
+        None,
     );
     let mut rt = tokio::runtime::Builder::new()
         .basic_scheduler()
@@ -103,7 +105,12 @@ pub fn state_sync_msg_strategy() -> impl Strategy<Value = StateSynchronizerMsg>
         }),
         (any::<GetChunkResponse>()).prop_map(|get_chunk_response| {
             StateSynchronizerMsg::GetChunkResponse(Box::new(get_chunk_response))
-        })
+        }),
+        (any::<u64>()).prop_map(|features| StateSynchronizerMsg::Capabilities(
+            SyncCapabilities::new(features)
+        )),
+        Just(StateSynchronizerMsg::WarmUpProbe),
+        Just(StateSynchronizerMsg::WarmUpProbeResponse),
     ]
 }
 
@@ -131,7 +138,11 @@ impl Arbitrary for TargetType {
         prop_oneof![
             (any::<LedgerInfoWithSignatures>()).prop_map(TargetType::TargetLedgerInfo),
             highest_available_strategy(),
-            (any::<u64>()).prop_map(TargetType::Waypoint)
+            (any::<u64>()).prop_map(TargetType::Waypoint),
+            waypoint_with_epoch_proofs_strategy(),
+            Just(TargetType::Tip),
+            (any::<u64>()).prop_map(|epoch| TargetType::EpochEnding { epoch }),
+            target_li_with_timeout_strategy()
         ]
         .boxed()
     }
@@ -139,6 +150,15 @@ impl Arbitrary for TargetType {
     type Strategy = BoxedStrategy<Self>;
 }
 
+fn target_li_with_timeout_strategy() -> impl Strategy<Value = TargetType> {
+    (any::<LedgerInfoWithSignatures>(), any::<u64>()).prop_map(|(target_li, timeout_ms)| {
+        TargetType::TargetLedgerInfoWithTimeout {
+            target_li,
+            timeout_ms,
+        }
+    })
+}
+
 fn highest_available_strategy() -> impl Strategy<Value = TargetType> {
     (option::of(any::<LedgerInfoWithSignatures>()), any::<u64>()).prop_map(
         |(target_li, timeout_ms)| TargetType::HighestAvailable {
@@ -148,6 +168,15 @@ fn highest_available_strategy() -> impl Strategy<Value = TargetType> {
     )
 }
 
+fn waypoint_with_epoch_proofs_strategy() -> impl Strategy<Value = TargetType> {
+    (any::<u64>(), any::<u64>()).prop_map(|(version, max_epoch_proofs)| {
+        TargetType::WaypointWithEpochProofs {
+            version,
+            max_epoch_proofs,
+        }
+    })
+}
+
 impl Arbitrary for GetChunkResponse {
     type Parameters = ();
 
@@ -171,7 +200,11 @@ impl Arbitrary for ResponseLedgerInfo {
         prop_oneof![
             (any::<LedgerInfoWithSignatures>()).prop_map(ResponseLedgerInfo::VerifiableLedgerInfo),
             progressive_li_strategy(),
-            li_for_waypoint_strategy()
+            li_for_waypoint_strategy(),
+            li_for_waypoint_with_epoch_proofs_strategy(),
+            (any::<LedgerInfoWithSignatures>()).prop_map(ResponseLedgerInfo::UnavailableLedgerInfo),
+            (any::<LedgerInfoWithSignatures>()).prop_map(ResponseLedgerInfo::TipLedgerInfo),
+            (any::<LedgerInfoWithSignatures>()).prop_map(ResponseLedgerInfo::PartialLedgerInfo)
         ]
         .boxed()
     }
@@ -204,3 +237,18 @@ fn li_for_waypoint_strategy() -> impl Strategy<Value = ResponseLedgerInfo> {
             },
         )
 }
+
+fn li_for_waypoint_with_epoch_proofs_strategy() -> impl Strategy<Value = ResponseLedgerInfo> {
+    (
+        any::<LedgerInfoWithSignatures>(),
+        option::of(any::<LedgerInfoWithSignatures>()),
+        prop::collection::vec(any::<LedgerInfoWithSignatures>(), 0..3),
+    )
+        .prop_map(|(waypoint_li, end_of_epoch_li, epoch_proofs)| {
+            ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                waypoint_li,
+                end_of_epoch_li,
+                epoch_proofs,
+            }
+        })
+}
@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    executor_proxy::ExecutorProxyTrait, tests::mock_storage::MockStorage, SynchronizerState,
+    executor_proxy::{ChunkReader, ExecutorProxyTrait, SyncCheckpoint},
+    tests::mock_storage::MockStorage,
+    SynchronizerState,
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use libra_config::config::HANDSHAKE_VERSION;
 use libra_crypto::{hash::ACCUMULATOR_PLACEHOLDER_HASH, test_utils::TEST_SEED, x25519, Uniform};
 use libra_network_address::{
@@ -12,15 +14,22 @@ use libra_network_address::{
     NetworkAddress, Protocol,
 };
 use libra_types::{
-    contract_event::ContractEvent, ledger_info::LedgerInfoWithSignatures,
-    on_chain_config::ValidatorSet, proof::TransactionListProof,
-    transaction::TransactionListWithProof, validator_config::ValidatorConfig,
-    validator_info::ValidatorInfo, validator_signer::ValidatorSigner,
+    contract_event::ContractEvent,
+    ledger_info::LedgerInfoWithSignatures,
+    on_chain_config::ValidatorSet,
+    proof::TransactionListProof,
+    transaction::{TransactionListWithProof, Version},
+    validator_config::ValidatorConfig,
+    validator_info::ValidatorInfo,
+    validator_signer::ValidatorSigner,
     validator_verifier::random_validator_verifier,
 };
 use memsocket::MemoryListener;
 use rand::{rngs::StdRng, SeedableRng};
-use std::sync::{Arc, RwLock};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
 
 pub(crate) struct SynchronizerEnvHelper;
 
@@ -94,18 +103,90 @@ pub(crate) type MockRpcHandler = Box<
 >;
 
 pub(crate) struct MockExecutorProxy {
-    handler: MockRpcHandler,
+    chunk_reader: Arc<MockChunkReader>,
     storage: Arc<RwLock<MockStorage>>,
+    // chunks with more transactions fail to execute
+    max_chunk_size: Option<usize>,
+    // reconfiguration events published to the subscribed components
+    published_reconfig_events: Option<Arc<RwLock<Vec<ContractEvent>>>>,
+    // reads of the local storage state fail while set
+    storage_unavailable: Option<Arc<AtomicBool>>,
 }
 
 impl MockExecutorProxy {
     pub(crate) fn new(handler: MockRpcHandler, storage: Arc<RwLock<MockStorage>>) -> Self {
-        Self { handler, storage }
+        let chunk_reader = Arc::new(MockChunkReader {
+            handler,
+            storage: storage.clone(),
+        });
+        Self {
+            chunk_reader,
+            storage,
+            max_chunk_size: None,
+            published_reconfig_events: None,
+            storage_unavailable: None,
+        }
+    }
+
+    /// Fails the execution of chunks larger than `max_chunk_size`, e.g. to simulate memory
+    /// pressure.
+    pub(crate) fn with_max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = Some(max_chunk_size);
+        self
+    }
+
+    /// Records the published reconfiguration events into `published_reconfig_events`.
+    pub(crate) fn with_published_reconfig_events(
+        mut self,
+        published_reconfig_events: Arc<RwLock<Vec<ContractEvent>>>,
+    ) -> Self {
+        self.published_reconfig_events = Some(published_reconfig_events);
+        self
+    }
+
+    /// Fails the reads of the local storage state while `storage_unavailable` is set.
+    pub(crate) fn with_storage_unavailable(mut self, storage_unavailable: Arc<AtomicBool>) -> Self {
+        self.storage_unavailable = Some(storage_unavailable);
+        self
+    }
+}
+
+pub(crate) struct MockChunkReader {
+    handler: MockRpcHandler,
+    storage: Arc<RwLock<MockStorage>>,
+}
+
+impl ChunkReader for MockChunkReader {
+    fn get_chunk(
+        &self,
+        known_version: u64,
+        limit: u64,
+        target_version: u64,
+    ) -> Result<TransactionListWithProof> {
+        let txns = self
+            .storage
+            .read()
+            .unwrap()
+            .get_chunk(known_version + 1, limit, target_version);
+        let first_txn_version = txns.first().map(|_| known_version + 1);
+        let txns_with_proof = TransactionListWithProof::new(
+            txns,
+            None,
+            first_txn_version,
+            TransactionListProof::new_empty(),
+        );
+        (self.handler)(txns_with_proof)
     }
 }
 
 impl ExecutorProxyTrait for MockExecutorProxy {
     fn get_local_storage_state(&self) -> Result<SynchronizerState> {
+        if let Some(storage_unavailable) = &self.storage_unavailable {
+            ensure!(
+                !storage_unavailable.load(Ordering::SeqCst),
+                "[mock executor proxy] local storage is unavailable"
+            );
+        }
         Ok(self.storage.read().unwrap().get_local_storage_state())
     }
 
@@ -115,6 +196,13 @@ impl ExecutorProxyTrait for MockExecutorProxy {
         ledger_info_with_sigs: LedgerInfoWithSignatures,
         intermediate_end_of_epoch_li: Option<LedgerInfoWithSignatures>,
     ) -> Result<()> {
+        if let Some(max_chunk_size) = self.max_chunk_size {
+            ensure!(
+                txn_list_with_proof.len() <= max_chunk_size,
+                "chunk of {} transactions is too large to execute",
+                txn_list_with_proof.len()
+            );
+        }
         self.storage.write().unwrap().add_txns_with_li(
             txn_list_with_proof.transactions,
             ledger_info_with_sigs,
@@ -123,25 +211,37 @@ impl ExecutorProxyTrait for MockExecutorProxy {
         Ok(())
     }
 
+    fn verify_chunk(
+        &self,
+        txn_list_with_proof: &TransactionListWithProof,
+        _verified_target_li: &LedgerInfoWithSignatures,
+        first_version: Version,
+    ) -> Result<()> {
+        // the mock proofs are empty, only the checks done before executing a chunk are mirrored
+        ensure!(
+            txn_list_with_proof.first_transaction_version == Some(first_version),
+            "chunk starts at version {:?} instead of {}",
+            txn_list_with_proof.first_transaction_version,
+            first_version
+        );
+        if let Some(max_chunk_size) = self.max_chunk_size {
+            ensure!(
+                txn_list_with_proof.len() <= max_chunk_size,
+                "chunk of {} transactions is too large to execute",
+                txn_list_with_proof.len()
+            );
+        }
+        Ok(())
+    }
+
     fn get_chunk(
         &self,
         known_version: u64,
         limit: u64,
         target_version: u64,
     ) -> Result<TransactionListWithProof> {
-        let txns = self
-            .storage
-            .read()
-            .unwrap()
-            .get_chunk(known_version + 1, limit, target_version);
-        let first_txn_version = txns.first().map(|_| known_version + 1);
-        let txns_with_proof = TransactionListWithProof::new(
-            txns,
-            None,
-            first_txn_version,
-            TransactionListProof::new_empty(),
-        );
-        (self.handler)(txns_with_proof)
+        self.chunk_reader
+            .get_chunk(known_version, limit, target_version)
     }
 
     fn get_epoch_proof(&self, epoch: u64) -> Result<LedgerInfoWithSignatures> {
@@ -159,7 +259,41 @@ impl ExecutorProxyTrait for MockExecutorProxy {
         Ok(())
     }
 
-    fn publish_on_chain_config_updates(&mut self, _events: Vec<ContractEvent>) -> Result<()> {
+    fn publish_on_chain_config_updates(&mut self, events: Vec<ContractEvent>) -> Result<()> {
+        if let Some(published_reconfig_events) = &self.published_reconfig_events {
+            published_reconfig_events.write().unwrap().extend(events);
+        }
+        Ok(())
+    }
+
+    fn save_checkpoint(&mut self, checkpoint: &SyncCheckpoint) -> Result<()> {
+        self.storage
+            .write()
+            .unwrap()
+            .save_checkpoint(lcs::to_bytes(checkpoint)?);
         Ok(())
     }
+
+    fn load_checkpoint(&self) -> Result<Option<SyncCheckpoint>> {
+        match self.storage.read().unwrap().checkpoint() {
+            Some(bytes) => Ok(Some(lcs::from_bytes(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_reconfig_journal(&mut self, events: &[ContractEvent]) -> Result<()> {
+        self.storage
+            .write()
+            .unwrap()
+            .save_reconfig_journal(events.to_vec());
+        Ok(())
+    }
+
+    fn load_reconfig_journal(&self) -> Result<Vec<ContractEvent>> {
+        Ok(self.storage.read().unwrap().reconfig_journal())
+    }
+
+    fn chunk_reader(&self) -> Arc<dyn ChunkReader> {
+        self.chunk_reader.clone()
+    }
 }
@@ -2,12 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    network::{StateSynchronizerEvents, StateSynchronizerSender},
+    network::{
+        StateSynchronizerEvents, StateSynchronizerMsg, StateSynchronizerSender, SyncCapabilities,
+    },
     tests::{
         helpers::{MockExecutorProxy, MockRpcHandler, SynchronizerEnvHelper},
         mock_storage::MockStorage,
     },
-    StateSyncClient, StateSynchronizer,
+    InitializationResult, StateSyncClient, StateSynchronizer,
 };
 use anyhow::{bail, Result};
 use channel::{libra_channel, message_queues::QueueStyle};
@@ -66,6 +68,7 @@ struct SynchronizerEnv {
         HashMap<PeerId, libra_channel::Sender<(PeerId, ProtocolId), PeerManagerNotification>>,
     network_conn_event_notifs_txs: HashMap<PeerId, conn_notifs_channel::Sender>,
     multi_peer_ids: Vec<Vec<PeerId>>, // maps peer's synchronizer env index to that peer's PeerIds, to support node with multiple network IDs
+    advertise_capabilities: bool,     // applies to the synchronizers started afterwards
 }
 
 impl SynchronizerEnv {
@@ -117,6 +120,7 @@ impl SynchronizerEnv {
             network_notifs_txs: HashMap::new(),
             network_conn_event_notifs_txs: HashMap::new(),
             multi_peer_ids: vec![],
+            advertise_capabilities: false,
         }
     }
 
@@ -155,7 +159,8 @@ impl SynchronizerEnv {
         let mut config = libra_config::config::NodeConfig::default_for_validator();
         config.base.role = role;
         config.state_sync.sync_request_timeout_ms = timeout_ms;
-        config.state_sync.multicast_timeout_ms = multicast_timeout_ms;
+        config.state_sync.multicast_timeout_ms = Some(multicast_timeout_ms);
+        config.state_sync.advertise_capabilities = self.advertise_capabilities;
 
         let network = config.validator_network.unwrap();
         let network_id = if role.is_validator() {
@@ -281,6 +286,9 @@ impl SynchronizerEnv {
             &config.state_sync,
             config.upstream,
             MockExecutorProxy::new(handler, storage_proxy.clone()),
+            None,
+            None,
+            None,
         );
         self.mempools
             .push(MockSharedMempool::new(Some(mempool_requests)));
@@ -358,7 +366,7 @@ impl SynchronizerEnv {
         false
     }
 
-    fn wait_until_initialized(&self, peer_id: usize) -> Result<()> {
+    fn wait_until_initialized(&self, peer_id: usize) -> Result<InitializationResult> {
         block_on(self.synchronizers[peer_id].wait_until_initialized())
     }
 
@@ -379,12 +387,22 @@ impl SynchronizerEnv {
     /// Delivers next message from peer with index `sender` in this SynchronizerEnv
     /// Returns the recipient of the msg
     fn deliver_msg(&mut self, sender: (usize, usize)) -> PeerId {
+        self.deliver_and_check_msg(sender, |_| {})
+    }
+
+    /// Same as `deliver_msg`, with `check` called on the delivered msg
+    fn deliver_and_check_msg<F: FnOnce(StateSynchronizerMsg)>(
+        &mut self,
+        sender: (usize, usize),
+        check: F,
+    ) -> PeerId {
         let sender_id = self.get_peer_network_id(sender);
         let network_reqs_rx = self.network_reqs_rxs.get_mut(&sender_id).unwrap();
         let network_req = block_on(network_reqs_rx.next()).unwrap();
 
         // await next message from node
         if let PeerManagerRequest::SendMessage(receiver_id, msg) = network_req {
+            check(lcs::from_bytes(&msg.mdata).unwrap());
             let receiver_network_notif_tx = self.network_notifs_txs.get_mut(&receiver_id).unwrap();
             receiver_network_notif_tx
                 .push(
@@ -495,6 +513,53 @@ fn test_basic_catch_up() {
     assert_eq!(env.latest_li(1).ledger_info().version(), 2000);
 }
 
+#[test]
+fn test_capabilities_exchange() {
+    let mut env = SynchronizerEnv::new(2);
+    env.advertise_capabilities = true;
+    for _ in 0..2 {
+        env.start_next_synchronizer(
+            SynchronizerEnv::default_handler(),
+            RoleType::Validator,
+            Waypoint::default(),
+            true,
+            None,
+        );
+    }
+    let (node_0, node_1) = ((0, 0), (1, 0));
+    env.send_peer_event(node_1, node_0, true, Inbound);
+    env.send_peer_event(node_0, node_1, true, Outbound);
+
+    // each node advertises its capabilities upon connection, which the other one receives
+    fn check_capabilities(msg: StateSynchronizerMsg) {
+        match msg {
+            StateSynchronizerMsg::Capabilities(capabilities) => {
+                assert_eq!(capabilities, SyncCapabilities::local())
+            }
+            msg => panic!("expected the capabilities, got {:?}", msg),
+        }
+    }
+    let recipient = env.deliver_and_check_msg(node_0, check_capabilities);
+    assert_eq!(recipient, env.get_peer_network_id(node_1));
+    let recipient = env.deliver_and_check_msg(node_1, check_capabilities);
+    assert_eq!(recipient, env.get_peer_network_id(node_0));
+    env.assert_no_message_sent(node_0);
+    env.assert_no_message_sent(node_1);
+
+    // the nodes keep syncing with each other once their capabilities are known
+    env.commit(0, 20);
+    let sync = env.clients[1].sync_to(env.latest_li(0));
+    let sync = std::thread::spawn(move || block_on(sync));
+    env.deliver_and_check_msg(node_1, |msg| {
+        assert!(matches!(msg, StateSynchronizerMsg::GetChunkRequest(_)))
+    });
+    env.deliver_and_check_msg(node_0, |msg| {
+        assert!(matches!(msg, StateSynchronizerMsg::GetChunkResponse(_)))
+    });
+    sync.join().unwrap().unwrap();
+    assert_eq!(env.latest_li(1).ledger_info().version(), 20);
+}
+
 #[test]
 fn test_flaky_peer_sync() {
     // create handler that causes error, but has successful retries
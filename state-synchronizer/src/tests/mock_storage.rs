@@ -4,7 +4,7 @@
 use crate::SynchronizerState;
 use anyhow::{bail, Result};
 use executor_types::ExecutedTrees;
-use libra_crypto::HashValue;
+use libra_crypto::{hash::CryptoHash, HashValue};
 #[cfg(test)]
 use libra_types::{
     account_address::AccountAddress,
@@ -16,8 +16,8 @@ use libra_types::{
     transaction::{authenticator::AuthenticationKey, SignedTransaction},
 };
 use libra_types::{
-    epoch_state::EpochState, ledger_info::LedgerInfoWithSignatures, transaction::Transaction,
-    validator_signer::ValidatorSigner,
+    contract_event::ContractEvent, epoch_state::EpochState, ledger_info::LedgerInfoWithSignatures,
+    transaction::Transaction, validator_signer::ValidatorSigner,
 };
 #[cfg(test)]
 use std::collections::BTreeMap;
@@ -42,6 +42,10 @@ pub struct MockStorage {
     signer: ValidatorSigner,
     // A validator verifier of the latest epoch
     epoch_state: EpochState,
+    // serialized coordinator checkpoint
+    checkpoint: Option<Vec<u8>>,
+    // reconfiguration events journaled until they are published
+    reconfig_journal: Vec<ContractEvent>,
 }
 
 impl MockStorage {
@@ -57,20 +61,35 @@ impl MockStorage {
             epoch_num,
             signer,
             epoch_state,
+            checkpoint: None,
+            reconfig_journal: vec![],
         }
     }
 
     fn add_txns(&mut self, txns: &mut Vec<Transaction>) {
+        let mut leaves: Vec<_> = txns.iter().map(CryptoHash::hash).collect();
+        let accumulator = self.synced_trees.txn_accumulator();
+        if accumulator.num_leaves() == 0 {
+            // placeholder leaf of the genesis transaction
+            leaves.insert(0, HashValue::zero());
+        }
+        let accumulator = accumulator.append(&leaves);
         self.transactions.append(txns);
-        let num_leaves = self.transactions.len() + 1;
-        let frozen_subtree_roots = vec![HashValue::zero(); num_leaves.count_ones() as usize];
         self.synced_trees = ExecutedTrees::new(
             HashValue::zero(), /* dummy_state_root */
-            frozen_subtree_roots,
-            num_leaves as u64,
+            accumulator.frozen_subtree_roots().clone(),
+            accumulator.num_leaves(),
         );
     }
 
+    /// The leaves of the transaction accumulator: the placeholder of the genesis transaction
+    /// followed by the hashes of the mock transactions.
+    pub fn txn_accumulator_leaves(&self) -> Vec<HashValue> {
+        std::iter::once(HashValue::zero())
+            .chain(self.transactions.iter().map(CryptoHash::hash))
+            .collect()
+    }
+
     pub fn version(&self) -> u64 {
         self.transactions.len() as u64
     }
@@ -240,6 +259,22 @@ impl MockStorage {
             .clone();
     }
 
+    pub fn save_checkpoint(&mut self, checkpoint: Vec<u8>) {
+        self.checkpoint = Some(checkpoint);
+    }
+
+    pub fn checkpoint(&self) -> Option<Vec<u8>> {
+        self.checkpoint.clone()
+    }
+
+    pub fn save_reconfig_journal(&mut self, events: Vec<ContractEvent>) {
+        self.reconfig_journal = events;
+    }
+
+    pub fn reconfig_journal(&self) -> Vec<ContractEvent> {
+        self.reconfig_journal.clone()
+    }
+
     // Find LedgerInfo for an epoch boundary version.
     pub fn get_epoch_ending_ledger_info(&self, version: u64) -> Result<LedgerInfoWithSignatures> {
         for li in self.ledger_infos.values() {
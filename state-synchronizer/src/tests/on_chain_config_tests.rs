@@ -43,7 +43,7 @@ fn test_on_chain_config_pub_sub() {
 
     let mut block_executor = Box::new(Executor::<LibraVM>::new(db_rw.clone()));
     let chunk_executor = Box::new(Executor::<LibraVM>::new(db_rw));
-    let mut executor_proxy = ExecutorProxy::new(db, chunk_executor, vec![subscription]);
+    let mut executor_proxy = ExecutorProxy::new(db, chunk_executor, vec![subscription], None, None);
 
     assert!(
         reconfig_receiver
@@ -1,10 +1,63 @@
 // Copyright (c) The Libra Core Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::request_manager::{PeerScoreUpdateType, RequestManager};
-use libra_config::config::{PeerNetworkId, UpstreamConfig};
+use crate::{
+    chunk_request::{GetChunkRequest, TargetType},
+    chunk_response::{GetChunkResponse, ResponseLedgerInfo},
+    coordinator::{
+        sync_duration_label, CoordinatorMessage, EpochChangeNotification, InitializationResult,
+        PendingLedgerInfos, ServingPolicy, SyncCoordinator, SyncPhase, SyncPhaseChange,
+        SyncRequest, SyncRequestError, SyncRequestOutcome, SUBSCRIPTION_MEMORY_ESTIMATE,
+    },
+    counters,
+    executor_proxy::ExecutorProxyTrait,
+    network::{StateSynchronizerMsg, StateSynchronizerSender, SyncCapabilities},
+    request_manager::{NetworkHealth, PeerScoreUpdateType, RequestManager},
+    tests::{
+        helpers::{MockExecutorProxy, MockRpcHandler, SynchronizerEnvHelper},
+        mock_storage::MockStorage,
+    },
+};
+use channel::{libra_channel, message_queues::QueueStyle};
+use futures::{
+    channel::{mpsc, oneshot},
+    future, stream, FutureExt, SinkExt, StreamExt,
+};
+use libra_config::{
+    config::{PeerNetworkId, RoleType, StateSyncConfig, UpstreamConfig},
+    network_id::{NetworkId, NodeNetworkId},
+};
+use libra_crypto::{hash::TransactionAccumulatorHasher, HashValue};
+use libra_mempool::{CommitNotification, CommitResponse, CommitSource};
+use libra_types::{
+    account_address::AccountAddress,
+    account_config::coin1_tmp_tag,
+    contract_event::ContractEvent,
+    ledger_info::LedgerInfoWithSignatures,
+    on_chain_config::{new_epoch_event_key, ValidatorSet},
+    proof::{
+        accumulator::InMemoryAccumulator, TransactionAccumulatorRangeProof, TransactionListProof,
+    },
+    transaction::{Transaction, TransactionListWithProof, Version},
+    waypoint::Waypoint,
+    PeerId,
+};
 use netcore::transport::ConnectionOrigin;
-use std::{collections::HashMap, time::Duration};
+use network::{
+    peer_manager::{ConnectionRequestSender, PeerManagerRequest, PeerManagerRequestSender},
+    protocols::network::{Event, NewNetworkSender},
+    ProtocolId,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{runtime::Runtime, time::timeout};
 
 #[test]
 fn test_request_manager() {
@@ -17,6 +70,7 @@ fn test_request_manager() {
     let mut request_manager = RequestManager::new(
         UpstreamConfig::default(),
         Duration::from_secs(10),
+        Duration::from_secs(0),
         Duration::from_secs(30),
         HashMap::new(),
     );
@@ -51,6 +105,7 @@ fn test_remove_requests() {
     let mut request_manager = RequestManager::new(
         UpstreamConfig::default(),
         Duration::from_secs(0),
+        Duration::from_secs(0),
         Duration::from_secs(30),
         HashMap::new(),
     );
@@ -82,6 +137,7 @@ fn test_request_manager_request_metadata() {
     let mut request_manager = RequestManager::new(
         UpstreamConfig::default(),
         Duration::from_secs(0),
+        Duration::from_secs(0),
         Duration::from_secs(30),
         HashMap::new(),
     );
@@ -100,3 +156,3933 @@ fn test_request_manager_request_metadata() {
             <= request_manager.get_last_request_time(1).unwrap()
     );
 }
+
+#[test]
+fn test_request_manager_network_mismatch() {
+    let validator_peer = PeerNetworkId::random_validator();
+    let public_peer = PeerNetworkId(NodeNetworkId::new(NetworkId::Public, 0), PeerId::random());
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+
+    // no outstanding request: nothing to mismatch against
+    assert!(!request_manager.is_network_mismatch(1, &public_peer));
+
+    request_manager.add_request(1, vec![validator_peer.clone()]);
+    assert!(!request_manager.is_network_mismatch(1, &validator_peer));
+    assert!(!request_manager.is_network_mismatch(1, &PeerNetworkId::random_validator()));
+    assert!(request_manager.is_network_mismatch(1, &public_peer));
+
+    // a retry on another network keeps responses to earlier attempts acceptable
+    request_manager.add_request(1, vec![public_peer.clone()]);
+    assert!(!request_manager.is_network_mismatch(1, &validator_peer));
+    assert!(!request_manager.is_network_mismatch(1, &public_peer));
+}
+
+#[test]
+fn test_request_manager_reset_score() {
+    let peer = PeerNetworkId::random_validator();
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+
+    // unknown peers are rejected
+    assert!(request_manager.reset_score(&peer).is_err());
+
+    request_manager.enable_peer(peer.clone(), ConnectionOrigin::Outbound);
+    for _ in 0..10 {
+        request_manager.update_score(&peer, PeerScoreUpdateType::InvalidChunk);
+    }
+    assert!(request_manager.peer_score(&peer).unwrap() < 100.0);
+
+    request_manager.reset_score(&peer).unwrap();
+    assert!((request_manager.peer_score(&peer).unwrap() - 100.0).abs() < std::f64::EPSILON);
+}
+
+#[test]
+fn test_request_manager_force_multicast() {
+    let upstream_config = UpstreamConfig {
+        networks: vec![NetworkId::Public, NetworkId::Private("vfn".into())],
+    };
+    let public_peer = PeerNetworkId(NodeNetworkId::new(NetworkId::Public, 0), PeerId::random());
+    let private_peer = PeerNetworkId(
+        NodeNetworkId::new(NetworkId::Private("vfn".into()), 0),
+        PeerId::random(),
+    );
+    let mut request_manager = RequestManager::new(
+        upstream_config,
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+    request_manager.enable_peer(public_peer.clone(), ConnectionOrigin::Outbound);
+    request_manager.enable_peer(private_peer, ConnectionOrigin::Outbound);
+    assert_eq!(request_manager.pick_peers(), vec![public_peer.clone()]);
+
+    request_manager.add_request(1, vec![public_peer]);
+    let multicast_start_time = request_manager.get_multicast_start_time(1).unwrap();
+
+    // all the upstream networks are picked right away, with the multicast timer restarted
+    request_manager.force_multicast(1);
+    assert_eq!(request_manager.pick_peers().len(), 2);
+    assert!(request_manager.get_multicast_start_time(1).unwrap() >= multicast_start_time);
+}
+
+#[test]
+fn test_request_manager_peer_reliability() {
+    let peer = PeerNetworkId::random_validator();
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+    request_manager.enable_peer(peer.clone(), ConnectionOrigin::Outbound);
+    assert!(request_manager.peer_reliability().is_empty());
+
+    request_manager.add_request(1, vec![peer.clone()]);
+    request_manager.record_response_time(&peer, 1);
+    // responses to requests that were not sent to the peer are not timed
+    request_manager.record_response_time(&peer, 2);
+    request_manager.update_score(&peer, PeerScoreUpdateType::Success);
+    request_manager.update_score(&peer, PeerScoreUpdateType::Success);
+    request_manager.update_score(&peer, PeerScoreUpdateType::EmptyChunk);
+    request_manager.update_score(&peer, PeerScoreUpdateType::InvalidChunk);
+    request_manager.update_score(&peer, PeerScoreUpdateType::TimeOut);
+    request_manager.update_score(&peer, PeerScoreUpdateType::ChunkVersionCannotBeApplied);
+
+    let reliability = request_manager.peer_reliability().remove(&peer).unwrap();
+    assert_eq!(reliability.successes, 2);
+    assert_eq!(reliability.failures, 2);
+    assert_eq!(reliability.empty_chunks, 1);
+    assert_eq!(reliability.invalid_chunks, 1);
+    assert!(reliability.mean_response_time.is_some());
+}
+
+#[test]
+fn test_request_manager_duplicate_enable() {
+    let peer = PeerNetworkId::random_validator();
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+    assert!(request_manager.enable_peer(peer.clone(), ConnectionOrigin::Outbound));
+    request_manager.update_score(&peer, PeerScoreUpdateType::InvalidChunk);
+    let score = request_manager.peer_score(&peer).unwrap();
+    assert!(score < 100.0);
+
+    // enabling an already active peer again doesn't reset its score
+    assert!(!request_manager.enable_peer(peer.clone(), ConnectionOrigin::Inbound));
+    assert!((request_manager.peer_score(&peer).unwrap() - score).abs() < std::f64::EPSILON);
+    assert_eq!(request_manager.pick_peers(), vec![peer]);
+}
+
+#[test]
+fn test_request_manager_retry_backoff() {
+    let peer = PeerNetworkId::random_validator();
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(60),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+    request_manager.enable_peer(peer.clone(), ConnectionOrigin::Outbound);
+    request_manager.add_request(1, vec![peer.clone()]);
+    assert_eq!(
+        request_manager.get_request_timeout(1),
+        Duration::from_secs(10)
+    );
+    assert!(!request_manager.check_timeout(1));
+
+    // every consecutive timeout doubles the retry timeout, up to the ceiling
+    for &expected_secs in &[20, 40, 60] {
+        request_manager.expedite_retry(1);
+        assert!(request_manager.check_timeout(1));
+        request_manager.add_request(1, vec![peer.clone()]);
+        assert_eq!(
+            request_manager.get_request_timeout(1),
+            Duration::from_secs(expected_secs)
+        );
+        assert!(!request_manager.check_timeout(1));
+    }
+
+    // once a chunk is applied, the request for the next version starts from the base timeout
+    request_manager.process_success_response(&peer);
+    request_manager.add_request(11, vec![peer]);
+    request_manager.remove_requests(11);
+    assert_eq!(
+        request_manager.get_request_timeout(11),
+        Duration::from_secs(10)
+    );
+}
+
+#[test]
+fn test_request_manager_network_health() {
+    let vfn_network = NodeNetworkId::new(NetworkId::Private("vfn".into()), 0);
+    let public_network = NodeNetworkId::new(NetworkId::Public, 0);
+    let upstream_config = UpstreamConfig {
+        networks: vec![vfn_network.network_id(), public_network.network_id()],
+    };
+    let network_senders = vec![vfn_network.clone(), public_network.clone()]
+        .into_iter()
+        .map(|network_id| {
+            let (network_reqs_tx, _) =
+                libra_channel::new(QueueStyle::FIFO, NonZeroUsize::new(8).unwrap(), None);
+            let (connection_reqs_tx, _) =
+                libra_channel::new(QueueStyle::FIFO, NonZeroUsize::new(8).unwrap(), None);
+            let sender = StateSynchronizerSender::new(
+                PeerManagerRequestSender::new(network_reqs_tx),
+                ConnectionRequestSender::new(connection_reqs_tx),
+            );
+            (network_id, sender)
+        })
+        .collect();
+    let mut request_manager = RequestManager::new(
+        upstream_config,
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        network_senders,
+    );
+    let vfn_peer = PeerNetworkId(vfn_network.clone(), PeerId::random());
+    request_manager.enable_peer(vfn_peer.clone(), ConnectionOrigin::Outbound);
+    request_manager.process_success_response(&vfn_peer);
+
+    // the public network has no peers: its health is reported even though syncing progresses
+    // through the vfn network
+    let health = request_manager.network_health();
+    assert_eq!(health.len(), 2);
+    assert_eq!(
+        health[&vfn_network],
+        NetworkHealth {
+            available_peers: 1,
+            secs_since_last_success: Some(0),
+        }
+    );
+    assert_eq!(health[&public_network], NetworkHealth::default());
+}
+
+#[test]
+fn test_availability_score_threshold() {
+    let peers = vec![
+        PeerNetworkId::random_validator(),
+        PeerNetworkId::random_validator(),
+    ];
+    let mut request_manager = RequestManager::new(
+        UpstreamConfig::default(),
+        Duration::from_secs(10),
+        Duration::from_secs(0),
+        Duration::from_secs(30),
+        HashMap::new(),
+    );
+    for peer in peers.iter() {
+        request_manager.enable_peer(peer.clone(), ConnectionOrigin::Outbound);
+    }
+    // brings the score of the first peer down to ~3.5
+    for _ in 0..15 {
+        request_manager.update_score(&peers[0], PeerScoreUpdateType::InvalidChunk);
+    }
+    let picked = |request_manager: &RequestManager| {
+        (0..1000)
+            .map(|_| request_manager.pick_peers()[0].clone())
+            .collect::<HashSet<_>>()
+    };
+
+    // off by default: every peer can be picked, weighted by its score
+    assert_eq!(picked(&request_manager).len(), 2);
+
+    // the peer below the threshold is left out while the other one is above it
+    request_manager.set_availability_score_threshold(10.0);
+    assert_eq!(
+        picked(&request_manager),
+        vec![peers[1].clone()].into_iter().collect()
+    );
+    assert_eq!(
+        request_manager.network_health()[&peers[1].network_id()].available_peers,
+        1
+    );
+
+    // with no peer above the threshold, all of them are picked from again
+    for _ in 0..15 {
+        request_manager.update_score(&peers[1], PeerScoreUpdateType::InvalidChunk);
+    }
+    assert_eq!(picked(&request_manager).len(), 2);
+}
+
+#[test]
+fn test_pending_ledger_infos_from_genesis() {
+    let (signers, validator_info, _keys, _addrs) = SynchronizerEnvHelper::initial_setup(1);
+    let genesis_li = SynchronizerEnvHelper::genesis_li(&validator_info);
+    let mut storage = MockStorage::new(genesis_li.clone(), signers[0].clone());
+    let genesis_state = storage.get_local_storage_state();
+    assert_eq!(genesis_state.highest_version_in_local_storage(), 0);
+    storage.commit_new_txns(10);
+    let li = storage.highest_local_li();
+
+    // without a target yet, any LI is queued, and the committed genesis LI is pruned right away
+    let mut pending_lis = PendingLedgerInfos::new(1000, 0);
+    pending_lis.add_li(genesis_li);
+    pending_lis.add_li(li);
+    pending_lis.update(&genesis_state, 250);
+    let versions: Vec<_> = pending_lis
+        .ledger_infos()
+        .iter()
+        .map(|li| li.ledger_info().version())
+        .collect();
+    assert_eq!(versions, vec![10]);
+    assert_eq!(pending_lis.target_li().unwrap().ledger_info().version(), 10);
+}
+
+#[test]
+fn test_pending_ledger_infos_count_cap() {
+    let (signers, validator_info, _keys, _addrs) = SynchronizerEnvHelper::initial_setup(1);
+    let mut storage = MockStorage::new(
+        SynchronizerEnvHelper::genesis_li(&validator_info),
+        signers[0].clone(),
+    );
+    let mut lis = vec![];
+    for _ in 0..4 {
+        storage.commit_new_txns(10);
+        lis.push(storage.highest_local_li());
+    }
+    let mut pending_lis = PendingLedgerInfos::new(2, 0);
+    let versions = |pending_lis: &PendingLedgerInfos| -> Vec<Version> {
+        pending_lis
+            .ledger_infos()
+            .iter()
+            .map(|li| li.ledger_info().version())
+            .collect()
+    };
+    pending_lis.add_li(lis[1].clone());
+    pending_lis.add_li(lis[2].clone());
+    assert_eq!(versions(&pending_lis), vec![20, 30]);
+
+    // at capacity, an LI higher than the lowest queued one evicts it
+    pending_lis.add_li(lis[3].clone());
+    assert_eq!(versions(&pending_lis), vec![30, 40]);
+
+    // at capacity, an LI lower than everything queued is dropped
+    pending_lis.add_li(lis[0].clone());
+    assert_eq!(versions(&pending_lis), vec![30, 40]);
+}
+
+// Returns the LI of `storage` padded with `num_signatures` extra (bogus) signatures
+fn padded_li(storage: &MockStorage, num_signatures: usize) -> LedgerInfoWithSignatures {
+    let mut li = storage.highest_local_li();
+    let signature = li.signatures().values().next().unwrap().clone();
+    for _ in 0..num_signatures {
+        li.add_signature(AccountAddress::random(), signature.clone());
+    }
+    li
+}
+
+#[test]
+fn test_pending_ledger_infos_byte_cap() {
+    let (signers, validator_info, _keys, _addrs) = SynchronizerEnvHelper::initial_setup(1);
+    let mut storage = MockStorage::new(
+        SynchronizerEnvHelper::genesis_li(&validator_info),
+        signers[0].clone(),
+    );
+    let mut lis = vec![];
+    for _ in 0..4 {
+        storage.commit_new_txns(10);
+        lis.push(padded_li(&storage, 20));
+    }
+    storage.commit_new_txns(10);
+    let oversized_li = padded_li(&storage, 100);
+
+    // room for two and a half LIs, far below the count limit
+    let li_bytes = lcs::to_bytes(&lis[0]).unwrap().len();
+    let mut pending_lis = PendingLedgerInfos::new(1000, 2 * li_bytes + li_bytes / 2);
+    let versions = |pending_lis: &PendingLedgerInfos| -> Vec<Version> {
+        pending_lis
+            .ledger_infos()
+            .iter()
+            .map(|li| li.ledger_info().version())
+            .collect()
+    };
+
+    // the lowest versions are evicted to make room for higher ones
+    for li in lis.iter().take(3) {
+        pending_lis.add_li(li.clone());
+    }
+    assert_eq!(versions(&pending_lis), vec![20, 30]);
+    pending_lis.add_li(lis[3].clone());
+    assert_eq!(versions(&pending_lis), vec![30, 40]);
+
+    // LIs lower than everything queued or too large to ever fit are dropped
+    pending_lis.add_li(lis[0].clone());
+    assert_eq!(versions(&pending_lis), vec![30, 40]);
+    assert!(lcs::to_bytes(&oversized_li).unwrap().len() > 2 * li_bytes + li_bytes / 2);
+    pending_lis.add_li(oversized_li);
+    assert_eq!(versions(&pending_lis), vec![30, 40]);
+}
+
+type NetworkRequests = libra_channel::Receiver<(PeerId, ProtocolId), PeerManagerRequest>;
+
+// Returns a storage holding the genesis LI of a single validator
+fn genesis_storage() -> Arc<RwLock<MockStorage>> {
+    let (signers, validator_info, _keys, _addrs) = SynchronizerEnvHelper::initial_setup(1);
+    let genesis_li = SynchronizerEnvHelper::genesis_li(&validator_info);
+    Arc::new(RwLock::new(MockStorage::new(
+        genesis_li,
+        signers[0].clone(),
+    )))
+}
+
+// Commits `num_txns` transactions, the last of which ends the current epoch
+fn commit_epoch(storage: &Arc<RwLock<MockStorage>>, num_txns: u64) {
+    let (signers, validator_info, _keys, _addrs) = SynchronizerEnvHelper::initial_setup(1);
+    let mut storage = storage.write().unwrap();
+    storage.commit_new_txns(num_txns);
+    storage.move_to_next_epoch(signers[0].clone(), ValidatorSet::new(validator_info));
+}
+
+// Returns a coordinator on top of `storage` that is connected to a single mock network, along
+// with the ID of that network, the receiver of the messages sent to it and the receiver of the
+// commit notifications sent to mempool (which are never ACKed unless done so by the test)
+fn coordinator(
+    role: RoleType,
+    config: StateSyncConfig,
+    waypoint: Waypoint,
+    storage: Arc<RwLock<MockStorage>>,
+) -> (
+    SyncCoordinator<MockExecutorProxy>,
+    NodeNetworkId,
+    NetworkRequests,
+    mpsc::Receiver<CommitNotification>,
+) {
+    coordinator_with_handler(
+        role,
+        config,
+        waypoint,
+        storage,
+        SynchronizerEnvHelper::default_handler(),
+    )
+}
+
+// Same as `coordinator`, with the chunks read from `storage` passed through `handler`
+fn coordinator_with_handler(
+    role: RoleType,
+    config: StateSyncConfig,
+    waypoint: Waypoint,
+    storage: Arc<RwLock<MockStorage>>,
+    handler: MockRpcHandler,
+) -> (
+    SyncCoordinator<MockExecutorProxy>,
+    NodeNetworkId,
+    NetworkRequests,
+    mpsc::Receiver<CommitNotification>,
+) {
+    coordinator_with_proxy(
+        role,
+        config,
+        waypoint,
+        MockExecutorProxy::new(handler, storage),
+        None,
+    )
+}
+
+// Same as `coordinator`, on top of the given executor proxy
+fn coordinator_with_proxy(
+    role: RoleType,
+    config: StateSyncConfig,
+    waypoint: Waypoint,
+    executor_proxy: MockExecutorProxy,
+    epoch_change_sender: Option<mpsc::Sender<EpochChangeNotification>>,
+) -> (
+    SyncCoordinator<MockExecutorProxy>,
+    NodeNetworkId,
+    NetworkRequests,
+    mpsc::Receiver<CommitNotification>,
+) {
+    let (coordinator, network_id, network_reqs_rx, mempool_receiver, _coordinator_sender) =
+        coordinator_with_client(role, config, waypoint, executor_proxy, epoch_change_sender);
+    (coordinator, network_id, network_reqs_rx, mempool_receiver)
+}
+
+// Same as `coordinator_with_proxy`, along with the sender of the client messages to the coordinator
+fn coordinator_with_client(
+    role: RoleType,
+    mut config: StateSyncConfig,
+    waypoint: Waypoint,
+    executor_proxy: MockExecutorProxy,
+    epoch_change_sender: Option<mpsc::Sender<EpochChangeNotification>>,
+) -> (
+    SyncCoordinator<MockExecutorProxy>,
+    NodeNetworkId,
+    NetworkRequests,
+    mpsc::Receiver<CommitNotification>,
+    mpsc::UnboundedSender<CoordinatorMessage>,
+) {
+    // mempool never ACKs the chunk commits of the tests: unless a test picks the commit pipeline
+    // depth, the ACKs are left pending instead of holding up every chunk until they time out
+    if config.max_commit_pipeline_depth.is_none() {
+        config.max_commit_pipeline_depth = Some(1_024);
+    }
+    let (coordinator_sender, coordinator_receiver) = mpsc::unbounded();
+    let (mempool_sender, mempool_receiver) = mpsc::channel(1_024);
+    let (network_reqs_tx, network_reqs_rx) =
+        libra_channel::new(QueueStyle::FIFO, NonZeroUsize::new(8).unwrap(), None);
+    let (connection_reqs_tx, _) =
+        libra_channel::new(QueueStyle::FIFO, NonZeroUsize::new(8).unwrap(), None);
+    let network_sender = StateSynchronizerSender::new(
+        PeerManagerRequestSender::new(network_reqs_tx),
+        ConnectionRequestSender::new(connection_reqs_tx),
+    );
+    let network_id = NodeNetworkId::new(NetworkId::Validator, 0);
+    let network_senders = vec![(network_id.clone(), network_sender)]
+        .into_iter()
+        .collect();
+    let initial_state = executor_proxy.get_local_storage_state().unwrap();
+    let coordinator = SyncCoordinator::new(
+        coordinator_receiver,
+        mempool_sender,
+        network_senders,
+        role,
+        waypoint,
+        config,
+        UpstreamConfig::default(),
+        executor_proxy,
+        initial_state,
+        epoch_change_sender,
+        None,
+    );
+    (
+        coordinator,
+        network_id,
+        network_reqs_rx,
+        mempool_receiver,
+        coordinator_sender,
+    )
+}
+
+// Returns the next message sent to the mock network (if any) along with its recipient
+fn next_msg(network_reqs_rx: &mut NetworkRequests) -> Option<(PeerId, StateSynchronizerMsg)> {
+    match network_reqs_rx.select_next_some().now_or_never()? {
+        PeerManagerRequest::SendMessage(peer_id, msg) => {
+            Some((peer_id, lcs::from_bytes(&msg.mdata).unwrap()))
+        }
+        _ => panic!("unexpected network request"),
+    }
+}
+
+fn chunk_response(
+    txns: Vec<Transaction>,
+    first_version: u64,
+    li: LedgerInfoWithSignatures,
+) -> StateSynchronizerMsg {
+    let txn_list_with_proof = TransactionListWithProof::new(
+        txns,
+        None,
+        Some(first_version),
+        TransactionListProof::new_empty(),
+    );
+    StateSynchronizerMsg::GetChunkResponse(Box::new(GetChunkResponse::new(
+        ResponseLedgerInfo::VerifiableLedgerInfo(li),
+        txn_list_with_proof,
+    )))
+}
+
+fn highest_available_request(known_version: u64, timeout_ms: u64) -> StateSynchronizerMsg {
+    StateSynchronizerMsg::GetChunkRequest(Box::new(GetChunkRequest::new(
+        known_version,
+        1,
+        250,
+        TargetType::HighestAvailable {
+            target_li: None,
+            timeout_ms,
+        },
+    )))
+}
+
+#[test]
+fn test_unavailable_response() {
+    let storage = genesis_storage();
+    for _ in 0..2 {
+        commit_epoch(&storage, 10);
+    }
+    let mut config = StateSyncConfig::default();
+    config.max_epoch_lookups_per_sec = 2;
+
+    // a request the node can't serve right now is explicitly answered so to the peers that
+    // support it, the other peers are sent an empty chunk
+    for &advertised in &[false, true] {
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config.clone(),
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+        if advertised {
+            rt.block_on(coordinator.process_one_message(
+                peer.clone(),
+                StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+            ));
+        }
+        // the second request in the same second exceeds the epoch lookup budget
+        let mut response = None;
+        for _ in 0..2 {
+            let request = GetChunkRequest::new(0, 1, 250, TargetType::Waypoint(20));
+            rt.block_on(coordinator.process_one_message(
+                peer.clone(),
+                StateSynchronizerMsg::GetChunkRequest(Box::new(request)),
+            ));
+            response = next_msg(&mut network_reqs_rx);
+        }
+        match response {
+            Some((_, StateSynchronizerMsg::GetChunkResponse(response))) => {
+                assert!(response.txn_list_with_proof.transactions.is_empty());
+                match response.response_li {
+                    ResponseLedgerInfo::UnavailableLedgerInfo(_) => assert!(advertised),
+                    ResponseLedgerInfo::VerifiableLedgerInfo(_) => assert!(!advertised),
+                    response_li => panic!("unexpected response LI {:?}", response_li),
+                }
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+    }
+
+    // the requester retries right away without penalizing the peer, while a plain empty chunk
+    // is penalized and waits for the request to time out
+    let li = storage.read().unwrap().highest_local_li();
+    for &unavailable in &[false, true] {
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            genesis_storage(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        assert!(next_msg(&mut network_reqs_rx).is_some());
+
+        let response_li = if unavailable {
+            ResponseLedgerInfo::UnavailableLedgerInfo(li.clone())
+        } else {
+            ResponseLedgerInfo::VerifiableLedgerInfo(li.clone())
+        };
+        let response = GetChunkResponse::new(response_li, TransactionListWithProof::new_empty());
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(coordinator.process_one_message(
+            peer.clone(),
+            StateSynchronizerMsg::GetChunkResponse(Box::new(response)),
+        ));
+
+        // the next progress check
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        assert_eq!(next_msg(&mut network_reqs_rx).is_some(), unavailable);
+        assert_eq!(coordinator.peer_score(&peer).unwrap() < 100.0, !unavailable);
+    }
+}
+
+#[test]
+fn test_subscription_served_with_freshest_li() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+
+    // there is nothing new for the peer yet, so its request becomes a subscription
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), highest_available_request(10, 10_000)),
+    );
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the local LI advances twice before the subscription is checked: the subscriber must be
+    // served relative to the latest LI
+    storage.write().unwrap().commit_new_txns(10);
+    storage.write().unwrap().commit_new_txns(30);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+
+    let (recipient, msg) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(recipient, peer.peer_id());
+    match msg {
+        StateSynchronizerMsg::GetChunkResponse(response) => {
+            assert_eq!(response.response_li.version(), 50);
+            assert_eq!(
+                response.txn_list_with_proof.first_transaction_version,
+                Some(11)
+            );
+            assert_eq!(response.txn_list_with_proof.len(), 40);
+        }
+        _ => panic!("expected a chunk response"),
+    }
+}
+
+#[test]
+fn test_commit_pipeline_cap_with_slow_mempool() {
+    let upstream_storage = genesis_storage();
+    let (first_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let first_li = upstream_storage.read().unwrap().highest_local_li();
+    let (second_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let second_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.max_commit_pipeline_depth = Some(1);
+    let (mut coordinator, network_id, _network_reqs_rx, mut mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the first chunk is applied right away and its commit awaits the mempool ACK
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), chunk_response(first_txns, 1, first_li)),
+    );
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert_eq!(coordinator.commit_pipeline_depth(), 1);
+
+    // the pipeline is full: the next chunk is not applied for as long as mempool does not ACK
+    let second_chunk = chunk_response(second_txns, 11, second_li);
+    let pending = rt.block_on(async {
+        timeout(
+            Duration::from_millis(200),
+            coordinator.process_one_message(peer.clone(), second_chunk.clone()),
+        )
+        .await
+    });
+    assert!(pending.is_err());
+    assert_eq!(storage.read().unwrap().version(), 10);
+
+    // once mempool catches up the next chunk gets applied
+    let notification = mempool_requests.try_next().unwrap().unwrap();
+    assert_eq!(notification.source, CommitSource::StateSync);
+    notification
+        .callback
+        .send(Ok(CommitResponse { msg: "".into() }))
+        .unwrap();
+    rt.block_on(coordinator.process_one_message(peer, second_chunk));
+    assert_eq!(storage.read().unwrap().version(), 20);
+    assert_eq!(coordinator.commit_pipeline_depth(), 1);
+}
+
+#[test]
+fn test_commit_awaits_mempool_ack_without_pipeline() {
+    let upstream_storage = genesis_storage();
+    let (first_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let first_li = upstream_storage.read().unwrap().highest_local_li();
+    let (second_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let second_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.max_commit_pipeline_depth = Some(0);
+    let (mut coordinator, network_id, _network_reqs_rx, mut mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the chunk commit is only done once mempool ACKs it, nothing is left pending
+    rt.block_on(async {
+        let ack = async {
+            let notification = mempool_requests.next().await.unwrap();
+            assert_eq!(notification.source, CommitSource::StateSync);
+            notification
+                .callback
+                .send(Ok(CommitResponse { msg: "".into() }))
+                .unwrap();
+        };
+        future::join(
+            coordinator.process_one_message(peer.clone(), chunk_response(first_txns, 1, first_li)),
+            ack,
+        )
+        .await
+    });
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert_eq!(coordinator.commit_pipeline_depth(), 0);
+
+    // without an ACK the chunk commit holds up the coordinator
+    let pending = rt.block_on(async {
+        timeout(
+            Duration::from_millis(200),
+            coordinator.process_one_message(peer, chunk_response(second_txns, 11, second_li)),
+        )
+        .await
+    });
+    assert!(pending.is_err());
+    assert_eq!(storage.read().unwrap().version(), 20);
+    assert_eq!(coordinator.commit_pipeline_depth(), 0);
+}
+
+#[test]
+fn test_waypoint_response_with_epoch_proofs() {
+    let storage = genesis_storage();
+    for _ in 0..4 {
+        commit_epoch(&storage, 10);
+    }
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+
+    let request = GetChunkRequest::new(
+        0,
+        1,
+        250,
+        TargetType::WaypointWithEpochProofs {
+            version: 40,
+            max_epoch_proofs: 10,
+        },
+    );
+    rt.block_on(coordinator.process_one_message(
+        peer,
+        StateSynchronizerMsg::GetChunkRequest(Box::new(request)),
+    ));
+
+    // the epoch proofs cover the epochs between the chunk epoch and the waypoint epoch
+    let response = match next_msg(&mut network_reqs_rx).unwrap().1 {
+        StateSynchronizerMsg::GetChunkResponse(response) => response,
+        _ => panic!("expected a chunk response"),
+    };
+    assert_eq!(response.txn_list_with_proof.len(), 10);
+    match response.response_li {
+        ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+            waypoint_li,
+            end_of_epoch_li,
+            epoch_proofs,
+        } => {
+            assert_eq!(waypoint_li.ledger_info().epoch(), 4);
+            assert_eq!(end_of_epoch_li.unwrap().ledger_info().version(), 10);
+            let versions: Vec<_> = epoch_proofs
+                .iter()
+                .map(|li| li.ledger_info().version())
+                .collect();
+            assert_eq!(versions, vec![20, 30]);
+        }
+        _ => panic!("expected a waypoint response with epoch proofs"),
+    }
+}
+
+#[test]
+fn test_waypoint_sync_with_epoch_prefetch() {
+    let upstream_storage = genesis_storage();
+    for _ in 0..4 {
+        commit_epoch(&upstream_storage, 10);
+    }
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(40)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+    let (mut upstream, upstream_network_id, mut upstream_reqs_rx, _upstream_mempool_requests) =
+        coordinator(
+            RoleType::Validator,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            upstream_storage,
+        );
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.max_epoch_prefetch = Some(10);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, waypoint, storage.clone());
+
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let upstream_peer = PeerNetworkId(upstream_network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(
+        upstream_peer.clone(),
+        StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+    ));
+    coordinator.process_new_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+
+    // relay the messages between the two nodes until the waypoint is reached
+    let mut targets = vec![];
+    while storage.read().unwrap().version() < 40 {
+        assert!(targets.len() < 10, "waypoint sync did not converge");
+        let request = next_msg(&mut network_reqs_rx).unwrap().1;
+        if let StateSynchronizerMsg::GetChunkRequest(request) = &request {
+            targets.push(request.target().clone());
+        }
+        rt.block_on(upstream.process_one_message(peer.clone(), request));
+        let response = next_msg(&mut upstream_reqs_rx).unwrap().1;
+        rt.block_on(coordinator.process_one_message(upstream_peer.clone(), response));
+    }
+
+    // the epoch proofs are requested only once, along with the first chunk
+    assert_eq!(
+        targets,
+        vec![
+            TargetType::WaypointWithEpochProofs {
+                version: 40,
+                max_epoch_proofs: 10
+            },
+            TargetType::Waypoint(40),
+            TargetType::Waypoint(40),
+            TargetType::Waypoint(40),
+        ]
+    );
+    assert_eq!(storage.read().unwrap().epoch_num(), 5);
+}
+
+#[test]
+fn test_epoch_prefetch_requires_capability() {
+    let upstream_storage = genesis_storage();
+    for _ in 0..4 {
+        commit_epoch(&upstream_storage, 10);
+    }
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(40)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    for &advertised in &[false, true] {
+        let mut config = StateSyncConfig::default();
+        config.max_epoch_prefetch = Some(10);
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) =
+            coordinator(RoleType::Validator, config, waypoint, genesis_storage());
+        let upstream_peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+        if advertised {
+            rt.block_on(coordinator.process_one_message(
+                upstream_peer.clone(),
+                StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+            ));
+        }
+        coordinator.process_new_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+
+        // the epoch proofs are only requested from a peer that advertised support for them,
+        // the other peers are sent plain waypoint requests
+        let expected_target = if advertised {
+            TargetType::WaypointWithEpochProofs {
+                version: 40,
+                max_epoch_proofs: 10,
+            }
+        } else {
+            TargetType::Waypoint(40)
+        };
+        match next_msg(&mut network_reqs_rx) {
+            Some((peer_id, StateSynchronizerMsg::GetChunkRequest(request))) => {
+                assert_eq!(peer_id, upstream_peer.peer_id());
+                assert_eq!(request.target(), &expected_target);
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+
+        // the capabilities are forgotten with the connection
+        coordinator.process_lost_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+        assert_eq!(
+            coordinator.peer_capabilities(&upstream_peer),
+            SyncCapabilities::default()
+        );
+    }
+}
+
+#[test]
+fn test_checkpoint_resumed_after_restart() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+    upstream_storage.write().unwrap().commit_new_txns(40);
+    let highest_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.checkpoint_interval_ms = 1;
+    let (mut node, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config.clone(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    node.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the chunk leaves the highest LI pending and the empty chunk lowers the peer score
+    let chunk = GetChunkResponse::new(
+        ResponseLedgerInfo::ProgressiveLedgerInfo {
+            target_li: target_li.clone(),
+            highest_li: Some(highest_li),
+        },
+        TransactionListWithProof::new(txns, None, Some(1), TransactionListProof::new_empty()),
+    );
+    rt.block_on(node.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::GetChunkResponse(Box::new(chunk)),
+    ));
+    let empty_chunk = GetChunkResponse::new(
+        ResponseLedgerInfo::VerifiableLedgerInfo(target_li),
+        TransactionListWithProof::new_empty(),
+    );
+    rt.block_on(node.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::GetChunkResponse(Box::new(empty_chunk)),
+    ));
+    let peer_score = node.peer_score(&peer).unwrap();
+    assert!(peer_score < 100.0);
+
+    std::thread::sleep(Duration::from_millis(10));
+    node.checkpoint_if_due();
+    drop(node);
+
+    // the restarted coordinator resumes with the peer score and the pending LI
+    let (mut restarted_node, _network_id, mut network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::FullNode, config, Waypoint::default(), storage);
+    assert!((restarted_node.peer_score(&peer).unwrap() - peer_score).abs() < 0.001);
+    restarted_node.process_new_peer(peer, ConnectionOrigin::Outbound);
+    match next_msg(&mut network_reqs_rx).unwrap().1 {
+        StateSynchronizerMsg::GetChunkRequest(request) => {
+            assert_eq!(request.known_version, 10);
+            assert_eq!(request.target().version(), Some(50));
+        }
+        _ => panic!("expected a chunk request"),
+    }
+}
+
+#[test]
+fn test_subscription_deliveries_cap() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut config = StateSyncConfig::default();
+    config.max_subscription_deliveries = 2;
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peers: Vec<_> = (0..3)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    let mut rt = Runtime::new().unwrap();
+
+    // subscribe in order, so that the first peer is the oldest waiter
+    for peer in peers.iter() {
+        rt.block_on(
+            coordinator.process_one_message(peer.clone(), highest_available_request(10, 10_000)),
+        );
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // only the two oldest subscriptions are served right away
+    storage.write().unwrap().commit_new_txns(10);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        next_msg(&mut network_reqs_rx).unwrap().0,
+        peers[0].peer_id()
+    );
+    assert_eq!(
+        next_msg(&mut network_reqs_rx).unwrap().0,
+        peers[1].peer_id()
+    );
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the deferred one is served by the next check
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        next_msg(&mut network_reqs_rx).unwrap().0,
+        peers[2].peer_id()
+    );
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+}
+
+#[test]
+fn test_caught_up_detection() {
+    let upstream_storage = genesis_storage();
+    let mut chunks = vec![];
+    for _ in 0..3 {
+        let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+        chunks.push((txns, upstream_storage.read().unwrap().highest_local_li()));
+    }
+    let highest_li = chunks[2].1.clone();
+
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+    assert!(!coordinator.is_lagging_behind_target());
+
+    // the first chunk reveals a target ahead of the synced version
+    let (txns, li) = chunks.remove(0);
+    let txn_list_with_proof =
+        TransactionListWithProof::new(txns, None, Some(1), TransactionListProof::new_empty());
+    let response = GetChunkResponse::new(
+        ResponseLedgerInfo::ProgressiveLedgerInfo {
+            target_li: li,
+            highest_li: Some(highest_li),
+        },
+        txn_list_with_proof,
+    );
+    rt.block_on(coordinator.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::GetChunkResponse(Box::new(response)),
+    ));
+    assert!(coordinator.is_lagging_behind_target());
+
+    // the node remains behind until the last chunk is applied
+    let (txns, li) = chunks.remove(0);
+    rt.block_on(coordinator.process_one_message(peer.clone(), chunk_response(txns, 11, li)));
+    assert!(coordinator.is_lagging_behind_target());
+
+    let (txns, li) = chunks.remove(0);
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(txns, 21, li)));
+    assert!(!coordinator.is_lagging_behind_target());
+}
+
+#[test]
+fn test_reorder_buffer_overflow() {
+    let upstream_storage = genesis_storage();
+    let mut chunks = vec![];
+    for _ in 0..4 {
+        let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+        chunks.push((txns, upstream_storage.read().unwrap().highest_local_li()));
+    }
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.max_reorder_buffer_chunks = 2;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let initial_score = coordinator.peer_score(&peer).unwrap();
+    let mut rt = Runtime::new().unwrap();
+    let response = |idx: usize| {
+        let (txns, li) = chunks[idx].clone();
+        chunk_response(txns, idx as u64 * 10 + 1, li)
+    };
+
+    // the non-contiguous chunks overflow the buffer: the oldest one gets dropped
+    for idx in 1..4 {
+        rt.block_on(coordinator.process_one_message(peer.clone(), response(idx)));
+    }
+    assert_eq!(storage.read().unwrap().version(), 0);
+    assert_eq!(coordinator.reorder_buffer_len(), 2);
+    assert!(coordinator.peer_score(&peer).unwrap() < initial_score);
+
+    // the buffered chunks cannot be stitched until the dropped one is received again
+    rt.block_on(coordinator.process_one_message(peer.clone(), response(0)));
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert_eq!(coordinator.reorder_buffer_len(), 2);
+
+    rt.block_on(coordinator.process_one_message(peer, response(1)));
+    assert_eq!(storage.read().unwrap().version(), 40);
+    assert_eq!(coordinator.reorder_buffer_len(), 0);
+}
+
+#[test]
+fn test_stale_upstream_deprioritized() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    // the mock LIs carry a zero timestamp, so they are older than any staleness bound
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    for &max_upstream_staleness_ms in &[0, 60_000] {
+        let mut config = StateSyncConfig::default();
+        config.max_upstream_staleness_ms = max_upstream_staleness_ms;
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            genesis_storage(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        let initial_score = coordinator.peer_score(&peer).unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator
+                .process_one_message(peer.clone(), chunk_response(txns.clone(), 1, li.clone())),
+        );
+        let score = coordinator.peer_score(&peer).unwrap();
+        if max_upstream_staleness_ms == 0 {
+            assert!((score - initial_score).abs() < std::f64::EPSILON);
+        } else {
+            assert!(score < initial_score);
+        }
+    }
+}
+
+#[test]
+fn test_slow_chunk_serve_does_not_stall_sync() {
+    let upstream_storage = genesis_storage();
+    let (first_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let first_li = upstream_storage.read().unwrap().highest_local_li();
+    let (second_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let second_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    storage
+        .write()
+        .unwrap()
+        .add_txns_with_li(first_txns, first_li, None);
+    let mut config = StateSyncConfig::default();
+    config.max_concurrent_chunk_serves = Some(1);
+    let slow_handler: MockRpcHandler = Box::new(|resp| {
+        std::thread::sleep(Duration::from_millis(1_000));
+        Ok(resp)
+    });
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) =
+        coordinator_with_handler(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+            slow_handler,
+        );
+    let upstream_peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    coordinator.process_new_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+    let downstream_peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+
+    // the downstream request is served off the coordinator loop
+    rt.block_on(
+        coordinator.process_one_message(downstream_peer.clone(), highest_available_request(0, 0)),
+    );
+    // meanwhile a chunk from upstream is applied
+    rt.block_on(
+        coordinator.process_one_message(upstream_peer, chunk_response(second_txns, 11, second_li)),
+    );
+    assert_eq!(storage.read().unwrap().version(), 20);
+    let is_chunk_response = |msg: &(PeerId, StateSynchronizerMsg)| match msg.1 {
+        StateSynchronizerMsg::GetChunkResponse(_) => true,
+        _ => false,
+    };
+    while let Some(msg) = next_msg(&mut network_reqs_rx) {
+        assert!(!is_chunk_response(&msg));
+    }
+
+    // the response is sent once the serve completes
+    rt.block_on(coordinator.wait_for_chunk_serve());
+    let msg = next_msg(&mut network_reqs_rx).unwrap();
+    assert!(is_chunk_response(&msg));
+    assert_eq!(msg.0, downstream_peer.peer_id());
+}
+
+#[test]
+fn test_sync_request_rejects_epoch_jump() {
+    let upstream_storage = genesis_storage();
+    let (first_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    commit_epoch(&upstream_storage, 10);
+    commit_epoch(&upstream_storage, 10);
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.validate_sync_request_epochs = true;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let (callback, _callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li.clone(),
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+    let mut rt = Runtime::new().unwrap();
+
+    // the first chunk of the current epoch comes with an LI two epochs ahead
+    let rejected_count = counters::UNEXPECTED_EPOCH_JUMP_COUNT.get();
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(first_txns, 1, target_li)));
+    assert_eq!(storage.read().unwrap().version(), 0);
+    assert_eq!(
+        counters::UNEXPECTED_EPOCH_JUMP_COUNT.get(),
+        rejected_count + 1
+    );
+}
+
+#[test]
+fn test_sync_request_max_epochs() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    commit_epoch(&upstream_storage, 10);
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    for &max_sync_request_epochs in &[1, 2] {
+        let mut config = StateSyncConfig::default();
+        config.max_sync_request_epochs = max_sync_request_epochs;
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            genesis_storage(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer, ConnectionOrigin::Outbound);
+        let (callback, mut callback_rcv) = oneshot::channel();
+        let result = coordinator.request_sync(SyncRequest {
+            callback,
+            target: target_li.clone(),
+            last_progress_tst: SystemTime::now(),
+        });
+
+        // the target is two epochs ahead
+        if max_sync_request_epochs == 1 {
+            assert!(result.is_err());
+            assert!(callback_rcv.try_recv().unwrap().unwrap().is_err());
+        } else {
+            assert!(result.is_ok());
+            assert!(callback_rcv.try_recv().unwrap().is_none());
+        }
+    }
+}
+
+#[test]
+fn test_noop_commit_skips_notifications() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.skip_noop_commit_notifications = true;
+    let (mut coordinator, network_id, _network_reqs_rx, mut mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    let chunk = chunk_response(txns, 1, li);
+    rt.block_on(coordinator.process_one_message(peer.clone(), chunk.clone()));
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert!(mempool_requests.try_next().unwrap().is_some());
+
+    // a duplicate chunk doesn't move the synced version: the commit observed afterwards has
+    // nothing to report to mempool
+    rt.block_on(coordinator.process_one_message(peer.clone(), chunk));
+    rt.block_on(coordinator.process_commit(vec![], None, Some(&peer)))
+        .unwrap();
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert!(mempool_requests.try_next().is_err());
+
+    // the callback of a no-op commit is still completed
+    let (callback, callback_rcv) = oneshot::channel();
+    rt.block_on(coordinator.process_commit(vec![], Some(callback), None))
+        .unwrap();
+    let CommitResponse { msg } = rt.block_on(callback_rcv).unwrap().unwrap();
+    assert_eq!(msg, "");
+    assert!(mempool_requests.try_next().is_err());
+}
+
+#[test]
+fn test_sync_request_before_initialization() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        waypoint,
+        genesis_storage(),
+    );
+    let (callback, mut callback_rcv) = oneshot::channel();
+    let result = coordinator.request_sync(SyncRequest {
+        callback,
+        target: target_li,
+        last_progress_tst: SystemTime::now(),
+    });
+
+    // the caller learns why its request was rejected instead of being dropped
+    assert!(result.is_err());
+    let error = callback_rcv.try_recv().unwrap().unwrap().unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<SyncRequestError>(),
+        Some(&SyncRequestError::NotInitialized)
+    );
+}
+
+#[test]
+fn test_sync_request_storage_unavailable() {
+    let storage_unavailable = Arc::new(AtomicBool::new(false));
+    let executor_proxy =
+        MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), genesis_storage())
+            .with_storage_unavailable(storage_unavailable.clone());
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator_with_proxy(
+            RoleType::Validator,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            executor_proxy,
+            None,
+        );
+
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target = upstream_storage.read().unwrap().highest_local_li();
+    storage_unavailable.store(true, Ordering::SeqCst);
+    let (callback, mut callback_rcv) = oneshot::channel();
+    let result = coordinator.request_sync(SyncRequest {
+        callback,
+        target,
+        last_progress_tst: SystemTime::now(),
+    });
+
+    // the caller is answered even though the request failed before any validation
+    assert!(result.is_err());
+    assert!(callback_rcv.try_recv().unwrap().unwrap().is_err());
+}
+
+#[test]
+fn test_phase_change_sink() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    let storage = genesis_storage();
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        waypoint,
+        storage.clone(),
+    );
+    let (sink, mut phase_changes) = mpsc::channel(1);
+    coordinator.set_phase_change_sink(sink);
+    let mut rt = Runtime::new().unwrap();
+
+    // no transition while the waypoint is not reached
+    storage.write().unwrap().commit_new_txns(5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(phase_changes.try_next().is_err());
+
+    commit_epoch(&storage, 5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        phase_changes.try_next().unwrap().unwrap(),
+        SyncPhaseChange {
+            old_phase: SyncPhase::WaypointSync,
+            new_phase: SyncPhase::Normal,
+            version: 10,
+        }
+    );
+}
+
+#[test]
+fn test_waypoint_init_timeout() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    // no upstream ever serves the waypoint
+    let mut config = StateSyncConfig::default();
+    config.waypoint_init_timeout_ms = 50;
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, waypoint, genesis_storage());
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    coordinator.check_waypoint_init_timeout();
+    assert!(callback_rcv.try_recv().unwrap().is_none());
+
+    std::thread::sleep(Duration::from_millis(60));
+    coordinator.check_waypoint_init_timeout();
+    assert!(callback_rcv.try_recv().unwrap().unwrap().is_err());
+
+    // later listeners learn about the timeout right away
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert!(callback_rcv.try_recv().unwrap().unwrap().is_err());
+}
+
+#[test]
+fn test_read_replica_lag() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let replica_li = storage.read().unwrap().highest_local_li();
+    // the replica is a snapshot of the storage lagging behind by 10 versions
+    let replica_storage = Arc::new(RwLock::new(storage.read().unwrap().clone()));
+    storage.write().unwrap().commit_new_txns(10);
+
+    for &read_replica_fallback in &[true, false] {
+        let mut config = StateSyncConfig::default();
+        config.read_replica_fallback = read_replica_fallback;
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let replica_reads = Arc::new(AtomicUsize::new(0));
+        let reads = replica_reads.clone();
+        coordinator.set_read_replica(MockExecutorProxy::new(
+            Box::new(move |txns| {
+                reads.fetch_add(1, Ordering::SeqCst);
+                Ok(txns)
+            }),
+            replica_storage.clone(),
+        ));
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+
+        // the replica serves the versions it has caught up with
+        let request =
+            GetChunkRequest::new(0, 1, 250, TargetType::TargetLedgerInfo(replica_li.clone()));
+        rt.block_on(coordinator.process_one_message(
+            peer.clone(),
+            StateSynchronizerMsg::GetChunkRequest(Box::new(request)),
+        ));
+        match next_msg(&mut network_reqs_rx).unwrap().1 {
+            StateSynchronizerMsg::GetChunkResponse(response) => {
+                assert_eq!(response.txn_list_with_proof.len(), 10);
+            }
+            _ => panic!("expected a chunk response"),
+        }
+        assert_eq!(replica_reads.load(Ordering::SeqCst), 1);
+
+        // the highest LI is not available on the replica yet
+        rt.block_on(coordinator.process_one_message(peer, highest_available_request(0, 10_000)));
+        match next_msg(&mut network_reqs_rx).unwrap().1 {
+            StateSynchronizerMsg::GetChunkResponse(response) => {
+                let expected_txns = if read_replica_fallback { 20 } else { 0 };
+                assert_eq!(response.txn_list_with_proof.len(), expected_txns);
+            }
+            _ => panic!("expected a chunk response"),
+        }
+        assert_eq!(replica_reads.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[test]
+fn test_subscription_version_margin() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut config = StateSyncConfig::default();
+    config.max_subscription_version_margin = 100;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, Waypoint::default(), storage);
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), highest_available_request(111, 10_000)),
+    );
+    assert!(!coordinator.is_subscribed(&peer));
+
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), highest_available_request(110, 10_000)),
+    );
+    assert!(coordinator.is_subscribed(&peer));
+}
+
+#[test]
+fn test_chunk_timings() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    for &record_chunk_timings in &[false, true] {
+        let mut config = StateSyncConfig::default();
+        config.record_chunk_timings = record_chunk_timings;
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            genesis_storage(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        // sends the request for the chunk
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator.process_one_message(peer, chunk_response(txns.clone(), 1, li.clone())),
+        );
+
+        let timings = coordinator.recent_chunk_timings();
+        if record_chunk_timings {
+            assert_eq!(timings.len(), 1);
+            assert_eq!(timings[0].first_version, 1);
+            assert_eq!(timings[0].num_txns, 10);
+            assert!(timings[0].network_receive.is_some());
+        } else {
+            assert!(timings.is_empty());
+        }
+    }
+}
+
+#[test]
+fn test_chunk_limit_downgrade() {
+    // chunks of 40 transactions fail to execute, chunks of 5 succeed
+    let large_storage = genesis_storage();
+    let (large_txns, _) = large_storage.write().unwrap().commit_new_txns(40);
+    let large_li = large_storage.read().unwrap().highest_local_li();
+    let upstream_storage = genesis_storage();
+    let mut chunks = vec![];
+    for _ in 0..10 {
+        let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(5);
+        chunks.push((txns, upstream_storage.read().unwrap().highest_local_li()));
+    }
+
+    let mut config = StateSyncConfig::default();
+    config.chunk_limit = Some(100);
+    config.min_chunk_limit = 30;
+    config.chunk_limit_downgrade_failures = 2;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator_with_proxy(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), genesis_storage())
+            .with_max_chunk_size(25),
+        None,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+    let mut send_large_chunk = |coordinator: &mut SyncCoordinator<MockExecutorProxy>| {
+        rt.block_on(coordinator.process_one_message(
+            peer.clone(),
+            chunk_response(large_txns.clone(), 1, large_li.clone()),
+        ));
+    };
+
+    // the limit is halved after every two consecutive failures, down to the floor
+    send_large_chunk(&mut coordinator);
+    assert_eq!(coordinator.chunk_limit(), 100);
+    send_large_chunk(&mut coordinator);
+    assert_eq!(coordinator.chunk_limit(), 50);
+    send_large_chunk(&mut coordinator);
+    send_large_chunk(&mut coordinator);
+    assert_eq!(coordinator.chunk_limit(), 30);
+    send_large_chunk(&mut coordinator);
+    send_large_chunk(&mut coordinator);
+    assert_eq!(coordinator.chunk_limit(), 30);
+
+    // sustained successes double the limit back
+    let mut rt = Runtime::new().unwrap();
+    for (i, (txns, li)) in chunks.into_iter().enumerate() {
+        assert_eq!(coordinator.chunk_limit(), 30);
+        rt.block_on(
+            coordinator
+                .process_one_message(peer.clone(), chunk_response(txns, 5 * i as u64 + 1, li)),
+        );
+    }
+    assert_eq!(coordinator.chunk_limit(), 60);
+}
+
+#[test]
+fn test_revoked_subscriber_not_served() {
+    for &revalidate_subscribers in &[true, false] {
+        let storage = genesis_storage();
+        storage.write().unwrap().commit_new_txns(10);
+        let mut config = StateSyncConfig::default();
+        config.revalidate_subscribers = revalidate_subscribers;
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator.process_one_message(peer.clone(), highest_available_request(10, 10_000)),
+        );
+
+        // the subscriber disconnects before new data becomes available
+        coordinator.process_lost_peer(peer.clone(), ConnectionOrigin::Inbound);
+        storage.write().unwrap().commit_new_txns(10);
+        rt.block_on(coordinator.process_commit(vec![], None, None))
+            .unwrap();
+        assert_eq!(
+            next_msg(&mut network_reqs_rx).is_some(),
+            !revalidate_subscribers
+        );
+        assert!(!coordinator.is_subscribed(&peer));
+    }
+}
+
+#[test]
+fn test_subscriber_disallowed_by_serving_policy() {
+    for &revalidate_subscribers in &[true, false] {
+        let storage = genesis_storage();
+        storage.write().unwrap().commit_new_txns(10);
+        let mut config = StateSyncConfig::default();
+        config.revalidate_subscribers = revalidate_subscribers;
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id.clone(), PeerId::random());
+        let other_peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+        for p in &[&peer, &other_peer] {
+            rt.block_on(
+                coordinator
+                    .process_one_message((*p).clone(), highest_available_request(10, 10_000)),
+            );
+        }
+
+        // the peer stops being allowed to be served while it's subscribed
+        coordinator.set_serving_policy(ServingPolicy {
+            networks: None,
+            allowlist: Some(vec![other_peer.clone()].into_iter().collect()),
+        });
+        storage.write().unwrap().commit_new_txns(10);
+        rt.block_on(coordinator.process_commit(vec![], None, None))
+            .unwrap();
+        let mut served = HashSet::new();
+        while let Some((peer_id, _msg)) = next_msg(&mut network_reqs_rx) {
+            served.insert(peer_id);
+        }
+        assert!(served.contains(&other_peer.peer_id()));
+        assert_eq!(served.contains(&peer.peer_id()), !revalidate_subscribers);
+        assert!(!coordinator.is_subscribed(&peer));
+
+        // its new requests are rejected
+        rt.block_on(
+            coordinator.process_one_message(peer.clone(), highest_available_request(20, 10_000)),
+        );
+        assert!(next_msg(&mut network_reqs_rx).is_none());
+        assert!(!coordinator.is_subscribed(&peer));
+    }
+}
+
+#[test]
+fn test_adaptive_tick_interval() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let mut config = StateSyncConfig::default();
+    config.tick_interval_ms = 100;
+    config.idle_tick_interval_ms = 1_000;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer, ConnectionOrigin::Outbound);
+
+    // a caught up validator without a sync request is idle
+    assert_eq!(coordinator.tick_interval(), Duration::from_millis(1_000));
+
+    let (callback, _callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li,
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+    assert_eq!(coordinator.tick_interval(), Duration::from_millis(100));
+}
+
+#[test]
+fn test_forked_chunk_rejected() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+    // the local accumulator is empty, so a proof with a left sibling of the first leaf can't
+    // extend it
+    let forked_chunk = |first_version: u64| {
+        let txn_list_with_proof = TransactionListWithProof::new(
+            txns.clone(),
+            None,
+            Some(first_version),
+            TransactionListProof::new(
+                TransactionAccumulatorRangeProof::new(vec![HashValue::random()], vec![]),
+                vec![],
+            ),
+        );
+        StateSynchronizerMsg::GetChunkResponse(Box::new(GetChunkResponse::new(
+            ResponseLedgerInfo::VerifiableLedgerInfo(li.clone()),
+            txn_list_with_proof,
+        )))
+    };
+
+    for &reject_forked_chunks in &[false, true] {
+        let mut config = StateSyncConfig::default();
+        config.reject_forked_chunks = reject_forked_chunks;
+        let storage = genesis_storage();
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        let initial_score = coordinator.peer_score(&peer).unwrap();
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(coordinator.process_one_message(peer.clone(), forked_chunk(1)));
+
+        if reject_forked_chunks {
+            assert_eq!(storage.read().unwrap().version(), 0);
+            assert!(coordinator.peer_score(&peer).unwrap() < initial_score);
+            // a chunk extending the local accumulator is still applied
+            rt.block_on(
+                coordinator.process_one_message(peer, chunk_response(txns.clone(), 1, li.clone())),
+            );
+        }
+        assert_eq!(storage.read().unwrap().version(), 10);
+    }
+}
+
+#[test]
+fn test_honest_chunk_extends_local_accumulator() {
+    let upstream_storage = genesis_storage();
+    // 5 leaves locally: the frozen subtrees are the ones of leaves 0..4 and of leaf 4
+    upstream_storage.write().unwrap().commit_new_txns(4);
+    let storage = Arc::new(RwLock::new(upstream_storage.read().unwrap().clone()));
+    assert_eq!(
+        storage
+            .read()
+            .unwrap()
+            .synced_trees()
+            .txn_accumulator()
+            .frozen_subtree_roots()
+            .len(),
+        2
+    );
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(3);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    // the honest proof of leaves 5..8 carries the left siblings from the leaf up
+    let leaves = upstream_storage.read().unwrap().txn_accumulator_leaves();
+    let left_siblings = vec![
+        leaves[4],
+        InMemoryAccumulator::<TransactionAccumulatorHasher>::from_leaves(&leaves[..4]).root_hash(),
+    ];
+    let proof = TransactionAccumulatorRangeProof::new(left_siblings, vec![]);
+    proof
+        .verify(
+            InMemoryAccumulator::<TransactionAccumulatorHasher>::from_leaves(&leaves).root_hash(),
+            Some(5),
+            &leaves[5..],
+        )
+        .unwrap();
+
+    let mut config = StateSyncConfig::default();
+    config.reject_forked_chunks = true;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let initial_score = coordinator.peer_score(&peer).unwrap();
+    let response = StateSynchronizerMsg::GetChunkResponse(Box::new(GetChunkResponse::new(
+        ResponseLedgerInfo::VerifiableLedgerInfo(li),
+        TransactionListWithProof::new(
+            txns,
+            None,
+            Some(5),
+            TransactionListProof::new(proof, vec![]),
+        ),
+    )));
+    Runtime::new()
+        .unwrap()
+        .block_on(coordinator.process_one_message(peer.clone(), response));
+    assert_eq!(storage.read().unwrap().version(), 7);
+    assert!(coordinator.peer_score(&peer).unwrap() >= initial_score);
+}
+
+#[test]
+fn test_warm_up_probe() {
+    for &warm_up_new_peers in &[false, true] {
+        let mut config = StateSyncConfig::default();
+        config.warm_up_new_peers = warm_up_new_peers;
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            genesis_storage(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        let initial_score = coordinator.peer_score(&peer).unwrap();
+
+        let mut msgs = vec![];
+        while let Some((peer_id, msg)) = next_msg(&mut network_reqs_rx) {
+            assert_eq!(peer_id, peer.peer_id());
+            match msg {
+                StateSynchronizerMsg::GetChunkRequest(request) => {
+                    assert_eq!(request.limit, 250);
+                    msgs.push("request");
+                }
+                StateSynchronizerMsg::WarmUpProbe => msgs.push("probe"),
+                _ => panic!("unexpected message"),
+            }
+        }
+        if !warm_up_new_peers {
+            assert_eq!(msgs, vec!["request"]);
+            continue;
+        }
+        // the probe precedes the real chunk request
+        assert_eq!(msgs, vec!["probe", "request"]);
+
+        // the response to the probe doesn't count against the peer
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator
+                .process_one_message(peer.clone(), StateSynchronizerMsg::WarmUpProbeResponse),
+        );
+        assert_eq!(coordinator.peer_score(&peer).unwrap(), initial_score);
+
+        // a reconnection race doesn't send another probe
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        while let Some((_, msg)) = next_msg(&mut network_reqs_rx) {
+            assert!(!matches!(msg, StateSynchronizerMsg::WarmUpProbe));
+        }
+    }
+}
+
+#[test]
+fn test_warm_up_probe_response() {
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(peer.clone(), StateSynchronizerMsg::WarmUpProbe));
+
+    // the probe is answered without serving any chunk
+    let (peer_id, msg) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(peer_id, peer.peer_id());
+    assert!(matches!(msg, StateSynchronizerMsg::WarmUpProbeResponse));
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    assert!(!coordinator.is_subscribed(&peer));
+}
+
+#[test]
+fn test_current_target() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    // a node behind its waypoint syncs to the waypoint, an initialized full node polls for the
+    // highest available version
+    for &(waypoint, expect_waypoint_sync) in &[(waypoint, true), (Waypoint::default(), false)] {
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            StateSyncConfig::default(),
+            waypoint,
+            genesis_storage(),
+        );
+        assert!(coordinator.last_sent_target().is_none());
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer, ConnectionOrigin::Outbound);
+
+        match coordinator.last_sent_target() {
+            Some(TargetType::Waypoint(version)) if expect_waypoint_sync => assert_eq!(version, 10),
+            Some(TargetType::HighestAvailable { target_li, .. }) if !expect_waypoint_sync => {
+                assert!(target_li.is_none())
+            }
+            target => panic!("unexpected target {:?}", target),
+        }
+    }
+}
+
+#[test]
+fn test_reconfig_events_republished_after_crash() {
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.journal_reconfig_events = true;
+    let published_reconfig_events = Arc::new(RwLock::new(vec![]));
+    let executor_proxy = || {
+        MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), storage.clone())
+            .with_published_reconfig_events(published_reconfig_events.clone())
+    };
+    let (mut node, _network_id, _network_reqs_rx, _mempool_requests) = coordinator_with_proxy(
+        RoleType::Validator,
+        config.clone(),
+        Waypoint::default(),
+        executor_proxy(),
+        None,
+    );
+    let events = vec![ContractEvent::new(
+        new_epoch_event_key(),
+        0,
+        coin1_tmp_tag(),
+        vec![],
+    )];
+
+    // the node crashes after the commit is processed but before its events are published
+    storage.write().unwrap().commit_new_txns(10);
+    node.journal_reconfig_events(&events);
+    Runtime::new()
+        .unwrap()
+        .block_on(node.process_commit(vec![], None, None))
+        .unwrap();
+    drop(node);
+    assert!(published_reconfig_events.read().unwrap().is_empty());
+
+    // the restarted node publishes the journaled events and clears the journal
+    let restarted_node = coordinator_with_proxy(
+        RoleType::Validator,
+        config.clone(),
+        Waypoint::default(),
+        executor_proxy(),
+        None,
+    );
+    assert_eq!(*published_reconfig_events.read().unwrap(), events);
+    assert!(storage.read().unwrap().reconfig_journal().is_empty());
+    drop(restarted_node);
+
+    // the events are published only once
+    let _restarted_node = coordinator_with_proxy(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        executor_proxy(),
+        None,
+    );
+    assert_eq!(published_reconfig_events.read().unwrap().len(), 1);
+}
+
+#[test]
+fn test_epoch_lookup_rate_limit() {
+    let storage = genesis_storage();
+    for _ in 0..2 {
+        commit_epoch(&storage, 10);
+    }
+    let mut config = StateSyncConfig::default();
+    config.max_epoch_lookups_per_sec = 2;
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, Waypoint::default(), storage);
+    let mut rt = Runtime::new().unwrap();
+
+    // serving a waypoint request of an earlier epoch takes two lookups: the waypoint LI and the
+    // end of the request epoch, so the second request in the same second exceeds the budget
+    let mut response_sizes = vec![];
+    for _ in 0..2 {
+        let request = GetChunkRequest::new(0, 1, 250, TargetType::Waypoint(20));
+        rt.block_on(coordinator.process_one_message(
+            PeerNetworkId(network_id.clone(), PeerId::random()),
+            StateSynchronizerMsg::GetChunkRequest(Box::new(request)),
+        ));
+        match next_msg(&mut network_reqs_rx).unwrap().1 {
+            StateSynchronizerMsg::GetChunkResponse(response) => {
+                response_sizes.push(response.txn_list_with_proof.len())
+            }
+            _ => panic!("expected a chunk response"),
+        }
+    }
+    assert_eq!(response_sizes, vec![10, 0]);
+}
+
+#[test]
+fn test_sync_from_genesis() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    for &role in &[RoleType::Validator, RoleType::FullNode] {
+        let storage = genesis_storage();
+        assert_eq!(storage.read().unwrap().version(), 0);
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            role,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        // validators only sync on demand
+        let mut callback_rcv = None;
+        if role == RoleType::Validator {
+            let (callback, rcv) = oneshot::channel();
+            coordinator
+                .request_sync(SyncRequest {
+                    callback,
+                    target: li.clone(),
+                    last_progress_tst: SystemTime::now(),
+                })
+                .unwrap();
+            callback_rcv = Some(rcv);
+        }
+
+        // the first chunk is requested right after the genesis version
+        match next_msg(&mut network_reqs_rx).unwrap().1 {
+            StateSynchronizerMsg::GetChunkRequest(request) => {
+                assert_eq!(request.known_version, 0);
+                assert_eq!(request.current_epoch, 1);
+            }
+            _ => panic!("expected a chunk request"),
+        }
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator.process_one_message(peer, chunk_response(txns.clone(), 1, li.clone())),
+        );
+        assert_eq!(storage.read().unwrap().version(), 10);
+        if let Some(mut callback_rcv) = callback_rcv {
+            assert!(callback_rcv.try_recv().unwrap().unwrap().is_ok());
+        }
+    }
+}
+
+#[test]
+fn test_stale_epoch_response() {
+    // the upstream is still in the epoch the node has already left
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let stale_li = upstream_storage.read().unwrap().highest_local_li();
+
+    for &reject_stale_epoch_responses in &[false, true] {
+        let storage = genesis_storage();
+        commit_epoch(&storage, 10);
+        let mut config = StateSyncConfig::default();
+        config.reject_stale_epoch_responses = reject_stale_epoch_responses;
+        let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::FullNode,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(coordinator.process_one_message(
+            peer.clone(),
+            chunk_response(txns.clone(), 11, stale_li.clone()),
+        ));
+
+        // the response is rejected either way, but a stale epoch is penalized lightly
+        assert_eq!(storage.read().unwrap().version(), 10);
+        let score = coordinator.peer_score(&peer).unwrap();
+        if reject_stale_epoch_responses {
+            assert!((score - 95.0).abs() < 0.001);
+        } else {
+            assert!((score - 80.0).abs() < 0.001);
+        }
+    }
+}
+
+#[test]
+fn test_low_peer_count_signal() {
+    let mut config = StateSyncConfig::default();
+    config.min_upstream_peers = 2;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let (sink, mut signals) = mpsc::channel(10);
+    coordinator.set_low_peer_count_sink(sink);
+    let peers: Vec<_> = (0..3)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    for peer in &peers {
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    }
+
+    // the signal fires once the number of enabled peers drops below the threshold
+    coordinator.process_lost_peer(peers[0].clone(), ConnectionOrigin::Outbound);
+    assert!(signals.try_next().is_err());
+    coordinator.process_lost_peer(peers[1].clone(), ConnectionOrigin::Outbound);
+    assert_eq!(signals.try_next().unwrap(), Some(1));
+    coordinator.process_lost_peer(peers[2].clone(), ConnectionOrigin::Outbound);
+    assert_eq!(signals.try_next().unwrap(), Some(0));
+}
+
+#[test]
+fn test_initialization_result_already_initialized() {
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert_eq!(
+        callback_rcv.try_recv().unwrap().unwrap().unwrap(),
+        InitializationResult::AlreadyInitialized
+    );
+}
+
+#[test]
+fn test_initialization_result_reached() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+    let (mut upstream, upstream_network_id, mut upstream_reqs_rx, _upstream_mempool_requests) =
+        coordinator(
+            RoleType::Validator,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            upstream_storage,
+        );
+
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        waypoint,
+        storage.clone(),
+    );
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert!(callback_rcv.try_recv().unwrap().is_none());
+
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let upstream_peer = PeerNetworkId(upstream_network_id, PeerId::random());
+    coordinator.process_new_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // relay the messages between the two nodes until the waypoint is reached
+    let mut rounds = 0;
+    while storage.read().unwrap().version() < 10 {
+        assert!(rounds < 10, "waypoint sync did not converge");
+        rounds += 1;
+        let request = next_msg(&mut network_reqs_rx).unwrap().1;
+        rt.block_on(upstream.process_one_message(peer.clone(), request));
+        let response = next_msg(&mut upstream_reqs_rx).unwrap().1;
+        rt.block_on(coordinator.process_one_message(upstream_peer.clone(), response));
+    }
+    assert_eq!(
+        callback_rcv.try_recv().unwrap().unwrap().unwrap(),
+        InitializationResult::Reached
+    );
+}
+
+#[test]
+fn test_subscription_memory_limit() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut config = StateSyncConfig::default();
+    // room for two subscriptions and a bit
+    config.max_subscription_memory_bytes = 2 * SUBSCRIPTION_MEMORY_ESTIMATE + 1;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, Waypoint::default(), storage);
+    let peers: Vec<_> = (0..4)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    let mut rt = Runtime::new().unwrap();
+
+    // subscribe in order, so that the first peer is the oldest waiter
+    let shed_count = counters::SHED_SUBSCRIPTION_COUNT
+        .with_label_values(&[&peers[0].raw_network_id().to_string()])
+        .get();
+    for peer in peers.iter() {
+        rt.block_on(
+            coordinator.process_one_message(peer.clone(), highest_available_request(10, 10_000)),
+        );
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    // the oldest subscriptions are shed to stay within the memory limit
+    assert!(!coordinator.is_subscribed(&peers[0]));
+    assert!(!coordinator.is_subscribed(&peers[1]));
+    assert!(coordinator.is_subscribed(&peers[2]));
+    assert!(coordinator.is_subscribed(&peers[3]));
+    assert_eq!(
+        counters::SHED_SUBSCRIPTION_COUNT
+            .with_label_values(&[&peers[0].raw_network_id().to_string()])
+            .get(),
+        shed_count + 2
+    );
+}
+
+#[test]
+fn test_storage_regression() {
+    let storage = genesis_storage();
+    let genesis_snapshot = storage.read().unwrap().clone();
+    commit_epoch(&storage, 10);
+    storage.write().unwrap().commit_new_txns(5);
+    let synced_snapshot = storage.read().unwrap().clone();
+    let synced_version = synced_snapshot.version();
+    let waypoint_li = storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    // a storage with a different history up to the waypoint version
+    let forked_storage = genesis_storage();
+    commit_epoch(&forked_storage, 10);
+    forked_storage.write().unwrap().commit_new_txns(2);
+
+    let mut config = StateSyncConfig::default();
+    config.reverify_waypoint_on_regression = true;
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, waypoint, storage.clone());
+    let (sink, mut phase_changes) = mpsc::channel(1);
+    coordinator.set_phase_change_sink(sink);
+    let mut rt = Runtime::new().unwrap();
+    let regression_count = counters::STORAGE_REGRESSION_COUNT.get();
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(counters::STORAGE_REGRESSION_COUNT.get(), regression_count);
+
+    // the regressed storage no longer matches the waypoint: back to waypoint sync
+    let forked_snapshot = forked_storage.read().unwrap().clone();
+    let forked_version = forked_snapshot.version();
+    *storage.write().unwrap() = forked_snapshot;
+    assert!(rt
+        .block_on(coordinator.process_commit(vec![], None, None))
+        .is_err());
+    assert_eq!(
+        counters::STORAGE_REGRESSION_COUNT.get(),
+        regression_count + 1
+    );
+    assert_eq!(
+        phase_changes.try_next().unwrap().unwrap(),
+        SyncPhaseChange {
+            old_phase: SyncPhase::Normal,
+            new_phase: SyncPhase::WaypointSync,
+            version: forked_version,
+        }
+    );
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert!(callback_rcv.try_recv().unwrap().is_none());
+
+    // the next commits don't fail again, and the node recovers once the storage matches the
+    // waypoint again
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(phase_changes.try_next().is_err());
+    *storage.write().unwrap() = synced_snapshot;
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        phase_changes.try_next().unwrap().unwrap(),
+        SyncPhaseChange {
+            old_phase: SyncPhase::WaypointSync,
+            new_phase: SyncPhase::Normal,
+            version: synced_version,
+        }
+    );
+    assert_eq!(
+        callback_rcv.try_recv().unwrap().unwrap().unwrap(),
+        InitializationResult::Reached
+    );
+
+    // a storage reset behind the waypoint goes back to waypoint sync
+    *storage.write().unwrap() = genesis_snapshot;
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        counters::STORAGE_REGRESSION_COUNT.get(),
+        regression_count + 2
+    );
+    assert_eq!(
+        phase_changes.try_next().unwrap().unwrap(),
+        SyncPhaseChange {
+            old_phase: SyncPhase::Normal,
+            new_phase: SyncPhase::WaypointSync,
+            version: 0,
+        }
+    );
+}
+
+#[test]
+fn test_tip_request() {
+    let storage = genesis_storage();
+    commit_epoch(&storage, 10);
+    storage.write().unwrap().commit_new_txns(20);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+
+    // the requester is far behind, yet it only gets the highest LI back
+    let request = StateSynchronizerMsg::GetChunkRequest(Box::new(GetChunkRequest::new(
+        0,
+        1,
+        250,
+        TargetType::Tip,
+    )));
+    rt.block_on(coordinator.process_one_message(peer.clone(), request));
+    let (recipient, msg) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(recipient, peer.peer_id());
+    match msg {
+        StateSynchronizerMsg::GetChunkResponse(response) => {
+            match &response.response_li {
+                ResponseLedgerInfo::TipLedgerInfo(li) => {
+                    assert_eq!(li.ledger_info().version(), 30);
+                    assert_eq!(li.ledger_info().epoch(), 2);
+                }
+                _ => panic!("expected a tip ledger info"),
+            }
+            assert_eq!(response.txn_list_with_proof.len(), 0);
+            assert!(response
+                .txn_list_with_proof
+                .first_transaction_version
+                .is_none());
+        }
+        _ => panic!("expected a chunk response"),
+    }
+    // tip queries never turn into subscriptions
+    assert!(!coordinator.is_subscribed(&peer));
+}
+
+#[test]
+fn test_tip_response() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    assert!(next_msg(&mut network_reqs_rx).is_some());
+    let initial_score = coordinator.peer_score(&peer).unwrap();
+
+    let response = GetChunkResponse::new(
+        ResponseLedgerInfo::TipLedgerInfo(li),
+        TransactionListWithProof::new_empty(),
+    );
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::GetChunkResponse(Box::new(response)),
+    ));
+
+    // unlike an empty chunk, the tip is neither penalized nor retried right away
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    assert_eq!(coordinator.peer_score(&peer).unwrap(), initial_score);
+}
+
+#[test]
+fn test_effective_max_timeout() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut config = StateSyncConfig::default();
+    config.max_subscription_memory_bytes = 10 * SUBSCRIPTION_MEMORY_ESTIMATE;
+    config.max_timeout_ms = 11_000;
+    config.min_subscription_timeout_ms = 1_000;
+    config.subscription_load_threshold_pct = 50;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, Waypoint::default(), storage);
+    let mut rt = Runtime::new().unwrap();
+    let mut subscribe = |coordinator: &mut SyncCoordinator<MockExecutorProxy>, count| {
+        for _ in 0..count {
+            let peer = PeerNetworkId(network_id.clone(), PeerId::random());
+            rt.block_on(
+                coordinator.process_one_message(peer, highest_available_request(10, 100_000)),
+            );
+        }
+    };
+
+    // the full timeout is granted while lightly loaded
+    assert_eq!(coordinator.effective_max_timeout_ms(), 11_000);
+    subscribe(&mut coordinator, 5);
+    assert_eq!(coordinator.effective_max_timeout_ms(), 11_000);
+
+    // the timeout shrinks as the subscriptions get closer to their capacity
+    subscribe(&mut coordinator, 1);
+    assert_eq!(coordinator.effective_max_timeout_ms(), 9_000);
+    subscribe(&mut coordinator, 4);
+    assert_eq!(coordinator.effective_max_timeout_ms(), 1_000);
+}
+
+#[test]
+fn test_sync_request_completion_records() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let first_li = upstream_storage.read().unwrap().highest_local_li();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let second_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let (sink, mut records) = mpsc::channel(10);
+    coordinator.set_completion_sink(sink);
+    let peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let sync_to = |coordinator: &mut SyncCoordinator<MockExecutorProxy>,
+                   target: &LedgerInfoWithSignatures,
+                   last_progress_tst| {
+        let (callback, callback_rcv) = oneshot::channel();
+        coordinator
+            .request_sync(SyncRequest {
+                callback,
+                target: target.clone(),
+                last_progress_tst,
+            })
+            .unwrap();
+        callback_rcv
+    };
+
+    // a request overridden by a newer one is cancelled
+    let _cancelled_rcv = sync_to(&mut coordinator, &first_li, SystemTime::now());
+    let _completed_rcv = sync_to(&mut coordinator, &first_li, SystemTime::now());
+    let record = records.try_next().unwrap().unwrap();
+    assert_eq!(record.outcome, SyncRequestOutcome::Cancelled);
+    assert_eq!(record.target_version, 10);
+    assert_eq!(record.chunks_applied, 0);
+
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(txns, 1, first_li)));
+    let record = records.try_next().unwrap().unwrap();
+    assert_eq!(record.outcome, SyncRequestOutcome::Complete);
+    assert_eq!(record.target_version, 10);
+    assert_eq!(record.chunks_applied, 1);
+    assert!(record.bytes_transferred > 0);
+    assert!(record.start_time <= record.end_time);
+
+    // a request without progress for too long times out upon the next progress check
+    let _timed_out_rcv = sync_to(&mut coordinator, &second_li, UNIX_EPOCH);
+    coordinator.process_new_peer(
+        PeerNetworkId(network_id, PeerId::random()),
+        ConnectionOrigin::Outbound,
+    );
+    let record = records.try_next().unwrap().unwrap();
+    assert_eq!(record.outcome, SyncRequestOutcome::Timeout);
+    assert_eq!(record.target_version, 20);
+    assert_eq!(record.chunks_applied, 0);
+    assert!(records.try_next().is_err());
+}
+
+#[test]
+fn test_max_sync_attempts() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let mut config = StateSyncConfig::default();
+    config.max_sync_attempts = 3;
+    config.tick_interval_ms = 1;
+    // the local storage never advances, as no upstream serves the target
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let (sink, mut records) = mpsc::channel(1);
+    coordinator.set_completion_sink(sink);
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li,
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+
+    // every timed out request is retried until the attempts are exhausted
+    for _ in 0..3 {
+        assert!(next_msg(&mut network_reqs_rx).is_some());
+        assert!(callback_rcv.try_recv().unwrap().is_none());
+        std::thread::sleep(Duration::from_millis(5));
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    }
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    let err = callback_rcv.try_recv().unwrap().unwrap().unwrap_err();
+    assert!(err.to_string().contains("version 0"));
+    assert_eq!(
+        records.try_next().unwrap().unwrap().outcome,
+        SyncRequestOutcome::AttemptsExhausted
+    );
+    assert_eq!(storage.read().unwrap().version(), 0);
+
+    // the coordinator is idle afterwards
+    std::thread::sleep(Duration::from_millis(5));
+    coordinator.process_new_peer(peer, ConnectionOrigin::Outbound);
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+}
+
+#[test]
+fn test_subscriptions_snapshot() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peers: Vec<_> = (0..3)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(
+        coordinator.process_one_message(peers[0].clone(), highest_available_request(10, 10_000)),
+    );
+    rt.block_on(
+        coordinator.process_one_message(peers[1].clone(), highest_available_request(12, 10_000)),
+    );
+    // expires right away
+    rt.block_on(
+        coordinator.process_one_message(peers[2].clone(), highest_available_request(11, 1)),
+    );
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(coordinator.is_subscribed(&peers[2]));
+
+    let mut snapshot = coordinator.subscriptions_snapshot();
+    snapshot.sort_by_key(|info| info.known_version);
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot[0].peer, peers[0]);
+    assert_eq!(snapshot[0].known_version, 10);
+    assert_eq!(snapshot[1].peer, peers[1]);
+    assert_eq!(snapshot[1].known_version, 12);
+    for info in snapshot {
+        assert_eq!(info.request_epoch, 1);
+        assert_eq!(info.limit, 250);
+        assert!(info.remaining <= Duration::from_millis(10_000));
+    }
+}
+
+#[test]
+fn test_partial_chunk_response() {
+    let storage = genesis_storage();
+    commit_epoch(&storage, 10);
+    storage.write().unwrap().commit_new_txns(10);
+    let local_li = storage.read().unwrap().highest_local_li();
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    let legacy_peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+    ));
+
+    // a target 100 versions ahead of the local LI
+    let ahead_storage = storage.read().unwrap().clone();
+    let ahead_storage = Arc::new(RwLock::new(ahead_storage));
+    ahead_storage.write().unwrap().commit_new_txns(100);
+    let ahead_li = ahead_storage.read().unwrap().highest_local_li();
+    assert_eq!(ahead_li.ledger_info().version(), 120);
+
+    let mut serve =
+        |peer: &PeerNetworkId, known_version, current_epoch, target: &LedgerInfoWithSignatures| {
+            let request = StateSynchronizerMsg::GetChunkRequest(Box::new(GetChunkRequest::new(
+                known_version,
+                current_epoch,
+                250,
+                TargetType::TargetLedgerInfo(target.clone()),
+            )));
+            rt.block_on(coordinator.process_one_message(peer.clone(), request));
+            match next_msg(&mut network_reqs_rx).unwrap().1 {
+                StateSynchronizerMsg::GetChunkResponse(response) => *response,
+                _ => panic!("expected a chunk response"),
+            }
+        };
+
+    // the response is clamped to the local LI and flagged
+    let response = serve(&peer, 10, 2, &ahead_li);
+    assert_eq!(
+        response.response_li,
+        ResponseLedgerInfo::PartialLedgerInfo(local_li.clone())
+    );
+    assert_eq!(response.txn_list_with_proof.len(), 10);
+    assert_eq!(
+        response.txn_list_with_proof.first_transaction_version,
+        Some(11)
+    );
+
+    // a peer that doesn't support partial responses gets it clamped only
+    let response = serve(&legacy_peer, 10, 2, &ahead_li);
+    assert_eq!(
+        response.response_li,
+        ResponseLedgerInfo::VerifiableLedgerInfo(local_li.clone())
+    );
+    assert_eq!(response.txn_list_with_proof.len(), 10);
+
+    // a target equal to the local LI is served in full
+    let response = serve(&peer, 10, 2, &local_li);
+    assert_eq!(
+        response.response_li,
+        ResponseLedgerInfo::VerifiableLedgerInfo(local_li.clone())
+    );
+    assert_eq!(response.txn_list_with_proof.len(), 10);
+
+    // a requester in a past epoch is served up to the end of its epoch
+    let response = serve(&peer, 0, 1, &ahead_li);
+    assert!(matches!(
+        response.response_li,
+        ResponseLedgerInfo::VerifiableLedgerInfo(_)
+    ));
+    assert_eq!(response.response_li.version(), 10);
+    assert_eq!(response.txn_list_with_proof.len(), 10);
+}
+
+#[test]
+fn test_cancel_sync_request() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer, ConnectionOrigin::Outbound);
+
+    // nothing to cancel yet
+    assert!(coordinator.cancel_sync_request().is_err());
+
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li,
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+    assert!(coordinator.has_sync_request());
+
+    assert!(coordinator.cancel_sync_request().is_ok());
+    let err = callback_rcv.try_recv().unwrap().unwrap().unwrap_err();
+    assert!(err.to_string().contains("sync cancelled"));
+    assert!(!coordinator.has_sync_request());
+    assert!(coordinator.cancel_sync_request().is_err());
+}
+
+#[test]
+fn test_sync_request_fanout() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let mut config = StateSyncConfig::default();
+    config.sync_request_fanout = Some(2);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peers: Vec<_> = (0..3)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    for peer in &peers {
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    }
+    let (callback, _callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li,
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+
+    // a single sync round sends the same request to `sync_request_fanout` distinct peers
+    let mut recipients = HashSet::new();
+    while let Some((peer_id, msg)) = next_msg(&mut network_reqs_rx) {
+        match msg {
+            StateSynchronizerMsg::GetChunkRequest(request) => {
+                assert_eq!(request.known_version, 0);
+                assert_eq!(request.target().version(), Some(10));
+            }
+            _ => panic!("expected a chunk request"),
+        }
+        recipients.insert(peer_id);
+    }
+    assert_eq!(recipients.len(), 2);
+}
+
+#[test]
+fn test_epoch_change_notification() {
+    let storage = genesis_storage();
+    let (sender, mut notifications) = mpsc::channel(1);
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator_with_proxy(
+            RoleType::Validator,
+            StateSyncConfig::default(),
+            Waypoint::default(),
+            MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), storage.clone()),
+            Some(sender),
+        );
+    let mut rt = Runtime::new().unwrap();
+
+    // a commit within the current epoch is not notified
+    storage.write().unwrap().commit_new_txns(5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(notifications.try_next().is_err());
+
+    // a commit that ends the epoch is
+    commit_epoch(&storage, 5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert_eq!(
+        notifications.try_next().unwrap().unwrap(),
+        EpochChangeNotification {
+            old_epoch: 1,
+            new_epoch: 2,
+            version: 10,
+        }
+    );
+    assert!(notifications.try_next().is_err());
+}
+
+#[test]
+fn test_stale_chunk_penalty() {
+    let storage = genesis_storage();
+    let (txns, _) = storage.write().unwrap().commit_new_txns(10);
+    let li = storage.read().unwrap().highest_local_li();
+
+    // without the escalation, a stale chunk is penalized like any chunk that can't be applied
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+    for _ in 0..2 {
+        rt.block_on(
+            coordinator
+                .process_one_message(peer.clone(), chunk_response(txns.clone(), 1, li.clone())),
+        );
+    }
+    assert!((coordinator.peer_score(&peer).unwrap() - 64.0).abs() < 0.001);
+
+    let mut config = StateSyncConfig::default();
+    config.escalate_stale_chunk_penalty = true;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::FullNode, config, Waypoint::default(), storage);
+    let stale_peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    let ahead_peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(stale_peer.clone(), ConnectionOrigin::Outbound);
+    coordinator.process_new_peer(ahead_peer.clone(), ConnectionOrigin::Outbound);
+
+    // a chunk ahead of the synced version is penalized as before
+    rt.block_on(coordinator.process_one_message(
+        ahead_peer.clone(),
+        chunk_response(txns.clone(), 12, li.clone()),
+    ));
+    assert!((coordinator.peer_score(&ahead_peer).unwrap() - 80.0).abs() < 0.001);
+
+    // the penalty of stale chunks escalates while they keep coming
+    rt.block_on(coordinator.process_one_message(
+        stale_peer.clone(),
+        chunk_response(txns.clone(), 1, li.clone()),
+    ));
+    assert!((coordinator.peer_score(&stale_peer).unwrap() - 50.0).abs() < 0.001);
+    for _ in 0..2 {
+        rt.block_on(coordinator.process_one_message(
+            stale_peer.clone(),
+            chunk_response(txns.clone(), 1, li.clone()),
+        ));
+    }
+    assert!(coordinator.peer_score(&stale_peer).unwrap() < 10.0);
+}
+
+#[test]
+fn test_wait_for_version() {
+    let storage = genesis_storage();
+    let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let mut rt = Runtime::new().unwrap();
+    let (callback_10, mut waiter_10) = oneshot::channel();
+    let (callback_20, mut waiter_20) = oneshot::channel();
+    coordinator.add_version_waiter(10, callback_10);
+    coordinator.add_version_waiter(20, callback_20);
+
+    storage.write().unwrap().commit_new_txns(5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(waiter_10.try_recv().unwrap().is_none());
+    assert!(waiter_20.try_recv().unwrap().is_none());
+
+    storage.write().unwrap().commit_new_txns(10);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(waiter_10.try_recv().unwrap().unwrap().is_ok());
+    assert!(waiter_20.try_recv().unwrap().is_none());
+
+    storage.write().unwrap().commit_new_txns(5);
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    assert!(waiter_20.try_recv().unwrap().unwrap().is_ok());
+
+    // a version reached already is notified right away
+    let (callback, mut waiter) = oneshot::channel();
+    coordinator.add_version_waiter(15, callback);
+    assert!(waiter.try_recv().unwrap().unwrap().is_ok());
+}
+
+#[test]
+fn test_max_chunk_response_bytes() {
+    let storage = genesis_storage();
+    let (txns, _) = storage.write().unwrap().commit_new_txns(20);
+    let full_chunk =
+        TransactionListWithProof::new(txns, None, Some(1), TransactionListProof::new_empty());
+    let full_chunk_bytes = lcs::to_bytes(&full_chunk).unwrap().len() as u64;
+
+    // a third of the full chunk takes a few halvings, a single byte leaves a single transaction
+    for &(max_chunk_response_bytes, max_txns) in &[(0, 20), (full_chunk_bytes / 3, 5), (1, 1)] {
+        let mut config = StateSyncConfig::default();
+        config.max_chunk_response_bytes = max_chunk_response_bytes;
+        let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let peer = PeerNetworkId(network_id, PeerId::random());
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(
+            coordinator.process_one_message(peer.clone(), highest_available_request(0, 10_000)),
+        );
+
+        let (_, msg) = next_msg(&mut network_reqs_rx).unwrap();
+        match msg {
+            StateSynchronizerMsg::GetChunkResponse(response) => {
+                let chunk = response.txn_list_with_proof;
+                assert_eq!(chunk.first_transaction_version, Some(1));
+                assert_eq!(chunk.len() as u64, max_txns);
+                if max_chunk_response_bytes > 1 {
+                    assert!(
+                        lcs::to_bytes(&chunk).unwrap().len() as u64 <= max_chunk_response_bytes
+                    );
+                }
+            }
+            _ => panic!("expected a chunk response"),
+        }
+    }
+}
+
+#[test]
+fn test_target_li_long_poll() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut ahead_storage = storage.read().unwrap().clone();
+    ahead_storage.commit_new_txns(10);
+    let target_li = ahead_storage.highest_local_li();
+
+    let mut config = StateSyncConfig::default();
+    config.max_subscription_version_margin = 100;
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    let request = |known_version| {
+        StateSynchronizerMsg::GetChunkRequest(Box::new(GetChunkRequest::new(
+            known_version,
+            1,
+            250,
+            TargetType::TargetLedgerInfoWithTimeout {
+                target_li: target_li.clone(),
+                timeout_ms: 10_000,
+            },
+        )))
+    };
+
+    // a subscriber too far ahead of the local version doesn't get a subscription slot
+    rt.block_on(coordinator.process_one_message(peer.clone(), request(111)));
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    assert!(coordinator.subscriptions_snapshot().is_empty());
+
+    // there is nothing new for the peer yet, so its request becomes a subscription
+    rt.block_on(coordinator.process_one_message(peer.clone(), request(10)));
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    let subscriptions = coordinator.subscriptions_snapshot();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].peer, peer);
+
+    // the subscription is served relative to the target once it's committed locally
+    *storage.write().unwrap() = ahead_storage;
+    rt.block_on(coordinator.process_commit(vec![], None, None))
+        .unwrap();
+    let (recipient, msg) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(recipient, peer.peer_id());
+    match msg {
+        StateSynchronizerMsg::GetChunkResponse(response) => {
+            assert!(!matches!(
+                response.response_li,
+                ResponseLedgerInfo::PartialLedgerInfo(_)
+            ));
+            assert_eq!(response.response_li.version(), 20);
+            assert_eq!(
+                response.txn_list_with_proof.first_transaction_version,
+                Some(11)
+            );
+            assert_eq!(response.txn_list_with_proof.len(), 10);
+        }
+        _ => panic!("expected a chunk response"),
+    }
+    assert!(coordinator.subscriptions_snapshot().is_empty());
+}
+
+#[test]
+fn test_sync_request_long_poll() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let target_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let mut config = StateSyncConfig::default();
+    config.sync_request_fanout = Some(2);
+    config.sync_request_long_poll_timeout_ms = 5_000;
+    let base_timeout = Duration::from_millis(2 * config.tick_interval_ms);
+    let long_poll_timeout = Duration::from_millis(config.tick_interval_ms + 5_000);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        config,
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    let legacy_peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+    ));
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    coordinator.process_new_peer(legacy_peer.clone(), ConnectionOrigin::Outbound);
+    assert_eq!(coordinator.request_timeout(0), base_timeout);
+
+    let (callback, _callback_rcv) = oneshot::channel();
+    coordinator
+        .request_sync(SyncRequest {
+            callback,
+            target: target_li.clone(),
+            last_progress_tst: SystemTime::now(),
+        })
+        .unwrap();
+
+    // only the peers that advertised support for it are sent the long poll target
+    let mut recipients = HashSet::new();
+    while let Some((peer_id, msg)) = next_msg(&mut network_reqs_rx) {
+        let expected_target = if peer_id == peer.peer_id() {
+            TargetType::TargetLedgerInfoWithTimeout {
+                target_li: target_li.clone(),
+                timeout_ms: 5_000,
+            }
+        } else {
+            TargetType::TargetLedgerInfo(target_li.clone())
+        };
+        match msg {
+            StateSynchronizerMsg::GetChunkRequest(request) => {
+                assert_eq!(request.target(), &expected_target);
+            }
+            _ => panic!("expected a chunk request"),
+        }
+        recipients.insert(peer_id);
+    }
+    assert_eq!(recipients.len(), 2);
+    assert_eq!(coordinator.request_timeout(0), long_poll_timeout);
+
+    // the base timeout is restored once the sync request is done
+    coordinator.cancel_sync_request().unwrap();
+    assert_eq!(coordinator.request_timeout(0), base_timeout);
+}
+
+#[test]
+fn test_sync_duration_label() {
+    let li = genesis_storage().read().unwrap().highest_local_li();
+    let labeled_responses = vec![
+        (
+            ResponseLedgerInfo::LedgerInfoForWaypoint {
+                waypoint_li: li.clone(),
+                end_of_epoch_li: None,
+            },
+            counters::WAYPOINT_RESPONSE_LABEL,
+        ),
+        (
+            ResponseLedgerInfo::LedgerInfoForWaypointWithEpochProofs {
+                waypoint_li: li.clone(),
+                end_of_epoch_li: None,
+                epoch_proofs: vec![],
+            },
+            counters::WAYPOINT_RESPONSE_LABEL,
+        ),
+        (
+            ResponseLedgerInfo::VerifiableLedgerInfo(li.clone()),
+            counters::VERIFIABLE_RESPONSE_LABEL,
+        ),
+        (
+            ResponseLedgerInfo::ProgressiveLedgerInfo {
+                target_li: li,
+                highest_li: None,
+            },
+            counters::PROGRESSIVE_RESPONSE_LABEL,
+        ),
+    ];
+    for (response_li, label) in labeled_responses {
+        assert_eq!(sync_duration_label(&response_li), label);
+    }
+
+    // the duration of an applied chunk is observed under the label of its response
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let histogram = counters::SYNC_PROGRESS_DURATION_BY_TYPE
+        .with_label_values(&[counters::VERIFIABLE_RESPONSE_LABEL]);
+    let sample_count = histogram.get_sample_count();
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(txns, 1, li)));
+    assert!(histogram.get_sample_count() > sample_count);
+}
+
+#[test]
+fn test_chunk_beyond_response_li() {
+    let upstream_storage = genesis_storage();
+    let (mut txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+    let (more_txns, _) = upstream_storage.write().unwrap().commit_new_txns(5);
+    txns.extend(more_txns);
+
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the 15 transactions overshoot the LI at version 10: nothing is executed and the chunk is
+    // penalized as invalid
+    rt.block_on(coordinator.process_one_message(peer.clone(), chunk_response(txns, 1, li)));
+    assert_eq!(storage.read().unwrap().version(), 0);
+    assert!((coordinator.peer_score(&peer).unwrap() - 80.0).abs() < 0.001);
+}
+
+#[test]
+fn test_shutdown() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    let storage = genesis_storage();
+    let executor_proxy = MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), storage);
+    let initial_state = executor_proxy.get_local_storage_state().unwrap();
+    let (mut coordinator_sender, coordinator_receiver) = mpsc::unbounded();
+    let (mempool_sender, _mempool_receiver) = mpsc::channel(1_024);
+    let coordinator = SyncCoordinator::new(
+        coordinator_receiver,
+        mempool_sender,
+        HashMap::new(),
+        RoleType::Validator,
+        waypoint,
+        StateSyncConfig::default(),
+        UpstreamConfig::default(),
+        executor_proxy,
+        initial_state,
+        None,
+        None,
+    );
+    let mut rt = Runtime::new().unwrap();
+    let coordinator_handle = rt.spawn(coordinator.start(vec![]));
+
+    rt.block_on(async move {
+        // the waypoint is out of reach, so the listener stays pending
+        let (listener, listener_rcv) = oneshot::channel();
+        coordinator_sender
+            .send(CoordinatorMessage::WaitInitialize(listener))
+            .await
+            .unwrap();
+        let (shutdown_ack, shutdown_ack_rcv) = oneshot::channel();
+        coordinator_sender
+            .send(CoordinatorMessage::Shutdown(shutdown_ack))
+            .await
+            .unwrap();
+
+        shutdown_ack_rcv.await.unwrap();
+        assert!(listener_rcv.await.unwrap().is_err());
+        timeout(Duration::from_secs(5), coordinator_handle)
+            .await
+            .unwrap()
+            .unwrap();
+    });
+}
+
+#[test]
+fn test_shutdown_flushes_commit_notifications() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.mempool_commit_batch_size = 25;
+    config.max_commit_pipeline_depth = Some(4);
+    let (
+        mut coordinator,
+        network_id,
+        _network_reqs_rx,
+        mut mempool_requests,
+        mut coordinator_sender,
+    ) = coordinator_with_client(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), storage.clone()),
+        None,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the chunk commit is below the batch size, so mempool is not notified of it yet
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(txns, 1, li)));
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert!(mempool_requests.try_next().is_err());
+
+    let coordinator_handle = rt.spawn(coordinator.start(vec![]));
+    rt.block_on(async move {
+        let (shutdown_ack, mut shutdown_ack_rcv) = oneshot::channel();
+        coordinator_sender
+            .send(CoordinatorMessage::Shutdown(shutdown_ack))
+            .await
+            .unwrap();
+
+        // the batched commit is flushed on shutdown, which waits for mempool to ACK it
+        let notification = mempool_requests.next().await.unwrap();
+        assert_eq!(notification.source, CommitSource::StateSync);
+        assert_eq!(notification.transactions.len(), 10);
+        assert!(timeout(Duration::from_millis(200), &mut shutdown_ack_rcv)
+            .await
+            .is_err());
+
+        notification
+            .callback
+            .send(Ok(CommitResponse { msg: "".into() }))
+            .unwrap();
+        shutdown_ack_rcv.await.unwrap();
+        timeout(Duration::from_secs(5), coordinator_handle)
+            .await
+            .unwrap()
+            .unwrap();
+    });
+}
+
+#[test]
+fn test_mempool_commit_ack_timeout() {
+    for &fail_commit_on_mempool_timeout in &[false, true] {
+        let storage = genesis_storage();
+        let mut config = StateSyncConfig::default();
+        config.mempool_commit_ack_timeout_ms = 10;
+        config.fail_commit_on_mempool_timeout = fail_commit_on_mempool_timeout;
+        // the mempool receiver holds on to the notifications without ever ACKing them
+        let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            config,
+            Waypoint::default(),
+            storage.clone(),
+        );
+        let mut rt = Runtime::new().unwrap();
+
+        storage.write().unwrap().commit_new_txns(10);
+        let (callback, callback_rcv) = oneshot::channel();
+        rt.block_on(coordinator.process_commit(vec![], Some(callback), None))
+            .unwrap();
+        let result = rt.block_on(callback_rcv).unwrap();
+        if fail_commit_on_mempool_timeout {
+            assert!(result.is_err());
+        } else {
+            let CommitResponse { msg } = result.unwrap();
+            assert!(msg.contains("did not receive ACK"));
+        }
+    }
+}
+
+#[test]
+fn test_duplicate_subscription() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let duplicate_count = || {
+        counters::DUPLICATE_SUBSCRIPTION_COUNT
+            .with_label_values(&[&peer.raw_network_id().to_string()])
+            .get()
+    };
+    let initial_count = duplicate_count();
+    let mut rt = Runtime::new().unwrap();
+
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), highest_available_request(10, 1_000)),
+    );
+    let first_remaining = coordinator.subscriptions_snapshot()[0].remaining;
+    std::thread::sleep(Duration::from_millis(50));
+    rt.block_on(
+        coordinator.process_one_message(peer.clone(), highest_available_request(10, 10_000)),
+    );
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the repeated request only refreshed the expiration of the subscription
+    let subscriptions = coordinator.subscriptions_snapshot();
+    assert_eq!(subscriptions.len(), 1);
+    assert!(subscriptions[0].remaining > first_remaining);
+    assert!(duplicate_count() > initial_count);
+}
+
+#[test]
+fn test_epoch_ending_request() {
+    let storage = genesis_storage();
+    commit_epoch(&storage, 10);
+    storage.write().unwrap().commit_new_txns(20);
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage,
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    let mut rt = Runtime::new().unwrap();
+    let epoch_ending_request = |known_version, epoch| {
+        StateSynchronizerMsg::GetChunkRequest(Box::new(GetChunkRequest::new(
+            known_version,
+            1,
+            250,
+            TargetType::EpochEnding { epoch },
+        )))
+    };
+
+    // the completed epoch is served up to its end, relative to its epoch-ending LI
+    rt.block_on(coordinator.process_one_message(peer.clone(), epoch_ending_request(0, 1)));
+    let (recipient, msg) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(recipient, peer.peer_id());
+    match msg {
+        StateSynchronizerMsg::GetChunkResponse(response) => {
+            match &response.response_li {
+                ResponseLedgerInfo::VerifiableLedgerInfo(li) => {
+                    assert_eq!(li.ledger_info().version(), 10);
+                    assert!(li.ledger_info().ends_epoch());
+                }
+                _ => panic!("expected a verifiable ledger info"),
+            }
+            assert_eq!(
+                response.txn_list_with_proof.first_transaction_version,
+                Some(1)
+            );
+            assert_eq!(response.txn_list_with_proof.len(), 10);
+        }
+        _ => panic!("expected a chunk response"),
+    }
+
+    // the current epoch has not ended yet
+    rt.block_on(coordinator.process_one_message(peer.clone(), epoch_ending_request(10, 2)));
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the requester already has the whole epoch
+    rt.block_on(coordinator.process_one_message(peer.clone(), epoch_ending_request(10, 1)));
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+    assert!(!coordinator.is_subscribed(&peer));
+}
+
+#[test]
+fn test_unrequested_response() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let requested_peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    let other_peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(requested_peer.clone(), ConnectionOrigin::Outbound);
+    coordinator.process_new_peer(other_peer.clone(), ConnectionOrigin::Outbound);
+    // the outstanding request was only sent to the first peer
+    let (recipient, _) = next_msg(&mut network_reqs_rx).unwrap();
+    assert_eq!(recipient, requested_peer.peer_id());
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the chunk of the other upstream is valid, so it's applied, but its sender is penalized
+    // instead of credited
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(other_peer.clone(), chunk_response(txns, 1, li)));
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert!((coordinator.peer_score(&other_peer).unwrap() - 95.0).abs() < 0.001);
+    assert!((coordinator.peer_score(&requested_peer).unwrap() - 100.0).abs() < 0.001);
+}
+
+#[test]
+fn test_sync_metrics() {
+    let upstream_storage = genesis_storage();
+    let (first_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let first_li = upstream_storage.read().unwrap().highest_local_li();
+    let (second_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let second_li = upstream_storage.read().unwrap().highest_local_li();
+    upstream_storage.write().unwrap().commit_new_txns(30);
+    let highest_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        genesis_storage(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let metrics = coordinator.sync_metrics();
+    assert_eq!(metrics.synced_version, 0);
+    assert_eq!(metrics.highest_target_version, None);
+    assert!(metrics.versions_per_sec.abs() < std::f64::EPSILON);
+
+    // the first chunk leaves the highest LI pending
+    let mut rt = Runtime::new().unwrap();
+    let chunk = GetChunkResponse::new(
+        ResponseLedgerInfo::ProgressiveLedgerInfo {
+            target_li: first_li,
+            highest_li: Some(highest_li),
+        },
+        TransactionListWithProof::new(first_txns, None, Some(1), TransactionListProof::new_empty()),
+    );
+    rt.block_on(coordinator.process_one_message(
+        peer.clone(),
+        StateSynchronizerMsg::GetChunkResponse(Box::new(chunk)),
+    ));
+    std::thread::sleep(Duration::from_millis(10));
+    rt.block_on(coordinator.process_one_message(peer, chunk_response(second_txns, 11, second_li)));
+
+    let metrics = coordinator.sync_metrics();
+    assert_eq!(metrics.synced_version, 20);
+    assert_eq!(metrics.highest_target_version, Some(50));
+    assert_eq!(metrics.lag(), 30);
+    assert!(metrics.versions_per_sec > 0.0);
+}
+
+#[test]
+fn test_persisted_target_li() {
+    let upstream_storage = genesis_storage();
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let valid_li = upstream_storage.read().unwrap().highest_local_li();
+    // the LI of an epoch the node hasn't reached cannot be verified by its epoch state
+    commit_epoch(&upstream_storage, 10);
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let invalid_li = upstream_storage.read().unwrap().highest_local_li();
+
+    for (persisted_target_li, expected_target_li) in
+        vec![(valid_li.clone(), Some(valid_li)), (invalid_li, None)]
+    {
+        let executor_proxy =
+            MockExecutorProxy::new(SynchronizerEnvHelper::default_handler(), genesis_storage());
+        let initial_state = executor_proxy.get_local_storage_state().unwrap();
+        let (_coordinator_sender, coordinator_receiver) = mpsc::unbounded();
+        let (mempool_sender, _mempool_receiver) = mpsc::channel(1_024);
+        let coordinator = SyncCoordinator::new(
+            coordinator_receiver,
+            mempool_sender,
+            HashMap::new(),
+            RoleType::FullNode,
+            Waypoint::default(),
+            StateSyncConfig::default(),
+            UpstreamConfig::default(),
+            executor_proxy,
+            initial_state,
+            None,
+            Some(persisted_target_li),
+        );
+        assert_eq!(coordinator.best_target_li(), expected_target_li);
+    }
+}
+
+#[test]
+fn test_mempool_commit_batching() {
+    let upstream_storage = genesis_storage();
+    let mut chunks = vec![];
+    for _ in 0..4 {
+        let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+        chunks.push((txns, upstream_storage.read().unwrap().highest_local_li()));
+    }
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.mempool_commit_batch_size = 25;
+    let (mut coordinator, network_id, _network_reqs_rx, mut mempool_requests) = coordinator(
+        RoleType::FullNode,
+        config,
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    let mut rt = Runtime::new().unwrap();
+
+    // the commits of the first three chunks coalesce into a single notification once the batch
+    // size is reached
+    for (idx, (txns, li)) in chunks.into_iter().enumerate() {
+        rt.block_on(
+            coordinator
+                .process_one_message(peer.clone(), chunk_response(txns, idx as u64 * 10 + 1, li)),
+        );
+        if idx == 2 {
+            let notification = mempool_requests.try_next().unwrap().unwrap();
+            assert_eq!(notification.source, CommitSource::StateSync);
+            assert_eq!(notification.transactions.len(), 30);
+        } else {
+            assert!(mempool_requests.try_next().is_err());
+        }
+    }
+    assert_eq!(storage.read().unwrap().version(), 40);
+    assert!(mempool_requests.try_next().is_err());
+
+    // the remaining commit is flushed by the timer
+    rt.block_on(coordinator.flush_mempool_commit_batch());
+    let notification = mempool_requests.try_next().unwrap().unwrap();
+    assert_eq!(notification.transactions.len(), 10);
+    assert!(mempool_requests.try_next().is_err());
+}
+
+#[test]
+fn test_waypoint_mismatch() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+
+    // the peers all serve a history whose epoch-ending LI doesn't match the waypoint
+    let other_storage = genesis_storage();
+    commit_epoch(&other_storage, 10);
+    let mismatching_li = other_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let txns = other_storage.read().unwrap().get_chunk(1, 10, 10);
+    let response = StateSynchronizerMsg::GetChunkResponse(Box::new(GetChunkResponse::new(
+        ResponseLedgerInfo::LedgerInfoForWaypoint {
+            waypoint_li: mismatching_li,
+            end_of_epoch_li: None,
+        },
+        TransactionListWithProof::new(txns, None, Some(1), TransactionListProof::new_empty()),
+    )));
+
+    let storage = genesis_storage();
+    let mut config = StateSyncConfig::default();
+    config.waypoint_mismatch_peer_threshold = 2;
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::Validator, config, waypoint, storage.clone());
+    let peers: Vec<_> = (0..2)
+        .map(|_| PeerNetworkId(network_id.clone(), PeerId::random()))
+        .collect();
+    for peer in peers.iter() {
+        coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    }
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    let mut rt = Runtime::new().unwrap();
+
+    // a single peer failing the verification, even repeatedly, may just be a bad peer
+    for _ in 0..2 {
+        rt.block_on(coordinator.process_one_message(peers[0].clone(), response.clone()));
+    }
+    assert!(callback_rcv.try_recv().unwrap().is_none());
+
+    // once distinct peers fail it at the same version, the waypoint itself is likely wrong
+    rt.block_on(coordinator.process_one_message(peers[1].clone(), response));
+    let error = callback_rcv.try_recv().unwrap().unwrap().unwrap_err();
+    assert!(error.to_string().contains("misconfigured"));
+    assert_eq!(storage.read().unwrap().version(), 0);
+
+    // later listeners learn about the mismatch right away
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert!(callback_rcv.try_recv().unwrap().unwrap().is_err());
+}
+
+#[test]
+fn test_verify_chunk() {
+    let upstream_storage = genesis_storage();
+    let (txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let valid_li = upstream_storage.read().unwrap().highest_local_li();
+    let (next_txns, _) = upstream_storage.write().unwrap().commit_new_txns(10);
+    let next_li = upstream_storage.read().unwrap().highest_local_li();
+    // the LI of an epoch the node hasn't reached cannot be verified by its epoch state
+    commit_epoch(&upstream_storage, 10);
+    upstream_storage.write().unwrap().commit_new_txns(10);
+    let tampered_li = upstream_storage.read().unwrap().highest_local_li();
+
+    let storage = genesis_storage();
+    let (coordinator, _network_id, _network_reqs_rx, _mempool_rx) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        Waypoint::default(),
+        storage.clone(),
+    );
+    let response = |txns: &Vec<Transaction>, first_version, li| {
+        GetChunkResponse::new(
+            ResponseLedgerInfo::VerifiableLedgerInfo(li),
+            TransactionListWithProof::new(
+                txns.clone(),
+                None,
+                Some(first_version),
+                TransactionListProof::new_empty(),
+            ),
+        )
+    };
+
+    assert!(coordinator
+        .verify_chunk(&response(&txns, 1, valid_li))
+        .is_ok());
+    assert!(coordinator
+        .verify_chunk(&response(&txns, 1, tampered_li))
+        .is_err());
+    // a chunk is checked against the synced version, not against the version it claims to start
+    // at: the node is at version 0, so a chunk starting at version 11 leaves a gap
+    assert!(coordinator
+        .verify_chunk(&response(&next_txns, 11, next_li))
+        .is_err());
+    // the dry run leaves the local state untouched
+    assert_eq!(storage.read().unwrap().version(), 0);
+    assert_eq!(coordinator.sync_metrics().synced_version, 0);
+}
+
+#[test]
+fn test_prioritize_serving() {
+    let storage = genesis_storage();
+    storage.write().unwrap().commit_new_txns(10);
+    let mut config = StateSyncConfig::default();
+    config.prioritize_serving = true;
+    // every progress check retries the chunk request to upstream
+    config.tick_interval_ms = 0;
+    config.long_poll_timeout_ms = 0;
+    let (mut coordinator, network_id, mut network_reqs_rx, _mempool_requests) =
+        coordinator(RoleType::FullNode, config, Waypoint::default(), storage);
+    let upstream_peer = PeerNetworkId(network_id.clone(), PeerId::random());
+    coordinator.process_new_peer(upstream_peer.clone(), ConnectionOrigin::Outbound);
+    assert!(next_msg(&mut network_reqs_rx).is_some());
+    let mut rt = Runtime::new().unwrap();
+
+    // a flood of downstream chunk requests is pending when the tick fires, with another event
+    // in the middle of it
+    let downstream_peers: Vec<_> = (0..5).map(|_| PeerId::random()).collect();
+    let mut events: Vec<_> = downstream_peers
+        .iter()
+        .map(|peer_id| {
+            (
+                network_id.clone(),
+                Event::Message(*peer_id, highest_available_request(0, 0)),
+            )
+        })
+        .collect();
+    let other_peer = PeerId::random();
+    events.insert(
+        3,
+        (
+            network_id.clone(),
+            Event::Message(
+                other_peer,
+                StateSynchronizerMsg::Capabilities(SyncCapabilities::local()),
+            ),
+        ),
+    );
+    let mut network_events = stream::iter(events).peekable();
+    rt.block_on(coordinator.process_tick(&mut network_events));
+
+    // the downstream peers whose requests are ahead of the other event are served before the
+    // chunk request is retried to upstream
+    for peer_id in &downstream_peers[..3] {
+        match next_msg(&mut network_reqs_rx).unwrap() {
+            (recipient, StateSynchronizerMsg::GetChunkResponse(response)) => {
+                assert_eq!(recipient, *peer_id);
+                assert_eq!(response.txn_list_with_proof.len(), 10);
+            }
+            _ => panic!("expected a chunk response"),
+        }
+    }
+    match next_msg(&mut network_reqs_rx).unwrap() {
+        (recipient, StateSynchronizerMsg::GetChunkRequest(request)) => {
+            assert_eq!(recipient, upstream_peer.peer_id());
+            assert_eq!(request.known_version, 10);
+        }
+        _ => panic!("expected a chunk request"),
+    }
+    assert!(next_msg(&mut network_reqs_rx).is_none());
+
+    // the other event and the requests behind it are left to the event loop
+    match rt.block_on(network_events.next()) {
+        Some((_, Event::Message(peer_id, StateSynchronizerMsg::Capabilities(_)))) => {
+            assert_eq!(peer_id, other_peer);
+        }
+        _ => panic!("expected the capabilities message"),
+    }
+    assert_eq!(rt.block_on(network_events.count()), 2);
+}
+
+#[test]
+fn test_update_waypoint() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_li = upstream_storage
+        .read()
+        .unwrap()
+        .get_epoch_ending_ledger_info(10)
+        .unwrap();
+    let waypoint = Waypoint::new_epoch_boundary(waypoint_li.ledger_info()).unwrap();
+    // the node is bootstrapped with the waypoint of another history
+    let other_storage = genesis_storage();
+    commit_epoch(&other_storage, 10);
+    let wrong_waypoint = Waypoint::new_epoch_boundary(
+        other_storage
+            .read()
+            .unwrap()
+            .get_epoch_ending_ledger_info(10)
+            .unwrap()
+            .ledger_info(),
+    )
+    .unwrap();
+
+    let storage = genesis_storage();
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::Validator,
+        StateSyncConfig::default(),
+        wrong_waypoint,
+        storage.clone(),
+    );
+    let peer = PeerNetworkId(network_id, PeerId::random());
+    coordinator.process_new_peer(peer.clone(), ConnectionOrigin::Outbound);
+    coordinator.update_waypoint(waypoint).unwrap();
+    let (callback, mut callback_rcv) = oneshot::channel();
+    coordinator.set_initialization_listener(callback);
+    assert!(callback_rcv.try_recv().unwrap().is_none());
+
+    // the chunk matching the new waypoint is applied and the listener is notified
+    let txns = upstream_storage.read().unwrap().get_chunk(1, 10, 10);
+    let response = StateSynchronizerMsg::GetChunkResponse(Box::new(GetChunkResponse::new(
+        ResponseLedgerInfo::LedgerInfoForWaypoint {
+            waypoint_li,
+            end_of_epoch_li: None,
+        },
+        TransactionListWithProof::new(txns, None, Some(1), TransactionListProof::new_empty()),
+    )));
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(coordinator.process_one_message(peer, response));
+    assert_eq!(storage.read().unwrap().version(), 10);
+    assert_eq!(
+        callback_rcv.try_recv().unwrap().unwrap().unwrap(),
+        InitializationResult::Reached
+    );
+
+    // the history synced to the waypoint isn't rewritten
+    let error = coordinator.update_waypoint(wrong_waypoint).unwrap_err();
+    assert!(error.to_string().contains("already initialized"));
+}
+
+#[test]
+fn test_update_waypoint_reached_already() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_at = |storage: &Arc<RwLock<MockStorage>>, version| {
+        let li = storage
+            .read()
+            .unwrap()
+            .get_epoch_ending_ledger_info(version)
+            .unwrap();
+        Waypoint::new_epoch_boundary(li.ledger_info()).unwrap()
+    };
+    let other_storage = genesis_storage();
+    commit_epoch(&other_storage, 10);
+    let wrong_waypoint = waypoint_at(&other_storage, 10);
+
+    for &(version, matching) in &[(10, true), (20, true), (10, false)] {
+        let storage = genesis_storage();
+        let (mut coordinator, _network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+            RoleType::Validator,
+            StateSyncConfig::default(),
+            wrong_waypoint,
+            storage.clone(),
+        );
+        let (callback, mut callback_rcv) = oneshot::channel();
+        coordinator.set_initialization_listener(callback);
+        assert!(callback_rcv.try_recv().unwrap().is_none());
+
+        // the storage is restored from a backup of the history up to version 20 meanwhile
+        *storage.write().unwrap() = upstream_storage.read().unwrap().clone();
+        if matching {
+            // the listener is notified as soon as the waypoint is updated
+            coordinator
+                .update_waypoint(waypoint_at(&upstream_storage, version))
+                .unwrap();
+            assert_eq!(
+                callback_rcv.try_recv().unwrap().unwrap().unwrap(),
+                InitializationResult::Reached
+            );
+        } else {
+            // a waypoint that doesn't match the synced history is rejected
+            let error = coordinator.update_waypoint(wrong_waypoint).unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("does not match the local storage"));
+            assert!(callback_rcv.try_recv().unwrap().is_none());
+        }
+    }
+}
+
+#[test]
+fn test_update_waypoint_backward() {
+    let upstream_storage = genesis_storage();
+    commit_epoch(&upstream_storage, 10);
+    commit_epoch(&upstream_storage, 10);
+    let waypoint_at = |version| {
+        let li = upstream_storage
+            .read()
+            .unwrap()
+            .get_epoch_ending_ledger_info(version)
+            .unwrap();
+        Waypoint::new_epoch_boundary(li.ledger_info()).unwrap()
+    };
+
+    let (mut coordinator, network_id, _network_reqs_rx, _mempool_requests) = coordinator(
+        RoleType::FullNode,
+        StateSyncConfig::default(),
+        waypoint_at(20),
+        genesis_storage(),
+    );
+    let error = coordinator.update_waypoint(waypoint_at(10)).unwrap_err();
+    assert!(error.to_string().contains("backward"));
+
+    // the node keeps syncing to the current waypoint
+    coordinator.process_new_peer(
+        PeerNetworkId(network_id, PeerId::random()),
+        ConnectionOrigin::Outbound,
+    );
+    match coordinator.last_sent_target() {
+        Some(TargetType::Waypoint(version)) => assert_eq!(version, 20),
+        target => panic!("unexpected target {:?}", target),
+    }
+}
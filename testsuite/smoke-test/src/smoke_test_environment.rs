@@ -24,7 +24,7 @@ impl SmokeTestEnvironment {
     pub fn new_with_chunk_limit(num_validators: usize, chunk_limit: u64) -> Self {
         ::libra_logger::Logger::new().init();
         let mut template = NodeConfig::default_for_validator();
-        template.state_sync.chunk_limit = chunk_limit;
+        template.state_sync.chunk_limit = Some(chunk_limit);
 
         let validator_swarm = LibraSwarm::configure_validator_swarm(
             &workspace_builder::get_libra_node_with_failpoints(),